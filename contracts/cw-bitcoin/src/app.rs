@@ -1,35 +1,80 @@
-use crate::adapter::Adapter;
-use crate::checkpoint::Checkpoint;
-use crate::interface::{BitcoinConfig, ChangeRates, Dest, Validator, Xpub};
+use crate::adapter::{Adapter, HashBinary};
+use crate::checkpoint::{Checkpoint, CheckpointStatus};
+use crate::constants::OP_RETURN_DEPOSIT_COMMITMENT;
+use crate::header::{HeaderList, WrappedHeader};
+use crate::interface::{BitcoinConfig, ChangeRates, DepositRecord, Dest, Validator, Xpub};
 use crate::signatory::SignatoryKeys;
 use crate::state::{
-    get_full_btc_denom, get_validators, BITCOIN_CONFIG, CONFIRMED_INDEX, FEE_POOL,
-    FIRST_UNHANDLED_CONFIRMED_INDEX, SIGNERS, SIG_KEYS, VALIDATORS, XPUBS,
+    get_full_btc_denom, get_validators, BITCOIN_CONFIG, BLOCK_DEPOSITS, CONFIG, CONFIRMED_INDEX,
+    DEPOSIT_HEIGHTS, DEPOSIT_HISTORY, FEE_POOL, FIRST_UNHANDLED_CONFIRMED_INDEX,
+    INVALIDATED_DEPOSITS, JAILED, OBSERVED_FEE_RATE, RECOVERY_SCRIPTS, SIGNATORY_REWARDS, SIGNERS,
+    SIG_KEYS, SMALL_DEPOSITS, VALIDATORS, WITHDRAWALS, XPUBS,
 };
-use crate::threshold_sig;
+use crate::units::{MSats, Sats};
 
 use super::checkpoint::Input;
+use super::emergency::disbursal_txs;
 use super::recovery::{RecoveryTxInput, RecoveryTxs};
 
 use super::checkpoint::BatchType;
 use super::checkpoint::CheckpointQueue;
 use super::error::{ContractError, ContractResult};
 use super::header::HeaderQueue;
-use bitcoin::Script;
+use bitcoin::blockdata::script::Instruction;
 use bitcoin::{util::merkleblock::PartialMerkleTree, Transaction};
+use bitcoin::{BlockHash, Script};
 use cosmwasm_schema::serde::{Deserialize, Serialize};
-use cosmwasm_std::{Addr, Coin, Env, Order, Storage, Uint128};
+use cosmwasm_std::{Addr, Coin, Env, Order, QuerierWrapper, StdResult, Storage, Uint128};
 
 use super::outpoint_set::OutpointSet;
-use super::signatory::SignatorySet;
-use std::collections::HashMap;
+use std::str::FromStr;
 
 pub const NETWORK: ::bitcoin::Network = ::bitcoin::Network::Bitcoin;
 
-/// Calculates the bridge fee for a deposit of the given amount of BTC, in
-/// satoshis.
-pub fn calc_deposit_fee(_: Uint128) -> u64 {
-    0
+/// Calculates the bridge fee for a deposit of the given amount of nBTC.
+pub fn calc_deposit_fee(_: MSats) -> MSats {
+    MSats(0)
+}
+
+/// How a relayed deposit was handled by `Bitcoin::relay_deposit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepositOutcome {
+    /// Added to the building checkpoint, to be credited once it is signed.
+    Credited,
+    /// Past its deposit timeout, so a recovery transaction was created.
+    Expired,
+    /// Below `min_deposit_amount` after fees, so it was not credited.
+    TooSmall,
+}
+
+/// Checks that a withdrawal destination is a standard output script which the
+/// checkpoint transaction can pay to: P2PKH, P2SH, P2WPKH, P2WSH or P2TR.
+pub fn validate_withdrawal_script(script: &Script) -> ContractResult<()> {
+    if script.is_p2pkh()
+        || script.is_p2sh()
+        || script.is_v0_p2wpkh()
+        || script.is_v0_p2wsh()
+        || script.is_v1_p2tr()
+    {
+        return Ok(());
+    }
+
+    Err(ContractError::OutputError(format!(
+        "Unsupported withdrawal script: {}",
+        script.asm()
+    )))
+}
+
+/// Returns the data pushed by the first OP_RETURN output of `tx`, if any.
+pub fn op_return_data(tx: &Transaction) -> Option<&[u8]> {
+    let output = tx
+        .output
+        .iter()
+        .find(|output| output.script_pubkey.is_op_return())?;
+    match output.script_pubkey.instructions().nth(1) {
+        Some(Ok(Instruction::PushBytes(data))) => Some(data),
+        _ => None,
+    }
 }
 
 /// The main structure where Bitcoin bridge state is held.
@@ -133,11 +178,11 @@ impl Bitcoin {
         signer: Addr,
         signatory_key: Xpub,
     ) -> ContractResult<()> {
-        let consensus_key = SIGNERS
-            .load(store, signer.as_str())
-            .map_err(|_| ContractError::App("Signer does not have a consensus key".to_string()))?;
+        let consensus_key = SIGNERS.load(store, signer.as_str()).map_err(|_| {
+            ContractError::Signer("Signer does not have a consensus key".to_string())
+        })?;
 
-        if signatory_key.network != self.network() {
+        if signatory_key.network != self.network(store)? {
             return Err(ContractError::App(
                 "Signatory key network does not match network".to_string(),
             ));
@@ -149,17 +194,48 @@ impl Bitcoin {
         Ok(())
     }
 
+    /// Registers the script the account's funds should be paid to if the
+    /// network makes an emergency disbursal. The script must be a standard
+    /// output script.
+    pub fn set_recovery_script(
+        &mut self,
+        store: &mut dyn Storage,
+        address: &Addr,
+        script: Adapter<Script>,
+    ) -> ContractResult<()> {
+        validate_withdrawal_script(&script).map_err(|_| {
+            ContractError::BitcoinRecoveryScript(format!(
+                "Unsupported recovery script: {}",
+                script.asm()
+            ))
+        })?;
+        RECOVERY_SCRIPTS.save(store, address.as_str(), &script)?;
+
+        Ok(())
+    }
+
+    /// The recovery script registered for the account, if any, to be paid
+    /// its funds when building emergency disbursal outputs.
+    pub fn recovery_script(
+        &self,
+        store: &dyn Storage,
+        address: &Addr,
+    ) -> ContractResult<Option<Script>> {
+        Ok(RECOVERY_SCRIPTS
+            .may_load(store, address.as_str())?
+            .map(Adapter::into_inner))
+    }
+
     pub fn calc_minimum_deposit_fees(
         &self,
         store: &dyn Storage,
         input_vsize: u64,
         fee_rate: u64,
-    ) -> ContractResult<u64> {
+    ) -> ContractResult<MSats> {
         let config = self.config(store)?;
-        Ok(
-            input_vsize * fee_rate * self.checkpoints.config(store).user_fee_factor / 10_000
-                * config.units_per_sat,
-        )
+        config.sat_to_units(Sats(
+            input_vsize * fee_rate * self.checkpoints.config(store).user_fee_factor / 10_000,
+        ))
     }
 
     pub fn calc_minimum_withdrawal_fees(
@@ -167,13 +243,117 @@ impl Bitcoin {
         store: &dyn Storage,
         script_pubkey_length: u64,
         fee_rate: u64,
-    ) -> ContractResult<u64> {
+    ) -> ContractResult<MSats> {
         let config = self.config(store)?;
-        Ok(
+        config.sat_to_units(Sats(
             (9 + script_pubkey_length) * fee_rate * self.checkpoints.config(store).user_fee_factor
-                / 10_000
-                * config.units_per_sat,
-        )
+                / 10_000,
+        ))
+    }
+
+    /// Verifies and appends a batch of consecutive Bitcoin headers to the
+    /// header queue in a single call.
+    ///
+    /// The whole batch is rejected if any header fails its linkage or
+    /// proof-of-work checks, or if the batch is larger than the maximum number
+    /// of headers which can be relayed at once.
+    ///
+    /// If the batch reorgs the chain, the deposits credited from the orphaned
+    /// blocks are invalidated (see `invalidate_deposits`).
+    pub fn relay_headers(
+        &mut self,
+        store: &mut dyn Storage,
+        headers: Vec<WrappedHeader>,
+    ) -> ContractResult<()> {
+        // a reorg can only replace the headers within `max_reorg_depth` of
+        // the tip, so those which are no longer in the queue afterwards were
+        // orphaned
+        let tip_height = self.headers.height(store)?;
+        let max_reorg_depth = self.headers.config(store)?.max_reorg_depth;
+        let mut prev_tip = vec![];
+        for height in tip_height.saturating_sub(max_reorg_depth)..=tip_height {
+            if let Ok(Some(header)) = self.headers.get_by_height(store, height) {
+                prev_tip.push((height, header.block_hash()));
+            }
+        }
+
+        self.headers.add(store, HeaderList::from(headers))?;
+
+        let mut replaced = vec![];
+        for (height, hash) in prev_tip {
+            match self.headers.get_by_height(store, height) {
+                Ok(Some(header)) if header.block_hash() == hash => {}
+                // pruned from the front of the queue, not reorged
+                Err(_) => {}
+                Ok(_) => replaced.push(hash),
+            }
+        }
+        self.invalidate_deposits(store, &replaced)
+    }
+
+    /// Reverses the deposits credited into the `Building` checkpoint from the
+    /// given orphaned blocks, marking them as invalidated.
+    ///
+    /// An invalidated deposit is dropped from the checkpoint's inputs and
+    /// pending transfers, along with the fee it paid, and its outpoint is no
+    /// longer marked as processed, so the deposit is credited again once it
+    /// is relayed from the block which includes it on the new chain.
+    ///
+    /// Deposits credited into a `Signing` or `Complete` checkpoint are left
+    /// credited: the checkpoint transaction spends them, so it is only valid
+    /// once the deposit is mined again.
+    pub fn invalidate_deposits(
+        &mut self,
+        store: &mut dyn Storage,
+        orphaned: &[BlockHash],
+    ) -> ContractResult<()> {
+        let config = self.config(store)?;
+        let building_index = self.checkpoints.index(store);
+        for block_hash in orphaned {
+            let block_hash = block_hash.to_string();
+            let deposits = BLOCK_DEPOSITS
+                .prefix(&block_hash)
+                .range(store, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+            for (outpoint, (dest, coin)) in deposits {
+                BLOCK_DEPOSITS.remove(store, (&block_hash, &outpoint));
+                if DEPOSIT_HEIGHTS.load(store, &outpoint)?.1 != Some(building_index) {
+                    continue;
+                }
+
+                let prevout = bitcoin::OutPoint::from_str(&outpoint)?;
+                let mut building = self.checkpoints.building(store)?;
+                let transfer = (dest, coin);
+                if let Some(i) = building
+                    .pending
+                    .iter()
+                    .position(|pending| *pending == transfer)
+                {
+                    building.pending.remove(i);
+                }
+                let checkpoint_tx = &mut building.batches[BatchType::Checkpoint][0];
+                if let Some(i) = checkpoint_tx
+                    .input
+                    .iter()
+                    .position(|input| *input.prevout == prevout)
+                {
+                    let input = checkpoint_tx.input.remove(i);
+                    let fee = config
+                        .sat_to_units(Sats(input.amount))?
+                        .checked_sub(MSats::try_from(transfer.1.amount)?)?;
+                    building.fees_collected = building
+                        .fees_collected
+                        .saturating_sub(config.units_to_sat(fee)?.0);
+                }
+                self.checkpoints.set(store, building_index, &building)?;
+
+                self.processed_outpoints.remove(store, prevout);
+                DEPOSIT_HEIGHTS.remove(store, &outpoint);
+                INVALIDATED_DEPOSITS.save(store, &outpoint, &())?;
+            }
+        }
+
+        Ok(())
     }
 
     /// Verifies and processes a deposit of BTC into the reserve.   
@@ -183,6 +363,10 @@ impl Bitcoin {
     /// current `Building` checkpoint to be spent as an input. The deposit's
     /// committed destination will be credited once the checkpoint is fully
     /// signed.
+    ///
+    /// If `block_index` is given, the proof must place the transaction at
+    /// that position in the block.
+    #[allow(clippy::too_many_arguments)]
     pub fn relay_deposit(
         &mut self,
         env: Env,
@@ -190,19 +374,29 @@ impl Bitcoin {
         btc_tx: Adapter<Transaction>,
         btc_height: u32,
         btc_proof: Adapter<PartialMerkleTree>,
+        block_index: Option<u32>,
         btc_vout: u32,
         sigset_index: u32,
         dest: Dest,
-    ) -> ContractResult<()> {
+    ) -> ContractResult<DepositOutcome> {
+        // reject structurally invalid transactions before any storage reads
+        if btc_tx.output.is_empty() || btc_tx.version == 0 {
+            return Err(ContractError::App("malformed transaction".to_string()));
+        }
+
         let config = self.config(store)?;
         let now = env.block.time.seconds();
+        dest.validate()?;
+        dest.validate_memo_length(config.max_memo_length)?;
+        dest.validate_channel(store)?;
 
         let btc_header = self
             .headers
             .get_by_height(store, btc_height)?
             .ok_or_else(|| ContractError::App("Invalid bitcoin block height".to_string()))?;
 
-        if self.headers.height(store)? - btc_height < config.min_confirmations {
+        let tip_height = self.headers.height(store)?;
+        if tip_height - btc_height < config.min_confirmations {
             return Err(ContractError::App(
                 "Block is not sufficiently confirmed".to_string(),
             ));
@@ -228,6 +422,14 @@ impl Bitcoin {
                 "Bitcoin merkle proof does not match transaction".to_string(),
             ))?;
         }
+        if let Some(block_index) = block_index {
+            if block_indexes[0] != block_index {
+                return Err(ContractError::BlockIndexMismatch {
+                    block_index,
+                    proof_index: block_indexes[0],
+                });
+            }
+        }
 
         if btc_vout as usize >= btc_tx.output.len() {
             return Err(ContractError::App(
@@ -236,11 +438,17 @@ impl Bitcoin {
         }
         let output = &btc_tx.output[btc_vout as usize];
 
-        // if output.value < self.config.min_deposit_amount {
-        //     return Err(ContractError::App(
-        //         "Deposit amount is below minimum".to_string(),
-        //     ))?;
-        // }
+        // larger and IBC deposits wait for more confirmations
+        if tip_height - btc_height < config.amount_confirmations(output.value) {
+            return Err(ContractError::App(
+                "Block is not sufficiently confirmed for the deposit amount".to_string(),
+            ));
+        }
+        if tip_height - btc_height < config.deposit_confirmations(output.value, &dest) {
+            return Err(ContractError::App(
+                "Block is not sufficiently confirmed for an IBC deposit".to_string(),
+            ));
+        }
 
         let checkpoint = self.checkpoints.get(store, sigset_index)?;
         let sigset = checkpoint.sigset.clone();
@@ -248,21 +456,41 @@ impl Bitcoin {
         let dest_bytes = dest.commitment_bytes()?;
         let threshold = self.checkpoints.config(store).sigset_threshold;
 
-        let expected_script = sigset.output_script(&dest_bytes, threshold)?;
-        if output.script_pubkey != expected_script {
+        // the output either commits to the dest in its script, or pays the
+        // signatory set's static deposit address with the dest committed to
+        // in an OP_RETURN output
+        let commitment = if output.script_pubkey == sigset.output_script(&dest_bytes, threshold)? {
+            dest_bytes
+        } else if output.script_pubkey
+            == sigset.output_script(OP_RETURN_DEPOSIT_COMMITMENT, threshold)?
+        {
+            if op_return_data(&btc_tx) != Some(dest_bytes.as_slice()) {
+                return Err(ContractError::App(
+                    "OP_RETURN commitment does not match dest".to_string(),
+                ));
+            }
+            OP_RETURN_DEPOSIT_COMMITMENT.to_vec()
+        } else if output.script_pubkey == self.reserve_script_for_sigset(store, sigset_index)? {
+            return Err(ContractError::App(
+                "Output pays the reserve script, not a deposit address".to_string(),
+            ));
+        } else {
             return Err(ContractError::App(
                 "Output script does not match signature set".to_string(),
             ))?;
-        }
+        };
         let outpoint = bitcoin::OutPoint::new(btc_tx.txid(), btc_vout);
         if self.processed_outpoints.contains(store, outpoint) {
             return Err(ContractError::App(
                 "Output has already been relayed".to_string(),
             ))?;
         }
+        // a deposit invalidated by a reorg is relayed again from the new chain
+        INVALIDATED_DEPOSITS.remove(store, &outpoint.to_string());
         let deposit_timeout = sigset.create_time() + config.max_deposit_age;
         self.processed_outpoints
             .insert(store, outpoint, deposit_timeout)?;
+        DEPOSIT_HEIGHTS.save(store, &outpoint.to_string(), &(btc_height, None))?;
 
         if !checkpoint.deposits_enabled {
             return Err(ContractError::App(
@@ -270,7 +498,11 @@ impl Bitcoin {
             ))?;
         }
 
-        if now > deposit_timeout {
+        // a deposit only expires once both the sidechain clock and Bitcoin's
+        // median-time-past have passed the deadline, so a single header with
+        // a skewed timestamp can't expire it
+        let median_time_past = self.headers.median_time_past(store)? as u64;
+        if now.min(median_time_past) > deposit_timeout {
             let checkpoint = self.checkpoints.building(store)?;
             let checkpoint_config = self.checkpoints.config(store);
             self.recovery_txs.create_recovery_tx(
@@ -280,6 +512,7 @@ impl Bitcoin {
                     vout: btc_vout,
                     old_sigset: &sigset,
                     new_sigset: &checkpoint.sigset,
+                    commitment: commitment.clone(),
                     dest,
                     fee_rate: checkpoint.fee_rate,
                     //TODO: Hold checkpoint config on state
@@ -287,7 +520,19 @@ impl Bitcoin {
                 },
             )?;
 
-            return Ok(());
+            return Ok(DepositOutcome::Expired);
+        }
+
+        // reject deposits from blocks mined too long before the current tip,
+        // so very old transactions can't be credited. Deposits to an expired
+        // signatory set are recovered above regardless of their age.
+        let tip_header = self
+            .headers
+            .get_by_height(store, tip_height)?
+            .ok_or_else(|| ContractError::App("Invalid bitcoin block height".to_string()))?;
+        let block_age = (tip_header.time() as u64).saturating_sub(btc_header.time() as u64);
+        if block_age > config.max_deposit_block_age {
+            return Err(ContractError::App("deposit too old".to_string()));
         }
 
         let prevout = bitcoin::OutPoint {
@@ -297,53 +542,148 @@ impl Bitcoin {
         let input = Input::new(
             prevout,
             &sigset,
-            &dest_bytes,
+            &commitment,
             output.value,
             self.checkpoints.config(store).sigset_threshold,
         )?;
         let input_size = input.est_vsize();
 
         // note: we only mint nbtc when it is send to destination
-        let mint_amount = (output.value * config.units_per_sat).into();
-        let denom = get_full_btc_denom(store)?;
-        let mut nbtc = Coin {
-            denom,
-            amount: mint_amount,
-        };
+        let mint_amount = config.sat_to_units(Sats(output.value))?;
         let fee_amount = self.calc_minimum_deposit_fees(store, input_size, checkpoint.fee_rate)?;
-        let deposit_fees = calc_deposit_fee(nbtc.amount);
-        let fee = (fee_amount + deposit_fees).into();
-        nbtc.amount = nbtc.amount.checked_sub(fee).map_err(|_| {
-            ContractError::App("Deposit amount is too small to pay its spending fee".to_string())
-        })?;
+        let deposit_fees = calc_deposit_fee(mint_amount);
+        let fee = fee_amount.checked_add(deposit_fees)?;
+        let net_amount = mint_amount.checked_sub(fee).unwrap_or_default();
+        if config.units_to_sat(net_amount)? < Sats(config.min_deposit_amount) {
+            // the outpoint stays marked as processed so it can't be relayed
+            // again, and is kept aside to be swept later rather than being
+            // spent as part of the reserve
+            SMALL_DEPOSITS.save(
+                store,
+                &outpoint.to_string(),
+                &(sigset_index, dest, output.value),
+            )?;
+            return Ok(DepositOutcome::TooSmall);
+        }
+        let nbtc = Coin {
+            denom: get_full_btc_denom(store)?,
+            amount: net_amount.into(),
+        };
         #[cfg(debug_assertions)]
         println!(
             "Relay deposit with output value: {}, input size: {}, checkpoint fee rate: {}",
             output.value, input_size, checkpoint.fee_rate
         );
 
-        self.give_miner_fee(store, fee)?;
-        // TODO: record as excess collected if inputs are full
-
         let mut building_mut = self.checkpoints.building(store)?;
         let building_checkpoint_batch = &mut building_mut.batches[BatchType::Checkpoint];
         let checkpoint_tx = building_checkpoint_batch.get_mut(0).unwrap();
+
+        // the building checkpoint spends the previous reserve output along with
+        // every deposit relayed so far, so its inputs are the reserve value the
+        // net deposit amount would be added to
+        let reserve_value = Sats(checkpoint_tx.input.iter().map(|input| input.amount).sum());
+        let net_deposit_value = config.units_to_sat(net_amount)?;
+        if reserve_value.checked_add(net_deposit_value)? > Sats(config.capacity_limit) {
+            return Err(ContractError::App("capacity limit reached".to_string()));
+        }
+
+        self.give_miner_fee(store, fee)?;
+        // TODO: record as excess collected if inputs are full
+
         checkpoint_tx.input.push(input);
         // TODO: keep in excess queue if full
 
         // let deposit_fee = nbtc.take(calc_deposit_fee(nbtc.amount.into()))?;
         // self.give_rewards(deposit_fee)?;
 
+        let address = dest.to_source_addr();
+        let history_index = DEPOSIT_HISTORY
+            .prefix(&address)
+            .keys(store, None, None, Order::Descending)
+            .next()
+            .transpose()?
+            .map_or(0, |last| last + 1);
+        DEPOSIT_HISTORY.save(
+            store,
+            (&address, history_index),
+            &DepositRecord {
+                index: history_index,
+                txid: HashBinary(outpoint.txid),
+                vout: btc_vout,
+                amount: nbtc.amount,
+                height: btc_height,
+            },
+        )?;
+
+        BLOCK_DEPOSITS.save(
+            store,
+            (&btc_header.block_hash().to_string(), &outpoint.to_string()),
+            &(dest.clone(), nbtc.clone()),
+        )?;
+
         building_mut.insert_pending(dest, nbtc)?;
 
         let index = self.checkpoints.index(store);
         self.checkpoints.set(store, index, &building_mut)?;
+        DEPOSIT_HEIGHTS.save(store, &outpoint.to_string(), &(btc_height, Some(index)))?;
 
-        Ok(())
+        Ok(DepositOutcome::Credited)
+    }
+
+    /// Relays several deposit outputs of the same transaction under one
+    /// Merkle proof, each committing to its own destination. The whole call
+    /// fails if any of the outputs can't be relayed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn relay_deposit_multi(
+        &mut self,
+        env: Env,
+        store: &mut dyn Storage,
+        btc_tx: Adapter<Transaction>,
+        btc_height: u32,
+        btc_proof: Adapter<PartialMerkleTree>,
+        outputs: Vec<(u32, Dest)>,
+        sigset_index: u32,
+    ) -> ContractResult<Vec<DepositOutcome>> {
+        if outputs.is_empty() {
+            return Err(ContractError::App("No deposit outputs given".to_string()));
+        }
+
+        outputs
+            .into_iter()
+            .map(|(btc_vout, dest)| {
+                self.relay_deposit(
+                    env.clone(),
+                    store,
+                    btc_tx.clone(),
+                    btc_height,
+                    btc_proof.clone(),
+                    None,
+                    btc_vout,
+                    sigset_index,
+                    dest,
+                )
+            })
+            .collect()
+    }
+
+    /// The script pubkey of the reserve outputs of the checkpoint with the
+    /// given index, paying to its signatory set. Reserve outputs commit to no
+    /// dest, so they are never valid deposit outputs.
+    pub fn reserve_script_for_sigset(
+        &self,
+        store: &dyn Storage,
+        index: u32,
+    ) -> ContractResult<Script> {
+        let sigset = self.checkpoints.get(store, index)?.sigset;
+        let threshold = self.checkpoints.config(store).sigset_threshold;
+        sigset.output_script(&[0u8], threshold)
     }
 
     /// Records proof that a checkpoint produced by the network has been
-    /// confirmed into a Bitcoin block.    
+    /// confirmed into a Bitcoin block. The block must have at least
+    /// `min_checkpoint_confirmations + 1` confirmations, counting the block
+    /// itself.
     pub fn relay_checkpoint(
         &mut self,
         store: &mut dyn Storage,
@@ -365,7 +705,8 @@ impl Bitcoin {
             .get_by_height(store, btc_height)?
             .ok_or_else(|| ContractError::App("Invalid bitcoin block height".to_string()))?;
 
-        if self.headers.height(store)? - btc_height < config.min_checkpoint_confirmations {
+        let confirmations = self.headers.height(store)? - btc_height + 1;
+        if confirmations < config.min_checkpoint_confirmations + 1 {
             return Err(ContractError::App(
                 "Block is not sufficiently confirmed".to_string(),
             ));
@@ -404,15 +745,75 @@ impl Bitcoin {
         Ok(())
     }
 
+    /// Records a mempool fee rate observed by the owner or a registered
+    /// signer, in satoshis per virtual byte. The next checkpoint's fee rate is
+    /// raised towards the highest rate observed since the last checkpoint was
+    /// advanced, by at most one fee adjustment step.
+    pub fn observe_fee_rate(
+        &mut self,
+        store: &mut dyn Storage,
+        sender: &Addr,
+        fee_rate: u64,
+    ) -> ContractResult<()> {
+        if *sender != CONFIG.load(store)?.owner && !SIGNERS.has(store, sender.as_str()) {
+            return Err(ContractError::Unauthorized {});
+        }
+        let observed = OBSERVED_FEE_RATE.may_load(store)?.unwrap_or_default();
+        OBSERVED_FEE_RATE.save(store, &observed.max(fee_rate))?;
+        Ok(())
+    }
+
+    /// Confirms the checkpoint at `cp_index` given its fully-signed transaction
+    /// and a proof that it was included at `block_index` in the Bitcoin block
+    /// at `btc_height`. The proof is then checked as in `relay_checkpoint`.
+    pub fn confirm_checkpoint(
+        &mut self,
+        store: &mut dyn Storage,
+        cp_index: u32,
+        btc_tx: Transaction,
+        btc_height: u32,
+        btc_proof: PartialMerkleTree,
+        block_index: u32,
+    ) -> ContractResult<()> {
+        let txid = btc_tx.txid();
+        if txid != self.checkpoints.get(store, cp_index)?.txid()? {
+            return Err(ContractError::App(
+                "Transaction does not match checkpoint".to_string(),
+            ));
+        }
+
+        let mut txids = vec![];
+        let mut block_indexes = vec![];
+        btc_proof
+            .extract_matches(&mut txids, &mut block_indexes)
+            .map_err(|_| ContractError::BitcoinMerkleBlockError)?;
+        if txids != [txid] {
+            return Err(ContractError::App(
+                "Bitcoin merkle proof does not match transaction".to_string(),
+            ));
+        }
+        if block_indexes[0] != block_index {
+            return Err(ContractError::BlockIndexMismatch {
+                block_index,
+                proof_index: block_indexes[0],
+            });
+        }
+
+        self.relay_checkpoint(store, btc_height, Adapter::new(btc_proof), cp_index)
+    }
+
     /// Adds an output to the current `Building` checkpoint to be paid out once
-    /// the checkpoint is fully signed.
+    /// the checkpoint is fully signed, returning the miner fee deducted from
+    /// `amount`.
     pub fn add_withdrawal(
         &mut self,
         store: &mut dyn Storage,
+        withdrawer: &Addr,
         script_pubkey: Adapter<Script>,
         mut amount: Uint128,
-    ) -> ContractResult<()> {
+    ) -> ContractResult<Uint128> {
         let config = self.config(store)?;
+        let gross_amount = amount;
         if script_pubkey.len() as u64 > config.max_withdrawal_script_length {
             return Err(ContractError::App(
                 "Script exceeds maximum length".to_string(),
@@ -431,15 +832,15 @@ impl Bitcoin {
             script_pubkey.len() as u64,
             self.checkpoints.building(store)?.fee_rate,
         )?;
-        let fee = fee_amount.into();
+        let fee: Uint128 = fee_amount.into();
         amount = amount.checked_sub(fee).map_err(|_| {
             ContractError::App("Withdrawal is too small to pay its miner fee".to_string())
         })?;
 
-        self.give_miner_fee(store, fee)?;
+        self.give_miner_fee(store, fee_amount)?;
         // TODO: record as collected for excess if full
 
-        let value = (amount.u128() as u64) / config.units_per_sat;
+        let value = config.units_to_sat(MSats::try_from(amount)?)?.0;
         // if value < self.config.min_withdrawal_amount {
         //     return Err(ContractError::App(
         //         "Withdrawal is smaller than than minimum amount".to_string(),
@@ -450,6 +851,12 @@ impl Bitcoin {
                 "Withdrawal is too small to pay its dust limit".to_string(),
             ));
         }
+        if config.max_withdrawal_amount > 0 && value > config.max_withdrawal_amount {
+            return Err(ContractError::App(format!(
+                "Withdrawal exceeds the maximum amount of {} satoshis",
+                config.max_withdrawal_amount
+            )));
+        }
 
         let output = bitcoin::TxOut {
             script_pubkey: script_pubkey.into_inner(),
@@ -466,20 +873,128 @@ impl Bitcoin {
         self.checkpoints.set(store, index, &checkpoint)?;
         // TODO: push to excess if full
 
-        Ok(())
+        let mut withdrawals = WITHDRAWALS.may_load(store, index)?.unwrap_or_default();
+        withdrawals.push((withdrawer.clone(), gross_amount, fee));
+        WITHDRAWALS.save(store, index, &withdrawals)?;
+
+        Ok(fee)
     }
 
-    /// The amount of BTC in the reserve output of the most recent fully-signed
+    /// Removes a withdrawal output from the checkpoint at `checkpoint_index`,
+    /// which must still be `Building`, and returns the full nBTC amount
+    /// (including the deducted miner fee) to be re-credited to the withdrawer.
+    pub fn cancel_withdrawal(
+        &mut self,
+        store: &mut dyn Storage,
+        sender: &Addr,
+        checkpoint_index: u32,
+        output_index: u32,
+    ) -> ContractResult<Uint128> {
+        let config = self.config(store)?;
+        let mut checkpoint = self.checkpoints.get(store, checkpoint_index)?;
+        if checkpoint.status != CheckpointStatus::Building {
+            return Err(ContractError::Checkpoint(
+                "Checkpoint is already signing".to_string(),
+            ));
+        }
+
+        let mut withdrawals = WITHDRAWALS
+            .may_load(store, checkpoint_index)?
+            .unwrap_or_default();
+        let output_index = output_index as usize;
+        let (withdrawer, amount, fee) = withdrawals
+            .get(output_index)
+            .cloned()
+            .ok_or_else(|| ContractError::App("Withdrawal not found".to_string()))?;
+        if withdrawer != *sender {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let fee_sats = config.units_to_sat(fee)?;
+        checkpoint.fees_collected = checkpoint
+            .fees_collected
+            .checked_sub(fee_sats)
+            .ok_or_else(|| ContractError::App("Withdrawal fee was already spent".to_string()))?;
+        let checkpoint_tx = &mut checkpoint.batches[BatchType::Checkpoint][0];
+        checkpoint_tx.output.remove(output_index);
+        self.checkpoints.set(store, checkpoint_index, &checkpoint)?;
+
+        withdrawals.remove(output_index);
+        WITHDRAWALS.save(store, checkpoint_index, &withdrawals)?;
+
+        Ok(amount)
+    }
+
+    /// Queues a transfer of `amount` nBTC from `from` to `to`, to be minted
+    /// once the `Building` checkpoint is fully signed, after deducting the
+    /// flat `transfer_fee` and adding it to the fee pool.
+    ///
+    /// Fails if transfers are not allowed, unless `from` is one of the
+    /// configured transfer exceptions.
+    ///
+    /// Returns the amount which will be received by `to`.
+    pub fn transfer(
+        &mut self,
+        store: &mut dyn Storage,
+        from: &Addr,
+        to: Addr,
+        amount: Uint128,
+    ) -> ContractResult<Uint128> {
+        let config = self.config(store)?;
+        if !config.transfers_allowed && !config.transfer_exceptions.contains(from) {
+            return Err(ContractError::Account(
+                "Transfers are not allowed".to_string(),
+            ));
+        }
+        let fee = MSats(config.transfer_fee);
+        if amount <= fee.into() {
+            return Err(ContractError::App(
+                "Transfer amount must exceed the transfer fee".to_string(),
+            ));
+        }
+        let net_amount = amount - Uint128::from(fee);
+
+        let mut fee_pool = self.fee_pool(store)?;
+        fee_pool += i64::try_from(fee)?;
+        FEE_POOL.save(store, &fee_pool)?;
+
+        let mut checkpoint = self.checkpoints.building(store)?;
+        checkpoint.insert_pending(
+            Dest::Address(to),
+            Coin {
+                denom: get_full_btc_denom(store)?,
+                amount: net_amount,
+            },
+        )?;
+        let index = self.checkpoints.index(store);
+        self.checkpoints.set(store, index, &checkpoint)?;
+
+        Ok(net_amount)
+    }
+
+    /// The amount of BTC in the reserve outputs of the most recent fully-signed
     /// checkpoint.    
     pub fn value_locked(&self, store: &dyn Storage) -> ContractResult<u64> {
-        let last_completed = self.checkpoints.last_completed(store)?;
-        Ok(last_completed.reserve_output()?.unwrap().value)
+        self.checkpoints.last_completed(store)?.reserve_value()
+    }
+
+    /// The value of the reserve outputs of the last checkpoint confirmed on
+    /// the Bitcoin blockchain, in satoshis. Each checkpoint spends the reserve
+    /// outputs of the one before it, so these are the only reserve outputs
+    /// left unspent by the confirmed checkpoint chain. Returns 0 if no
+    /// checkpoint has been confirmed yet.
+    pub fn reserve_value(&self, store: &dyn Storage) -> ContractResult<u64> {
+        let index = match self.checkpoints.confirmed_index(store) {
+            Some(index) => index,
+            None => return Ok(0),
+        };
+        self.checkpoints.get(store, index)?.reserve_value()
     }
 
     /// The network (e.g. Bitcoin testnet vs mainnet) which is currently
     /// configured.
-    pub fn network(&self) -> bitcoin::Network {
-        self.headers.network()
+    pub fn network(&self, store: &dyn Storage) -> ContractResult<bitcoin::Network> {
+        self.headers.network(store)
     }
 
     /// Gets the rate of change of the reserve output and signatory set over the
@@ -522,49 +1037,46 @@ impl Bitcoin {
 
         let prev_checkpoint = completed.get(prev_index).unwrap();
 
-        let amount_prev = prev_checkpoint.reserve_output()?.unwrap().value;
-        let amount_now = signing.reserve_output()?.unwrap().value;
+        let amount_prev = prev_checkpoint.reserve_value()?;
+        let amount_now = signing.reserve_value()?;
 
         let reserve_decrease = amount_prev.saturating_sub(amount_now);
 
-        let vp_shares = |sigset: &SignatorySet| -> ContractResult<_> {
-            let sigset_index = sigset.index();
-            let total_vp = sigset.present_vp() as f64;
-            let sigset_fractions: HashMap<_, _> = sigset
-                .iter()
-                .map(|v| (v.pubkey.as_slice(), v.voting_power as f64 / total_vp))
-                .collect();
-            let mut sigset: HashMap<_, _> = Default::default();
-            for entry in SIG_KEYS.range_raw(store, None, None, Order::Ascending) {
-                let (_, xpub) = entry?;
-                let pubkey: threshold_sig::Pubkey = xpub.derive_pubkey(sigset_index)?.into();
-                sigset.insert(
-                    xpub.key.encode(),
-                    *sigset_fractions.get(pubkey.as_slice()).unwrap_or(&0.0),
-                );
-            }
-
-            Ok(sigset)
-        };
-
-        let now_sigset = vp_shares(&signing.sigset)?;
-        let prev_sigset = vp_shares(&prev_checkpoint.sigset)?;
-        let sigset_change = now_sigset.iter().fold(0.0, |acc, (k, v)| {
-            let prev_share = prev_sigset.get(k).unwrap_or(&0.0);
-            if v > prev_share {
-                acc + (v - prev_share)
-            } else {
-                acc
-            }
-        });
-        let sigset_change = (sigset_change * 10_000.0) as u16;
+        let sigset_change = signing
+            .sigset
+            .change_since(store, &prev_checkpoint.sigset)?;
 
         Ok(ChangeRates {
-            withdrawal: (reserve_decrease * 10_000 / amount_prev) as u16,
+            // a reserve dropped as dust has nothing left to withdraw
+            withdrawal: (reserve_decrease * 10_000)
+                .checked_div(amount_prev)
+                .unwrap_or_default() as u16,
             sigset_change,
         })
     }
 
+    /// Replaces the transaction of the most recent unconfirmed checkpoint with
+    /// one paying `new_fee_rate`, charging the additional miner fee to the fee
+    /// pool. The checkpoint must be signed again before it can be broadcast.
+    pub fn bump_checkpoint_fee(
+        &mut self,
+        env: &Env,
+        store: &mut dyn Storage,
+        index: u32,
+        new_fee_rate: u64,
+    ) -> ContractResult<()> {
+        let config = self.config(store)?;
+        let additional_fee =
+            self.checkpoints
+                .bump_fee(store, index, new_fee_rate, env.block.time.seconds())?;
+
+        let mut fee_pool = FEE_POOL.load(store)?;
+        fee_pool -= i64::try_from(config.sat_to_units(Sats(additional_fee))?)?;
+        FEE_POOL.save(store, &fee_pool)?;
+
+        Ok(())
+    }
+
     /// Called once per sidechain block to advance the checkpointing process.        
     /// Can add to clock module
     pub fn begin_block_step(
@@ -593,22 +1105,204 @@ impl Bitcoin {
 
         let btc_height = self.headers.height(store)?;
 
-        let pushed = self.checkpoints.maybe_step(
-            env,
+        // Keep the headers needed to confirm new deposits, and those which may
+        // contain the oldest unconfirmed checkpoint so it can still be relayed.
+        let mut retain_from_height = btc_height.saturating_sub(config.min_confirmations);
+        if let Some(index) = self.checkpoints.first_unconfirmed_index(store)? {
+            let signed_at = self.checkpoints.get(store, index)?.signed_at_btc_height;
+            retain_from_height = retain_from_height.min(signed_at.unwrap_or(0));
+        }
+        self.headers.prune(store, retain_from_height)?;
+
+        // a checkpoint which could not be signed in time is folded back into
+        // the building checkpoint, and the fee pool gets back the miner fee
+        // and signatory rewards it was charged, less the fees it was credited,
+        // so all are accounted for again when the building checkpoint is
+        // advanced
+        let now = env.block.time.seconds();
+        if let Some((miner_fee, fees_collected, reward)) =
+            self.checkpoints.expire_signing(store, now)?
+        {
+            let mut fee_pool = self.fee_pool(store)?;
+            fee_pool += i64::try_from(config.sat_to_units(Sats(miner_fee))?)?;
+            fee_pool += i64::try_from(reward.u128())?;
+            fee_pool -= i64::try_from(config.sat_to_units(Sats(fees_collected))?)?;
+            FEE_POOL.save(store, &fee_pool)?;
+            return Ok(vec![]);
+        }
+
+        let mut pushed = self.checkpoints.maybe_step(
+            env.clone(),
             store,
             btc_height,
             !reached_capacity_limit,
-            timestamping_commitment,
+            timestamping_commitment.clone(),
             &config,
         )?;
+        if !pushed {
+            pushed = self.build_rebalance_checkpoint(env, store, timestamping_commitment)?;
+        }
 
         // TODO: remove expired outpoints from processed_outpoints
 
-        if pushed {
-            self.offline_signers(store)
+        if !pushed {
+            return Ok(vec![]);
+        }
+
+        if self.checkpoints.signing(store)?.is_some() {
+            let index = self.checkpoints.index(store) - 1;
+            let mut signing = self.checkpoints.get(store, index)?;
+            let miner_fee = signing.checkpoint_tx_miner_fees()?;
+            signing.signatory_reward =
+                self.give_checkpoint_fees(store, signing.fees_collected, miner_fee)?;
+            self.checkpoints.set(store, index, &signing)?;
+        }
+
+        self.offline_signers(store)
+    }
+
+    /// Advances the `Building` checkpoint early if it only consolidates
+    /// reserve UTXOs and spends more than `rebalance_utxo_threshold` of them
+    /// (see `CheckpointQueue::should_rebalance`). Its outputs pay the
+    /// checkpoint's signatory set like any other checkpoint, and its miner fee
+    /// is paid by the fee pool. Returns whether a checkpoint was produced.
+    pub fn build_rebalance_checkpoint(
+        &mut self,
+        env: Env,
+        store: &mut dyn Storage,
+        timestamping_commitment: Vec<u8>,
+    ) -> ContractResult<bool> {
+        if !self
+            .checkpoints
+            .should_rebalance(store, &timestamping_commitment)?
+        {
+            return Ok(false);
+        }
+
+        let config = self.config(store)?;
+        let btc_height = self.headers.height(store)?;
+        let deposits_enabled = self.checkpoints.building(store)?.deposits_enabled;
+        self.checkpoints.push_checkpoint(
+            env,
+            store,
+            btc_height,
+            deposits_enabled,
+            timestamping_commitment,
+            &config,
+        )
+    }
+
+    /// Credits the user fees collected by a checkpoint which was just advanced
+    /// to `Signing` to the fee pool, which has already been charged the
+    /// checkpoint's miner fee.
+    ///
+    /// Of the surplus of collected fees over the miner fee, the
+    /// `fee_pool_reward_split` fraction is paid out as signatory rewards while
+    /// the pool is below `fee_pool_target_balance`, and all of it once the
+    /// pool has reached the target. The rewards are taken out of the pool and
+    /// returned, to be held on the checkpoint until it is signed (see
+    /// `credit_signatory_rewards`).
+    pub fn give_checkpoint_fees(
+        &mut self,
+        store: &mut dyn Storage,
+        fees_collected: u64,
+        miner_fee: u64,
+    ) -> ContractResult<Uint128> {
+        let config = self.config(store)?;
+        let mut fee_pool = self.fee_pool(store)?;
+        fee_pool += i64::try_from(config.sat_to_units(Sats(fees_collected))?)?;
+
+        let surplus = config
+            .sat_to_units(Sats(fees_collected.saturating_sub(miner_fee)))?
+            .0 as u128;
+        let target = i64::try_from(config.sat_to_units(Sats(config.fee_pool_target_balance))?)?;
+        let (numerator, denominator) = config.fee_pool_reward_split;
+        let reward = if fee_pool >= target {
+            surplus
         } else {
-            Ok(vec![])
+            (surplus * numerator as u128)
+                .checked_div(denominator as u128)
+                .ok_or_else(|| ContractError::App("Invalid fee pool reward split".to_string()))?
+                .min(surplus)
+        };
+
+        fee_pool -= i64::try_from(reward)?;
+        FEE_POOL.save(store, &fee_pool)?;
+
+        Ok(Uint128::from(reward))
+    }
+
+    /// Credits the signatory rewards held by the checkpoint at `index` to the
+    /// signatories which signed it, weighted by the voting power they signed
+    /// with, once it is complete. Rewards are only credited once, and any
+    /// remainder left by rounding goes back to the fee pool.
+    pub fn credit_signatory_rewards(
+        &mut self,
+        store: &mut dyn Storage,
+        index: u32,
+    ) -> ContractResult<()> {
+        let mut checkpoint = self.checkpoints.get(store, index)?;
+        if checkpoint.status != CheckpointStatus::Complete || checkpoint.signatory_reward.is_zero()
+        {
+            return Ok(());
+        }
+        let reward = checkpoint.signatory_reward.u128();
+
+        let mut signers = vec![];
+        for entry in VALIDATORS.range(store, None, None, Order::Ascending) {
+            let (cons_key, (_, address)) = entry?;
+            let Some(xpub) = SIG_KEYS.may_load(store, &cons_key)? else {
+                continue;
+            };
+            let power = checkpoint.signed_power(&xpub)?;
+            if power > 0 {
+                signers.push((address, power));
+            }
         }
+        let total_power: u128 = signers.iter().map(|(_, power)| *power as u128).sum();
+
+        let mut paid = 0;
+        if total_power > 0 {
+            for (address, power) in signers {
+                let share = reward * power as u128 / total_power;
+                SIGNATORY_REWARDS.update(store, &address, |rewards| -> ContractResult<_> {
+                    Ok(rewards.unwrap_or_default() + Uint128::from(share))
+                })?;
+                paid += share;
+            }
+        }
+        let mut fee_pool = self.fee_pool(store)?;
+        fee_pool += i64::try_from(reward - paid)?;
+        FEE_POOL.save(store, &fee_pool)?;
+
+        checkpoint.signatory_reward = Uint128::zero();
+        self.checkpoints.set(store, index, &checkpoint)?;
+
+        Ok(())
+    }
+
+    /// Takes the signatory rewards accrued by `signer` from checkpoint fees,
+    /// zeroing their balance. Returns the amount to pay them, in nBTC units.
+    pub fn claim_signatory_rewards(
+        &mut self,
+        store: &mut dyn Storage,
+        signer: &Addr,
+    ) -> ContractResult<Uint128> {
+        if !SIGNERS.has(store, signer.as_str()) {
+            return Err(ContractError::Signer(
+                "Only registered signers can claim rewards".to_string(),
+            ));
+        }
+
+        let rewards = SIGNATORY_REWARDS
+            .may_load(store, signer.as_str())?
+            .unwrap_or_default();
+        if rewards.is_zero() {
+            return Err(ContractError::App("No rewards to claim".to_string()));
+        }
+        SIGNATORY_REWARDS.remove(store, signer.as_str());
+
+        Ok(rewards)
     }
 
     /// Returns the consensus keys of signers who have not submitted signatures
@@ -660,12 +1354,45 @@ impl Bitcoin {
         Ok(offline_signers)
     }
 
+    /// Returns the number of most recent completed checkpoints in a row which
+    /// the signatory with the given key was asked to sign but did not.
+    pub fn missed_checkpoints(&self, store: &dyn Storage, xpub: &Xpub) -> ContractResult<u32> {
+        let mut missed = 0;
+        for checkpoint in self.checkpoints.completed(store, u32::MAX)?.iter().rev() {
+            if checkpoint.to_sign(xpub)?.is_empty() {
+                break;
+            }
+            missed += 1;
+        }
+        Ok(missed)
+    }
+
+    /// Jails an offline signer, removing them from the validator set so they
+    /// are no longer included in future signatory sets, and slashes
+    /// `slash_fraction` of their voting power.
+    ///
+    /// Returns the amount of voting power slashed, which is zero if the signer
+    /// is already jailed.
     pub fn punish_validator(
         &mut self,
         store: &mut dyn Storage,
         cons_key: &ConsensusKey,
         addr: String,
-    ) -> ContractResult<()> {
+    ) -> ContractResult<u64> {
+        if JAILED.has(store, cons_key) {
+            return Ok(0);
+        }
+
+        let (numerator, denominator) = self.config(store)?.slash_fraction;
+        let power = VALIDATORS
+            .may_load(store, cons_key)?
+            .map_or(0, |(power, _)| power);
+        let slashed = (power as u128 * numerator as u128)
+            .checked_div(denominator as u128)
+            .ok_or_else(|| ContractError::App("Invalid slash fraction".to_string()))?
+            .min(power as u128) as u64;
+
+        JAILED.save(store, cons_key, &(power - slashed))?;
         VALIDATORS.remove(store, cons_key);
         SIGNERS.remove(store, &addr);
         let xpub = SIG_KEYS.may_load(store, cons_key)?;
@@ -676,22 +1403,27 @@ impl Bitcoin {
             }
             None => {}
         }
-        Ok(())
+        Ok(slashed)
     }
     /// Takes the pending nBTC transfers from the most recent fully-signed
     /// checkpoint, leaving the vector empty after calling.
     ///
     /// This should be used to process the pending transfers, crediting each of
     /// them now that the checkpoint has been fully signed.
+    ///
+    /// At most `max_checkpoints` confirmed checkpoints are handled per call to
+    /// bound gas usage; the rest are left for later calls.
     #[allow(clippy::type_complexity)]
     pub fn take_pending_confirmed(
         &mut self,
         store: &mut dyn Storage,
+        max_checkpoints: u32,
     ) -> ContractResult<Vec<Vec<(Dest, Coin)>>> {
-        let unhandled_confirmed_cps = match self.checkpoints.unhandled_confirmed(store) {
-            Err(_) => return Ok(vec![]),
-            Ok(val) => val,
-        };
+        let unhandled_confirmed_cps =
+            match self.checkpoints.unhandled_confirmed(store, max_checkpoints) {
+                Err(_) => return Ok(vec![]),
+                Ok(val) => val,
+            };
         let mut confirmed_dests = vec![];
 
         // TODO: drain iter
@@ -710,17 +1442,36 @@ impl Bitcoin {
         Ok(confirmed_dests)
     }
 
+    /// Returns the pending nBTC transfers of the confirmed checkpoints which
+    /// have not been handled yet, without clearing them or advancing the
+    /// unhandled confirmed index.
+    #[allow(clippy::type_complexity)]
+    pub fn pending_confirmed(&self, store: &dyn Storage) -> ContractResult<Vec<Vec<(Dest, Coin)>>> {
+        let unhandled_confirmed_cps = match self.checkpoints.unhandled_confirmed(store, u32::MAX) {
+            Err(_) => return Ok(vec![]),
+            Ok(val) => val,
+        };
+
+        unhandled_confirmed_cps
+            .into_iter()
+            .map(|index| Ok(self.checkpoints.get(store, index)?.pending))
+            .collect()
+    }
+
     /// Takes the pending nBTC transfers from the most recent fully-signed
     /// checkpoint, leaving the vector empty after calling.
     ///
     /// This should be used to process the pending transfers, crediting each of
     /// them now that the checkpoint has been fully signed.
+    ///
+    /// See `take_pending_confirmed` for `max_checkpoints`.
     #[allow(clippy::type_complexity)]
     pub fn take_pending_completed(
         &mut self,
         store: &mut dyn Storage,
+        max_checkpoints: u32,
     ) -> ContractResult<Vec<Vec<(Dest, Coin)>>> {
-        let confirmed_dests = self.take_pending_confirmed(store)?;
+        let confirmed_dests = self.take_pending_confirmed(store, max_checkpoints)?;
 
         let last_completed_index = match self.checkpoints.last_completed_index(store) {
             Err(err) => {
@@ -750,13 +1501,8 @@ impl Bitcoin {
             .collect())
     }
 
-    pub fn give_miner_fee(
-        &mut self,
-        store: &mut dyn Storage,
-        amount: Uint128,
-    ) -> ContractResult<()> {
+    pub fn give_miner_fee(&mut self, store: &mut dyn Storage, amount: MSats) -> ContractResult<()> {
         let config = self.config(store)?;
-        let amount: u64 = amount.u128() as u64;
         // note: we don't need to burn coin here
         // coin.burn();
 
@@ -765,7 +1511,7 @@ impl Bitcoin {
         FEE_POOL.save(store, &fee_pool)?;
 
         let mut checkpoint = self.checkpoints.building(store)?;
-        checkpoint.fees_collected += amount / config.units_per_sat;
+        checkpoint.fees_collected += config.units_to_sat(amount)?.0;
 
         let index = self.checkpoints.index(store);
         self.checkpoints.set(store, index, &checkpoint)?;
@@ -823,4 +1569,23 @@ impl Bitcoin {
     //     let coins = self.accounts.withdraw(signer, amount)?;
     //     self.give_miner_fee(store, coins.amount)
     // }
+
+    /// Builds the emergency disbursal transactions for the reserve of the
+    /// latest confirmed checkpoint (see `emergency::disbursal_txs`).
+    pub fn emergency_disbursal_txs(
+        &self,
+        querier: QuerierWrapper,
+        store: &dyn Storage,
+    ) -> ContractResult<Vec<Adapter<Transaction>>> {
+        let index = self
+            .checkpoints
+            .confirmed_index(store)
+            .ok_or_else(|| ContractError::Checkpoint("No confirmed checkpoint".to_string()))?;
+        let checkpoint = self.checkpoints.get(store, index)?;
+        let config = self.checkpoints.config(store);
+        disbursal_txs(querier, store, &checkpoint, &config)?
+            .iter()
+            .map(|tx| Ok(Adapter::new(tx.to_bitcoin_tx()?)))
+            .collect()
+    }
 }
@@ -1,23 +1,27 @@
 use super::{
     signatory::SignatorySet,
-    threshold_sig::{Signature, ThresholdSig},
+    threshold_sig::{Pubkey, Signature, ThresholdSig},
 };
-use crate::{adapter::Adapter, interface::Xpub, state::BUILDING_INDEX};
+use crate::{adapter::Adapter, interface::Xpub, state::BUILDING_INDEX, units::Sats};
 use crate::{
     constants::DEFAULT_FEE_RATE,
     error::{ContractError, ContractResult},
-    state::{CHECKPOINT_CONFIG, CONFIRMED_INDEX, FEE_POOL, FIRST_UNHANDLED_CONFIRMED_INDEX},
+    state::{
+        CHECKPOINT_CONFIG, CONFIRMED_INDEX, DEPOSIT_HEIGHTS, FEE_POOL,
+        FIRST_UNHANDLED_CONFIRMED_INDEX, OBSERVED_FEE_RATE, SIG_KEYS, WITHDRAWALS,
+    },
 };
 use crate::{
-    interface::{BitcoinConfig, CheckpointConfig, Dest},
+    interface::{BitcoinConfig, CheckpointConfig, Dest, InputSelection},
     state::CHECKPOINTS,
 };
 use bitcoin::hashes::Hash;
 use bitcoin::{blockdata::transaction::EcdsaSighashType, Sequence, Transaction, TxIn, TxOut};
 use cosmwasm_schema::cw_serde;
 use cosmwasm_schema::serde::{Deserialize, Serialize};
-use cosmwasm_std::{Api, Coin, Env, QuerierWrapper, Storage};
+use cosmwasm_std::{Api, Coin, Env, Order, QuerierWrapper, Storage, Uint128};
 use derive_more::{Deref, DerefMut};
+use std::collections::BTreeMap;
 
 /// The status of a checkpoint. Checkpoints start as `Building`, and eventually
 /// advance through the three states.
@@ -81,6 +85,13 @@ pub struct Input {
     /// coordinate to submit their signatures, and starts out with no
     /// signatures.
     pub signatures: ThresholdSig,
+
+    /// Whether this input signals replaceability, so the transaction spending
+    /// it can be replaced by one paying a higher fee (see
+    /// `CheckpointQueue::bump_fee`). Inputs created before this was added
+    /// don't, so their signature messages are unchanged.
+    #[serde(default)]
+    pub rbf: bool,
 }
 
 impl Input {
@@ -95,7 +106,11 @@ impl Input {
         Ok(bitcoin::TxIn {
             previous_output: *self.prevout,
             script_sig: bitcoin::Script::new(),
-            sequence: Sequence(u32::MAX),
+            sequence: if self.rbf {
+                Sequence::ENABLE_RBF_NO_LOCKTIME
+            } else {
+                Sequence::MAX
+            },
             witness: bitcoin::Witness::from_vec(witness),
         })
     }
@@ -121,6 +136,7 @@ impl Input {
             amount,
             est_witness_vsize: sigset.est_witness_vsize(),
             signatures: ThresholdSig::from_sigset(sigset),
+            rbf: true,
         })
     }
 
@@ -358,16 +374,13 @@ impl Batch {
 /// transaction", which spends the reserve output of the previous checkpoint
 /// transaction and the outputs of any incoming deposits. It pays out to the the
 /// latest signatory set (in the "reserve output") and to destinations of any
-/// requested withdrawals. This transaction is included in the third batch of
-/// the `batches` deque.
+/// requested withdrawals. This transaction is the only transaction in the
+/// single batch of the `batches` deque.
 ///
-/// Checkpoints are also associated with a set of transactions which pay out to
-/// the recipients of the emergency disbursal (e.g. recovery wallets of nBTC
-/// holders), if the checkpoint transaction is not spent after a given amount of
-/// time (e.g. two weeks). These transactions are broken up into a single
-/// "intermediate emergency disbursal transaction" (in the second batch of the
-/// `batches` deque), and one or more "final emergency disbursal transactions"
-/// (in the first batch of the `batches` deque).
+/// Checkpoints do not carry emergency disbursal transactions. Those are built
+/// from the reserve of the latest confirmed checkpoint when queried, paying
+/// the nBTC balance of each account with a registered recovery script (see
+/// `Bitcoin::emergency_disbursal_txs`).
 #[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(crate = "cosmwasm_schema::serde")]
 pub struct Checkpoint {
@@ -377,8 +390,7 @@ pub struct Checkpoint {
 
     /// Pending transfers of nBTC to be processed once the checkpoint is fully
     /// signed. These transfers are processed in lockstep with the checkpointing
-    /// process in order to keep nBTC balances in sync with the emergency
-    /// disbursal.
+    /// process in order to keep nBTC balances in sync with the reserve.
     ///
     /// These transfers can be initiated by a simple nBTC send or by a deposit.    
     pub pending: Vec<(Dest, Coin)>,
@@ -404,6 +416,20 @@ pub struct Checkpoint {
     /// fast or too slow.    
     pub signed_at_btc_height: Option<u32>,
 
+    /// The time at which the checkpoint advanced to `Signing`, in seconds.
+    /// Signing times out `signing_timeout` seconds after this. Checkpoints
+    /// which advanced before this was recorded never time out.
+    #[serde(default)]
+    pub signing_since: Option<u64>,
+
+    /// Whether the checkpoint's transaction has been fully signed before, in
+    /// which case it may have been broadcast. Set when the fee of a completed
+    /// checkpoint is bumped (see `CheckpointQueue::bump_fee`), so that a
+    /// replacement which times out is never folded back into the `Building`
+    /// checkpoint.
+    #[serde(default)]
+    pub was_signed: bool,
+
     /// Whether or not to honor relayed deposits made against this signatory
     /// set. This can be used, for example, to enforce a cap on deposits into
     /// the system.    
@@ -411,6 +437,12 @@ pub struct Checkpoint {
 
     pub fees_collected: u64,
 
+    /// The signatory rewards set aside from this checkpoint's fees when it
+    /// advanced to `Signing`, in nBTC units. They are credited to the
+    /// signatories which signed it once it is complete.
+    #[serde(default)]
+    pub signatory_reward: Uint128,
+
     /// The signatory set associated with the checkpoint. Note that deposits to
     /// slightly older signatory sets can still be processed in this checkpoint,
     /// but the reserve output will be paid to the latest signatory set.
@@ -420,18 +452,19 @@ pub struct Checkpoint {
 impl Checkpoint {
     /// Creates a new checkpoint with the given signatory set.
     ///
-    /// The checkpoint will be initialized with a single empty checkpoint
-    /// transaction, a single empty intermediate emergency disbursal
-    /// transaction, and an empty batch of final emergency disbursal
-    /// transactions.
+    /// The checkpoint will be initialized with a single batch containing an
+    /// empty checkpoint transaction.
     pub fn new(sigset: SignatorySet) -> ContractResult<Self> {
         let mut checkpoint = Checkpoint {
             status: CheckpointStatus::default(),
             fee_rate: DEFAULT_FEE_RATE,
             signed_at_btc_height: None,
+            signing_since: None,
+            was_signed: false,
             deposits_enabled: true,
             sigset,
             fees_collected: 0,
+            signatory_reward: Uint128::zero(),
             pending: vec![],
             batches: vec![],
         };
@@ -559,6 +592,31 @@ impl Checkpoint {
         ))
     }
 
+    /// The txid of the checkpoint transaction, available once the checkpoint
+    /// transaction is fully signed so relayers can chain transactions onto it
+    /// before it is broadcast.
+    pub fn txid(&self) -> ContractResult<bitcoin::Txid> {
+        Ok(self.signed_checkpoint_tx()?.txid())
+    }
+
+    /// Gets the checkpoint transaction, including the witnesses of its inputs,
+    /// erroring if it is not yet fully signed.
+    pub fn signed_checkpoint_tx(&self) -> ContractResult<Transaction> {
+        let batch = self
+            .batches
+            .get(BatchType::Checkpoint as usize)
+            .ok_or_else(|| ContractError::Checkpoint("Missing checkpoint batch".into()))?;
+        if !batch.signed() {
+            return Err(ContractError::Checkpoint(
+                "Checkpoint transaction is not fully signed".into(),
+            ));
+        }
+        batch
+            .last()
+            .ok_or_else(|| ContractError::Checkpoint("Missing checkpoint transaction".into()))?
+            .to_bitcoin_tx()
+    }
+
     /// Gets the output containing the reserve funds for the checkpoint, the
     /// "reserve output". This output is owned by the latest signatory set, and
     /// is spent by the suceeding checkpoint transaction.
@@ -569,11 +627,35 @@ impl Checkpoint {
         // TODO: should return None for Building checkpoints? otherwise this
         // might return a withdrawal
         let checkpoint_tx = self.checkpoint_tx()?;
-        if let Some(output) = checkpoint_tx.output.first() {
-            Ok(Some(output.clone()))
-        } else {
-            Ok(None)
-        }
+        // a reserve below the dust limit is dropped, leaving the timestamping
+        // commitment as the first output
+        Ok(checkpoint_tx
+            .output
+            .first()
+            .filter(|output| !output.script_pubkey.is_op_return())
+            .cloned())
+    }
+
+    /// Gets all of the outputs the reserve funds are split across (see
+    /// `CheckpointConfig::reserve_output_count`), starting with the one
+    /// returned by `reserve_output`.
+    pub fn reserve_outputs(&self) -> ContractResult<Vec<TxOut>> {
+        let checkpoint_tx = self.checkpoint_tx()?;
+        Ok(checkpoint_tx
+            .output
+            .iter()
+            .take_while(|output| !output.script_pubkey.is_op_return())
+            .cloned()
+            .collect())
+    }
+
+    /// The total value of the reserve outputs, in satoshis.
+    pub fn reserve_value(&self) -> ContractResult<u64> {
+        Ok(self
+            .reserve_outputs()?
+            .iter()
+            .map(|output| output.value)
+            .sum())
     }
 
     /// Returns a list of all inputs in the checkpoint which the signatory with
@@ -602,6 +684,33 @@ impl Checkpoint {
         Ok(msgs)
     }
 
+    /// Returns the voting power with which the signatory with the given
+    /// extended public key signed the checkpoint, or zero if they were not
+    /// asked to sign it or have not signed all of its inputs.
+    pub fn signed_power(&self, xpub: &Xpub) -> ContractResult<u64> {
+        let mut power = 0;
+        for batch in &self.batches {
+            for tx in &batch.batch {
+                for input in &tx.input {
+                    let pubkey: Pubkey = xpub.derive_pubkey(input.sigset_index)?.into();
+                    if input.signatures.needs_sig(pubkey.clone()) {
+                        return Ok(0);
+                    }
+                    if let Some((_, share)) = input
+                        .signatures
+                        .shares()
+                        .into_iter()
+                        .find(|(key, _)| *key == pubkey)
+                    {
+                        power = power.max(share.power);
+                    }
+                }
+            }
+        }
+
+        Ok(power)
+    }
+
     /// Returns the number of fully-signed batches in the checkpoint.
     fn signed_batches(&self) -> usize {
         let mut signed_batches = 0;
@@ -638,6 +747,45 @@ impl Checkpoint {
         self.signed_batches() == self.batches.len()
     }
 
+    /// Returns `false` if any input which is not yet fully signed can no longer
+    /// reach its signing threshold, counting only the voting power of the
+    /// signatories which have already signed it or which still have a
+    /// registered signatory key (i.e. have not been jailed).
+    pub fn threshold_reachable(&self, store: &dyn Storage) -> ContractResult<bool> {
+        let xpubs = SIG_KEYS
+            .range(store, None, None, Order::Ascending)
+            .map(|entry| Ok(entry?.1))
+            .collect::<ContractResult<Vec<Xpub>>>()?;
+
+        // derived pubkeys of the remaining signatories, by sigset index
+        let mut live_pubkeys: BTreeMap<u32, Vec<Pubkey>> = BTreeMap::new();
+        for batch in &self.batches {
+            for tx in &batch.batch {
+                for input in &tx.input {
+                    if input.signatures.signed() {
+                        continue;
+                    }
+                    if !live_pubkeys.contains_key(&input.sigset_index) {
+                        let pubkeys = xpubs
+                            .iter()
+                            .map(|xpub| Ok(xpub.derive_pubkey(input.sigset_index)?.into()))
+                            .collect::<ContractResult<_>>()?;
+                        live_pubkeys.insert(input.sigset_index, pubkeys);
+                    }
+                    let pubkeys = &live_pubkeys[&input.sigset_index];
+                    let possible = input
+                        .signatures
+                        .possible_power(|pubkey| pubkeys.contains(pubkey));
+                    if possible <= input.signatures.threshold {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
     pub fn checkpoint_tx_miner_fees(&self) -> ContractResult<u64> {
         let mut fees = 0;
 
@@ -731,12 +879,13 @@ impl Checkpoint {
         config: &CheckpointConfig,
         timestamping_commitment: &[u8],
     ) -> ContractResult<Vec<bitcoin::TxOut>> {
-        // The reserve output is the first output of the checkpoint tx, and
-        // contains all funds held in reserve by the network.
+        // The reserve outputs are the first outputs of the checkpoint tx, and
+        // contain all funds held in reserve by the network.
         let reserve_out = bitcoin::TxOut {
             value: 0, // will be updated after counting ins/outs and fees
             script_pubkey: self.sigset.output_script(&[0u8], config.sigset_threshold)?,
         };
+        let mut outputs = vec![reserve_out; config.reserve_output_count.max(1) as usize];
 
         // The timestamping commitment output is the second output of the
         // checkpoint tx, and contains a commitment to some given data, which
@@ -747,7 +896,9 @@ impl Checkpoint {
             script_pubkey: bitcoin::Script::new_op_return(timestamping_commitment),
         };
 
-        Ok(vec![reserve_out, timestamping_commitment_out])
+        outputs.push(timestamping_commitment_out);
+
+        Ok(outputs)
     }
 }
 
@@ -810,19 +961,18 @@ pub struct BuildingCheckpoint(Checkpoint);
 
 /// The data returned by the `advance()` method of `BuildingCheckpointMut`.
 type BuildingAdvanceRes = (
-    bitcoin::OutPoint, // reserve outpoint
-    u64,               // reserve size (sats)
-    u64,               // fees paid (sats)
-    Vec<Input>,        // excess inputs
-    Vec<Output>,       // excess outputs
+    Vec<(bitcoin::OutPoint, u64)>, // reserve outpoints and their sizes (sats)
+    u64,                           // fees paid (sats)
+    Vec<Input>,                    // excess inputs
+    Vec<Output>,                   // excess outputs
 );
 
 impl BuildingCheckpoint {
     /// Advances the checkpoint to the `Signing` state.
     ///
-    /// This will generate the emergency disbursal transactions representing the
-    /// ownership of nBTC at this point in time. It will also prepare all inputs
-    /// to be signed, across the three transaction batches.
+    /// This will add the checkpoint's additional outputs to the checkpoint
+    /// transaction, move excess inputs and outputs to the next checkpoint and
+    /// prepare all inputs to be signed.
     ///
     /// This step freezes the checkpoint, and no further changes can be made to
     /// it other than adding signatures. This means at this point all
@@ -843,6 +993,29 @@ impl BuildingCheckpoint {
             checkpoint_tx.output.insert(0, Adapter::new(out.clone()));
         }
 
+        let input_selection =
+            if config.largest_first_fee_rate > 0 && self.fee_rate > config.largest_first_fee_rate {
+                InputSelection::LargestFirst
+            } else {
+                config.input_selection.clone()
+            };
+        // The reserve inputs stay first so each checkpoint spends the previous
+        // one, and the other inputs are ordered so the ones to keep come first.
+        let skip = checkpoint_tx
+            .input
+            .iter()
+            .take_while(|input| input.dest == [0u8])
+            .count();
+        match input_selection {
+            InputSelection::Oldest => {}
+            InputSelection::SmallestFirst => {
+                checkpoint_tx.input[skip..].sort_by_key(|input| input.amount);
+            }
+            InputSelection::LargestFirst => {
+                checkpoint_tx.input[skip..].sort_by_key(|input| std::cmp::Reverse(input.amount));
+            }
+        }
+
         // Remove excess inputs and outputs from the checkpoint tx, to be pushed
         // onto the suceeding checkpoint while in its `Building` state.
         let mut excess_inputs = vec![];
@@ -874,8 +1047,30 @@ impl BuildingCheckpoint {
         let reserve_value = in_amount.checked_sub(out_amount + cp_fees).ok_or_else(|| {
             ContractError::Checkpoint("Insufficient reserve value to cover miner fees".into())
         })?;
-        let reserve_out = &mut checkpoint_tx.output[0];
-        reserve_out.value = reserve_value;
+
+        // The reserve is split evenly across the reserve outputs, using fewer
+        // of them if the shares would fall below the dust limit. A reserve
+        // below the dust limit could never be spent economically and would
+        // make the transaction non-standard, so it is left to the miners
+        // instead and the next checkpoint starts without a reserve input.
+        let reserve_count = config.reserve_output_count.max(1) as u64;
+        let reserve_dust = checkpoint_tx.output[0].script_pubkey.dust_value().to_sat();
+        let reserve_outputs = (reserve_value / reserve_dust).min(reserve_count);
+        checkpoint_tx
+            .output
+            .drain(reserve_outputs as usize..reserve_count as usize);
+        for (i, reserve_out) in checkpoint_tx.output[..reserve_outputs as usize]
+            .iter_mut()
+            .enumerate()
+        {
+            // the first output also takes the remainder of the split
+            reserve_out.value = reserve_value / reserve_outputs
+                + if i == 0 {
+                    reserve_value % reserve_outputs
+                } else {
+                    0
+                };
+        }
 
         // Prepare the checkpoint tx's inputs to be signed by calculating their
         // sighashes.
@@ -892,25 +1087,23 @@ impl BuildingCheckpoint {
             input.signatures.set_message(sighash.into_inner());
         }
 
-        let reserve_outpoint = bitcoin::OutPoint {
-            txid: checkpoint_tx.txid()?,
-            vout: 0,
-        };
+        let txid = checkpoint_tx.txid()?;
+        let reserve_outpoints = checkpoint_tx.output[..reserve_outputs as usize]
+            .iter()
+            .enumerate()
+            .map(|(vout, output)| (bitcoin::OutPoint::new(txid, vout as u32), output.value))
+            .collect();
+        if reserve_outputs == 0 {
+            self.0.fees_collected += reserve_value;
+        }
 
-        Ok((
-            reserve_outpoint,
-            reserve_value,
-            cp_fees,
-            excess_inputs,
-            excess_outputs,
-        ))
+        Ok((reserve_outpoints, cp_fees, excess_inputs, excess_outputs))
     }
 
     /// Insert a transfer to the pending transfer queue.
     ///
     /// Transfers will be processed once the containing checkpoint is finished
-    /// being signed, but will be represented in this checkpoint's emergency
-    /// disbursal before they are processed.
+    /// being signed.
     pub fn insert_pending(&mut self, dest: Dest, coin: Coin) -> ContractResult<()> {
         self.pending.push((dest, coin));
         Ok(())
@@ -953,7 +1146,9 @@ impl CheckpointQueue {
     pub fn get(&self, store: &dyn Storage, index: u32) -> ContractResult<Checkpoint> {
         let queue_len = CHECKPOINTS.len(store)?;
         let index = self.get_deque_index(store, index, queue_len)?;
-        let checkpoint = CHECKPOINTS.get(store, index)?.unwrap();
+        let checkpoint = CHECKPOINTS
+            .get(store, index)?
+            .ok_or_else(|| ContractError::Checkpoint("Checkpoint not found".into()))?;
         Ok(checkpoint)
     }
 
@@ -985,7 +1180,7 @@ impl CheckpointQueue {
     ) -> ContractResult<u32> {
         let start = self.index(store) + 1 - queue_len;
         if index > self.index(store) || index < start {
-            Err(ContractError::App("Index out of bounds".into()))
+            Err(ContractError::Checkpoint("Index out of bounds".into()))
         } else {
             Ok(index - start)
         }
@@ -1191,6 +1386,42 @@ impl CheckpointQueue {
             return Ok(false);
         }
 
+        self.push_checkpoint(
+            env,
+            store,
+            btc_height,
+            should_allow_deposits,
+            timestamping_commitment,
+            parent_config,
+        )
+    }
+
+    /// Pushes a new `Building` checkpoint and advances the previous one to the
+    /// `Signing` state, carrying its reserve outputs and any excess inputs and
+    /// outputs over to the new checkpoint. Returns `false` if no checkpoint
+    /// could be pushed, e.g. if the signatory set has no quorum.
+    ///
+    /// Callers are responsible for checking that the checkpoint should be
+    /// advanced (see `should_push` and `should_rebalance`).
+    #[allow(clippy::too_many_arguments)]
+    pub fn push_checkpoint(
+        &mut self,
+        env: Env,
+        store: &mut dyn Storage,
+        btc_height: u32,
+        should_allow_deposits: bool,
+        timestamping_commitment: Vec<u8>,
+        parent_config: &BitcoinConfig,
+    ) -> ContractResult<bool> {
+        // Do not advance the `Building` checkpoint if too few signatories
+        // would be signing it.
+        if !CHECKPOINTS.is_empty(store)? {
+            let signatories = self.building(store)?.sigset.signatories.len() as u64;
+            if signatories < self.config(store).min_signatories as u64 {
+                return Ok(false);
+            }
+        }
+
         let is_not_maybe_push = self
             .maybe_push(env.clone(), store, should_allow_deposits)?
             .is_none();
@@ -1209,13 +1440,14 @@ impl CheckpointQueue {
             let sigset = prev.sigset.clone();
             let prev_fee_rate = prev.fee_rate;
             let mut building_checkpoint = BuildingCheckpoint(prev);
-            let (reserve_outpoint, reserve_value, fees_paid, excess_inputs, excess_outputs) =
+            let (reserve_outpoints, fees_paid, excess_inputs, excess_outputs) =
                 building_checkpoint.advance(timestamping_commitment, cp_fees, &config)?;
+            building_checkpoint.0.signing_since = Some(env.block.time.seconds());
             // update checkpoint
             self.set(store, prev_index, &building_checkpoint)?;
 
             let mut fee_pool = FEE_POOL.load(store)?;
-            fee_pool -= (fees_paid * parent_config.units_per_sat) as i64;
+            fee_pool -= i64::try_from(parent_config.sat_to_units(Sats(fees_paid))?)?;
             FEE_POOL.save(store, &fee_pool)?;
 
             // Adjust the fee rate for the next checkpoint based on whether past
@@ -1255,21 +1487,31 @@ impl CheckpointQueue {
                 }
             };
 
+            // Move towards the mempool fee rate last observed by signers, so
+            // checkpoints don't get stuck during fee spikes. The rate is only
+            // raised by one adjustment step per checkpoint.
+            let observed_fee_rate = OBSERVED_FEE_RATE.may_load(store)?.unwrap_or_default();
+            OBSERVED_FEE_RATE.remove(store);
+            let fee_rate =
+                fee_rate.max(observed_fee_rate.min(adjust_fee_rate(fee_rate, true, &config)));
+
             let mut building = self.building(store)?;
             building.fee_rate = fee_rate;
             let building_checkpoint_batch = &mut building.batches[BatchType::Checkpoint];
             let checkpoint_tx = &mut building_checkpoint_batch[0];
 
-            // The new checkpoint tx's first input is the reserve output from
-            // the previous checkpoint.
-            let input = Input::new(
-                reserve_outpoint,
-                &sigset,
-                &[0u8], // TODO: double-check safety
-                reserve_value,
-                config.sigset_threshold,
-            )?;
-            checkpoint_tx.input.push(input);
+            // The new checkpoint tx's first inputs are the reserve outputs
+            // from the previous checkpoint, unless they were dropped as dust.
+            for (reserve_outpoint, reserve_value) in reserve_outpoints {
+                let input = Input::new(
+                    reserve_outpoint,
+                    &sigset,
+                    &[0u8], // TODO: double-check safety
+                    reserve_value,
+                    config.sigset_threshold,
+                )?;
+                checkpoint_tx.input.push(input);
+            }
 
             // Add any excess inputs and outputs from the previous checkpoint to
             // the new checkpoint.
@@ -1279,6 +1521,25 @@ impl CheckpointQueue {
                 data.signatures = ThresholdSig::from_shares(shares);
                 checkpoint_tx.input.push(data);
             }
+            // Withdrawals can only be canceled while their checkpoint is
+            // building, except for the excess ones which are carried over.
+            // The others are kept while the checkpoint is signing, in case
+            // signing expires and they are folded back into a building
+            // checkpoint.
+            if prev_index > 0 {
+                WITHDRAWALS.remove(store, prev_index - 1);
+            }
+            let mut withdrawals = WITHDRAWALS.may_load(store, prev_index)?.unwrap_or_default();
+            let excess_start = withdrawals.len().saturating_sub(excess_outputs.len());
+            let carried: Vec<_> = withdrawals
+                .split_off(excess_start)
+                .into_iter()
+                .rev()
+                .collect();
+            WITHDRAWALS.save(store, prev_index, &withdrawals)?;
+            if !carried.is_empty() {
+                WITHDRAWALS.save(store, self.index(store), &carried)?;
+            }
             for output in excess_outputs {
                 checkpoint_tx.output.push(output);
             }
@@ -1290,13 +1551,23 @@ impl CheckpointQueue {
         Ok(true)
     }
 
-    /// Prunes old checkpoints from the queue.
-    pub fn prune(&mut self, store: &mut dyn Storage) -> ContractResult<()> {
+    /// Prunes checkpoints older than `max_age` from the queue, returning the
+    /// number removed.
+    ///
+    /// At least 10 checkpoints are always kept, and confirmed checkpoints whose
+    /// pending transfers have not been handled yet (those at or above
+    /// `first_unhandled_confirmed_index`) are never removed.
+    pub fn prune(&mut self, store: &mut dyn Storage) -> ContractResult<u32> {
         let latest = self.building(store)?.create_time();
+        let first_unhandled = FIRST_UNHANDLED_CONFIRMED_INDEX
+            .may_load(store)?
+            .unwrap_or_default();
         let mut queue_len = CHECKPOINTS.len(store)?;
+        let mut oldest_index = self.index(store) + 1 - queue_len;
+        let mut pruned = 0;
         while let Some(oldest) = CHECKPOINTS.front(store)? {
             // TODO: move to min_checkpoints field in config
-            if queue_len <= 10 {
+            if queue_len <= 10 || oldest_index >= first_unhandled {
                 break;
             }
 
@@ -1306,9 +1577,11 @@ impl CheckpointQueue {
 
             CHECKPOINTS.pop_front(store)?;
             queue_len -= 1;
+            oldest_index += 1;
+            pruned += 1;
         }
 
-        Ok(())
+        Ok(pruned)
     }
 
     pub fn should_push(
@@ -1324,6 +1597,22 @@ impl CheckpointQueue {
             return Ok(false);
         }
 
+        // Do not push if there are too many unconfirmed checkpoints.
+        //
+        // If there is a long chain of unconfirmed checkpoints, there is possibly an
+        // issue causing the transactions to not be included on Bitcoin (e.g. an
+        // invalid transaction was created, the fee rate is too low even after
+        // adjustments, Bitcoin miners are censoring the transactions, etc.), in
+        // which case the network should evaluate and fix the issue before creating
+        // more checkpoints.
+        //
+        // This will also stop the fee rate from being adjusted too high if the
+        // issue is simply with relayers failing to report the confirmation of the
+        // checkpoint transactions.
+        if self.halted(store)? {
+            return Ok(false);
+        }
+
         if !CHECKPOINTS.is_empty(store)? {
             let now = env.block.time.seconds();
             let elapsed = now - self.building(store)?.create_time();
@@ -1354,11 +1643,14 @@ impl CheckpointQueue {
             // since creating the current `Building` checkpoint.
             if elapsed < self.config(store).max_checkpoint_interval || self.index(store) == 0 {
                 let checkpoint_tx = building.checkpoint_tx()?;
-                let has_pending_deposit = if self.index(store) == 0 {
-                    !checkpoint_tx.input.is_empty()
+                let reserve_inputs = if self.index(store) > 0 {
+                    self.get(store, self.index(store) - 1)?
+                        .reserve_outputs()?
+                        .len()
                 } else {
-                    checkpoint_tx.input.len() > 1
+                    0
                 };
+                let has_pending_deposit = checkpoint_tx.input.len() > reserve_inputs;
 
                 let has_pending_withdrawal = !checkpoint_tx.output.is_empty();
                 let has_pending_transfers = building.pending.first().is_some();
@@ -1393,23 +1685,6 @@ impl CheckpointQueue {
             }
         }
 
-        // Do not push if there are too many unconfirmed checkpoints.
-        //
-        // If there is a long chain of unconfirmed checkpoints, there is possibly an
-        // issue causing the transactions to not be included on Bitcoin (e.g. an
-        // invalid transaction was created, the fee rate is too low even after
-        // adjustments, Bitcoin miners are censoring the transactions, etc.), in
-        // which case the network should evaluate and fix the issue before creating
-        // more checkpoints.
-        //
-        // This will also stop the fee rate from being adjusted too high if the
-        // issue is simply with relayers failing to report the confirmation of the
-        // checkpoint transactions.
-        let unconfs = self.num_unconfirmed(store)?;
-        if unconfs >= self.config(store).max_unconfirmed_checkpoints {
-            return Ok(false);
-        }
-
         // Increment the index. For the first checkpoint, leave the index at
         // zero.
         let mut index = self.index(store);
@@ -1434,6 +1709,42 @@ impl CheckpointQueue {
         Ok(true)
     }
 
+    /// Whether the `Building` checkpoint should be advanced early as a
+    /// consolidation checkpoint, because it spends more than
+    /// `rebalance_utxo_threshold` UTXOs while carrying no withdrawals or
+    /// transfers of its own.
+    ///
+    /// The consolidation is paid for by the fee pool, so it is only done if
+    /// the spent UTXOs are worth more than the checkpoint's miner fee.
+    pub fn should_rebalance(
+        &self,
+        store: &dyn Storage,
+        timestamping_commitment: &[u8],
+    ) -> ContractResult<bool> {
+        let config = self.config(store);
+        if config.rebalance_utxo_threshold == 0 || CHECKPOINTS.is_empty(store)? {
+            return Ok(false);
+        }
+
+        if self.signing(store)?.is_some() || self.halted(store)? {
+            return Ok(false);
+        }
+
+        let building = self.building(store)?;
+        let checkpoint_tx = &building.batches[BatchType::Checkpoint][0];
+        if !checkpoint_tx.output.is_empty() || !building.pending.is_empty() {
+            return Ok(false);
+        }
+        if checkpoint_tx.input.len() as u64 <= config.rebalance_utxo_threshold as u64 {
+            return Ok(false);
+        }
+
+        let cp_miner_fees =
+            self.calc_fee_checkpoint(store, self.index(store), timestamping_commitment)?;
+        let (input_amount, _) = building.calc_total_input_and_output(&config)?;
+        Ok(input_amount >= cp_miner_fees)
+    }
+
     pub fn calc_fee_checkpoint(
         &self,
         store: &dyn Storage,
@@ -1463,7 +1774,16 @@ impl CheckpointQueue {
 
         // Build the signatory set for the new checkpoint based on the current
         // validator set.
-        let sigset = SignatorySet::from_validator_ctx(store, env.block.time.seconds(), index)?;
+        let mut sigset = SignatorySet::from_validator_ctx(store, env.block.time.seconds(), index)?;
+
+        // The first checkpoint can't be built until a signatory has registered
+        // a key, since there is no previous signatory set to fall back on.
+        if CHECKPOINTS.is_empty(store)? {
+            if sigset.signatories.is_empty() {
+                return Err(ContractError::EmptySignatorySet);
+            }
+            self.config(store).validate()?;
+        }
 
         // Do not push if there are no validators in the signatory set.
         if sigset.possible_vp() == 0 {
@@ -1475,6 +1795,14 @@ impl CheckpointQueue {
             return Ok(None);
         }
 
+        // Only move part of the way to the new signatory set if rotating to it
+        // would move too much voting power at once.
+        let max_sigset_change = self.config(store).max_sigset_change;
+        if max_sigset_change > 0 && !CHECKPOINTS.is_empty(store)? {
+            let prev_sigset = self.building(store)?.sigset.clone();
+            sigset.limit_change(store, &prev_sigset, max_sigset_change)?;
+        }
+
         let _ = BUILDING_INDEX.save(store, &index);
         CHECKPOINTS.push_back(store, &Checkpoint::new(sigset)?)?;
 
@@ -1527,6 +1855,15 @@ impl CheckpointQueue {
 
         checkpoint.sign(api, xpub, sigs, btc_height)?;
 
+        // Fail loudly rather than leave the checkpoint stuck in the `Signing`
+        // state if too many signatories have been jailed for it to complete.
+        if matches!(status, CheckpointStatus::Signing)
+            && !checkpoint.signed()
+            && !checkpoint.threshold_reachable(store)?
+        {
+            return Err(ContractError::Checkpoint("threshold unreachable".into()));
+        }
+
         if matches!(status, CheckpointStatus::Signing) && checkpoint.signed() {
             let checkpoint_tx = checkpoint.checkpoint_tx()?;
             #[cfg(debug_assertions)]
@@ -1540,6 +1877,240 @@ impl CheckpointQueue {
         Ok(())
     }
 
+    /// Rebuilds the transaction of the most recent signing or completed
+    /// checkpoint to pay the given, higher fee rate, so that it can replace a
+    /// broadcast of the same checkpoint which is stuck in the mempool.
+    ///
+    /// The additional miner fee is deducted from the reserve output. All
+    /// existing signatures are cleared and the checkpoint returns to the
+    /// `Signing` state so the signatories sign the new transaction, and the
+    /// `Building` checkpoint is updated to spend the new reserve output.
+    /// Signing times out `signing_timeout` seconds after `now`.
+    ///
+    /// Returns the additional miner fee paid, in satoshis.
+    pub fn bump_fee(
+        &mut self,
+        store: &mut dyn Storage,
+        index: u32,
+        new_fee_rate: u64,
+        now: u64,
+    ) -> ContractResult<u64> {
+        let config = self.config(store);
+        let building_index = self.index(store);
+        if building_index == 0 || index != building_index - 1 {
+            return Err(ContractError::Checkpoint(
+                "Only the most recent unconfirmed checkpoint can be bumped".into(),
+            ));
+        }
+        if let Some(confirmed_index) = self.confirmed_index(store) {
+            if index <= confirmed_index {
+                return Err(ContractError::Checkpoint(
+                    "Checkpoint has already been confirmed".into(),
+                ));
+            }
+        }
+
+        let mut checkpoint = self.get(store, index)?;
+        if new_fee_rate <= checkpoint.fee_rate {
+            return Err(ContractError::Checkpoint(
+                "New fee rate must be higher than the current fee rate".into(),
+            ));
+        }
+        if new_fee_rate > config.max_fee_rate {
+            return Err(ContractError::Checkpoint(
+                "New fee rate exceeds the maximum fee rate".into(),
+            ));
+        }
+
+        let checkpoint_tx = &mut checkpoint.batches[BatchType::Checkpoint][0];
+        if !checkpoint_tx.input.iter().any(|input| input.rbf) {
+            return Err(ContractError::Checkpoint(
+                "Checkpoint transaction does not signal replaceability".into(),
+            ));
+        }
+        let prev_txid = checkpoint_tx.txid()?;
+
+        for input in checkpoint_tx.input.iter_mut() {
+            input.signatures.clear_sigs();
+        }
+        checkpoint_tx.signed_inputs = 0;
+
+        let est_vsize = checkpoint_tx.vsize()?
+            + checkpoint_tx
+                .input
+                .iter()
+                .map(|input| input.est_witness_vsize)
+                .sum::<u64>();
+        let additional_fee = (new_fee_rate - checkpoint.fee_rate) * est_vsize;
+
+        let reserve_out = checkpoint_tx
+            .output
+            .get_mut(0)
+            .filter(|output| !output.script_pubkey.is_op_return())
+            .ok_or_else(|| ContractError::Checkpoint("Checkpoint has no reserve output".into()))?;
+        reserve_out.value = reserve_out
+            .value
+            .checked_sub(additional_fee)
+            .ok_or_else(|| {
+                ContractError::Checkpoint("Insufficient reserve value to cover miner fees".into())
+            })?;
+        let reserve_value = reserve_out.value;
+
+        for i in 0..checkpoint_tx.input.len() {
+            checkpoint_tx.populate_input_sig_message(i)?;
+        }
+        let txid = checkpoint_tx.txid()?;
+
+        checkpoint.batches[BatchType::Checkpoint].signed_txs = 0;
+        checkpoint.fee_rate = new_fee_rate;
+        checkpoint.was_signed |= matches!(checkpoint.status, CheckpointStatus::Complete);
+        checkpoint.status = CheckpointStatus::Signing;
+        checkpoint.signed_at_btc_height = None;
+        checkpoint.signing_since = Some(now);
+        self.set(store, index, &checkpoint)?;
+
+        let mut building = self.building(store)?;
+        let building_tx = &mut building.batches[BatchType::Checkpoint][0];
+        // the fee is taken from the first reserve output, but every reserve
+        // output is now created by a transaction with a new txid
+        for input in building_tx
+            .input
+            .iter_mut()
+            .filter(|input| input.prevout.txid == prev_txid)
+        {
+            if input.prevout.vout == 0 {
+                input.amount = reserve_value;
+            }
+            input.prevout = Adapter::new(bitcoin::OutPoint::new(txid, input.prevout.vout));
+        }
+        self.set(store, building_index, &building)?;
+
+        Ok(additional_fee)
+    }
+
+    /// The index of the `Signing` checkpoint, if it has been signing for longer
+    /// than `signing_timeout` without reaching the signing threshold.
+    pub fn signing_expired(&self, store: &dyn Storage, now: u64) -> ContractResult<Option<u32>> {
+        let timeout = self.config(store).signing_timeout;
+        let signing_since = match self.signing(store)? {
+            Some(signing) if timeout > 0 => signing.signing_since,
+            _ => None,
+        };
+        match signing_since {
+            Some(signing_since) if now.saturating_sub(signing_since) > timeout => {
+                Ok(Some(self.index(store) - 1))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Aborts the `Signing` checkpoint if it has timed out (see
+    /// `signing_expired`).
+    ///
+    /// The aborted checkpoint's inputs, withdrawal outputs and pending
+    /// transfers are folded into the `Building` checkpoint, which drops the
+    /// reserve inputs spending the aborted transaction and takes over the
+    /// aborted checkpoint's index.
+    ///
+    /// A checkpoint which was fully signed before its fee was bumped may
+    /// already be on the Bitcoin network, so it is never aborted: only the
+    /// signatures on its replacement transaction are dropped, and signing of
+    /// the replacement starts over from `now`.
+    ///
+    /// Returns the miner fee the aborted checkpoint paid and the fees it had
+    /// collected, in satoshis, and the signatory rewards set aside for it, in
+    /// nBTC units, if a checkpoint was aborted.
+    pub fn expire_signing(
+        &mut self,
+        store: &mut dyn Storage,
+        now: u64,
+    ) -> ContractResult<Option<(u64, u64, Uint128)>> {
+        let index = match self.signing_expired(store, now)? {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        let mut expired = self.get(store, index)?;
+        if expired.was_signed {
+            let replacement_tx = &mut expired.batches[BatchType::Checkpoint][0];
+            for input in replacement_tx.input.iter_mut() {
+                input.signatures.clear_sigs();
+            }
+            replacement_tx.signed_inputs = 0;
+            expired.batches[BatchType::Checkpoint].signed_txs = 0;
+            expired.signing_since = Some(now);
+            self.set(store, index, &expired)?;
+            return Ok(None);
+        }
+
+        let expired_tx = &expired.batches[BatchType::Checkpoint][0];
+        let expired_txid = expired_tx.txid()?;
+        let miner_fee = expired.checkpoint_tx_miner_fees()?;
+
+        let mut building = self.building(store)?.0;
+        building.pending = expired
+            .pending
+            .iter()
+            .cloned()
+            .chain(building.pending)
+            .collect();
+        building.fees_collected += expired.fees_collected;
+
+        let building_tx = &mut building.batches[BatchType::Checkpoint][0];
+        let mut inputs: Vec<_> = expired_tx
+            .input
+            .iter()
+            .map(|input| {
+                let mut input = input.clone();
+                input.signatures.clear_sigs();
+                input
+            })
+            .collect();
+        inputs.extend(
+            building_tx
+                .input
+                .drain(..)
+                .filter(|input| input.prevout.txid != expired_txid),
+        );
+        building_tx.input = inputs;
+        // the outputs after the timestamping commitment are withdrawals, and
+        // go after the building checkpoint's own, as do their entries in
+        // `WITHDRAWALS`, so that they can be canceled again
+        building_tx.output.extend(
+            expired_tx
+                .output
+                .iter()
+                .skip_while(|output| !output.script_pubkey.is_op_return())
+                .skip(1)
+                .cloned(),
+        );
+
+        // deposits credited into the building checkpoint now belong to the
+        // aborted checkpoint's index
+        for input in building_tx.input.iter() {
+            let outpoint = input.prevout.to_string();
+            if let Some((height, Some(cp_index))) = DEPOSIT_HEIGHTS.may_load(store, &outpoint)? {
+                if cp_index == index + 1 {
+                    DEPOSIT_HEIGHTS.save(store, &outpoint, &(height, Some(index)))?;
+                }
+            }
+        }
+        let mut withdrawals = WITHDRAWALS.may_load(store, index + 1)?.unwrap_or_default();
+        withdrawals.extend(WITHDRAWALS.may_load(store, index)?.unwrap_or_default());
+        WITHDRAWALS.remove(store, index + 1);
+        WITHDRAWALS.save(store, index, &withdrawals)?;
+
+        CHECKPOINTS.pop_back(store)?;
+        BUILDING_INDEX.save(store, &index)?;
+        self.set(store, index, &building)?;
+
+        Ok(Some((
+            miner_fee,
+            expired.fees_collected,
+            expired.signatory_reward,
+        )))
+    }
+
     /// The signatory set for the checkpoint with the given index.
     pub fn sigset(&self, store: &dyn Storage, index: u32) -> ContractResult<SignatorySet> {
         Ok(self.get(store, index)?.sigset.clone())
@@ -1575,6 +2146,33 @@ impl CheckpointQueue {
         Ok(last_completed_index - confirmed_index)
     }
 
+    /// Returns `true` if the number of unconfirmed checkpoints has reached
+    /// `max_unconfirmed_checkpoints`, or the fee rate is stuck at its ceiling,
+    /// in which case no new checkpoint will be pushed to `Signing` until one
+    /// of them is confirmed.
+    pub fn halted(&self, store: &dyn Storage) -> ContractResult<bool> {
+        let unconfs = self.num_unconfirmed(store)?;
+        Ok(unconfs >= self.config(store).max_unconfirmed_checkpoints
+            || self.fee_rate_ceiling_stuck(store)?)
+    }
+
+    /// Returns `true` if the latest `max_fee_rate_checkpoints` completed
+    /// checkpoints are all unconfirmed despite paying `max_fee_rate`.
+    pub fn fee_rate_ceiling_stuck(&self, store: &dyn Storage) -> ContractResult<bool> {
+        let config = self.config(store);
+        let limit = config.max_fee_rate_checkpoints;
+        if limit == 0 || self.num_unconfirmed(store)? < limit {
+            return Ok(false);
+        }
+
+        Ok(self
+            .unconfirmed(store)?
+            .iter()
+            .rev()
+            .take(limit as usize)
+            .all(|checkpoint| checkpoint.fee_rate >= config.max_fee_rate))
+    }
+
     pub fn first_unconfirmed_index(&self, store: &dyn Storage) -> ContractResult<Option<u32>> {
         let num_unconf = self.num_unconfirmed(store)?;
         if num_unconf == 0 {
@@ -1604,14 +2202,16 @@ impl CheckpointQueue {
         }
     }
 
-    pub fn unhandled_confirmed(&self, store: &dyn Storage) -> ContractResult<Vec<u32>> {
-        if self.confirmed_index(store).is_none() {
+    /// Returns the indexes of at most `limit` confirmed checkpoints which have
+    /// not been handled yet, oldest first.
+    pub fn unhandled_confirmed(&self, store: &dyn Storage, limit: u32) -> ContractResult<Vec<u32>> {
+        let Some(confirmed_index) = self.confirmed_index(store) else {
             return Ok(vec![]);
-        }
+        };
 
         let mut out = vec![];
-        for i in self.first_unhandled_confirmed_index(store)..=self.confirmed_index(store).unwrap()
-        {
+        let start = self.first_unhandled_confirmed_index(store);
+        for i in (start..=confirmed_index).take(limit as usize) {
             let cp = self.get(store, i)?;
             if !matches!(cp.status, CheckpointStatus::Complete) {
                 #[cfg(debug_assertions)]
@@ -10,21 +10,46 @@ pub const TRANSFER_FEE: u64 = 0;
 pub const MAX_DEPOSIT_AGE: u64 = 60 * 60 * 24 * 7 * 2; // 2 weeks
 pub const MAX_CHECKPOINT_INTERVAL: u64 = 60 * 60 * 24 * 12; // 12 days. This value should be smaller than max_deposit_age & MAX_CHECKPOINT_AGE
 pub const MAX_CHECKPOINT_AGE: u64 = 60 * 60 * 24 * 7 * 3; // 3 weeks
+pub const SIGNING_TIMEOUT: u64 = 60 * 60 * 24; // 1 day
+pub const EMERGENCY_DISBURSAL_LOCK_TIME_INTERVAL: u64 = 60 * 60 * 24 * 7; // 1 week
 
 // app constants
 pub const MIN_DEPOSIT_AMOUNT: u64 = 5000; // in satoshis
 pub const MIN_WITHDRAWAL_AMOUNT: u64 = 5000; // in satoshis
+pub const MAX_MEMO_LENGTH: u64 = 4096; // in bytes
+pub const RECEIVE_HOOK_GAS_LIMIT: u64 = 1_000_000; // for a Dest::Contract receive hook
 
 pub const MAX_LENGTH: u64 = 24_192; // ~6 months
+pub const MAX_REORG_DEPTH: u32 = 100;
 pub const MAX_HEADERS_RELAY_ONE_TIME: u64 = 1000;
+pub const MAX_FORK_HEADERS: usize = 1000; // competing fork headers kept for later reorgs
+pub const DEFAULT_FEE_RATE_HISTORY_LIMIT: u32 = 20;
+pub const MAX_FEE_RATE_HISTORY_LIMIT: u32 = 100;
+pub const MAX_DEPOSIT_HISTORY_LIMIT: u32 = 100;
+pub const MAX_RESERVE_UTXOS_LIMIT: u32 = 100;
+pub const MAX_LIST_CHECKPOINTS_LIMIT: u32 = 100;
+pub const MAX_EMERGENCY_DISBURSAL_TXS_LIMIT: u32 = 100;
+pub const MAX_PENDING_CHECKPOINTS: u32 = 10; // confirmed checkpoints handled per block
 pub const MAX_TIME_INCREASE: u32 = 2 * 60 * 60;
 pub const RETARGET_INTERVAL: u32 = 2016;
 pub const TARGET_SPACING: u32 = 10 * 60;
 pub const TARGET_TIMESPAN: u32 = RETARGET_INTERVAL * TARGET_SPACING;
 pub const MAX_TARGET: u32 = 0x1d00ffff;
+pub const SIGNET_MAX_TARGET: u32 = 0x1e0377ae;
+pub const REGTEST_MAX_TARGET: u32 = 0x207fffff;
 
 // TODO: move to config
 pub const MAX_SIGNATORIES: u64 = 20;
 pub const SIGSET_THRESHOLD: (u64, u64) = (2, 3);
+pub const SLASH_FRACTION: (u64, u64) = (1, 20); // 5%
 
 pub const BTC_NATIVE_TOKEN_DENOM: &str = "obtc";
+
+/// The version of the layout of the contract's stored state, bumped whenever
+/// a migration has to rewrite it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The commitment of each signatory set's static deposit address. Deposits to
+/// it carry their destination's commitment bytes in an OP_RETURN output
+/// instead.
+pub const OP_RETURN_DEPOSIT_COMMITMENT: &[u8] = &[1];
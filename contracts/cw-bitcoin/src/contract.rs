@@ -2,6 +2,7 @@
 use cosmwasm_std::entry_point;
 
 use crate::{
+    constants::SCHEMA_VERSION,
     entrypoints::*,
     error::ContractError,
     header::HeaderQueue,
@@ -9,11 +10,14 @@ use crate::{
     msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg},
     state::{
         BITCOIN_CONFIG, BUILDING_INDEX, CHECKPOINT_CONFIG, CONFIG, FEE_POOL,
-        FIRST_UNHANDLED_CONFIRMED_INDEX,
+        FIRST_UNHANDLED_CONFIRMED_INDEX, STORED_SCHEMA_VERSION,
     },
+    threshold_sig::SignatureEncoding,
 };
 
-use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+use cosmwasm_std::{
+    to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult,
+};
 use cw2::set_contract_version;
 
 // version info for migration info
@@ -28,6 +32,7 @@ pub fn instantiate(
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    STORED_SCHEMA_VERSION.save(deps.storage, &SCHEMA_VERSION)?;
 
     CONFIG.save(
         deps.storage,
@@ -45,7 +50,9 @@ pub fn instantiate(
 
     // Set up config
     CHECKPOINT_CONFIG.save(deps.storage, &CheckpointConfig::default())?;
-    BITCOIN_CONFIG.save(deps.storage, &&BitcoinConfig::default())?;
+    let bitcoin_config = BitcoinConfig::default();
+    bitcoin_config.validate()?;
+    BITCOIN_CONFIG.save(deps.storage, &bitcoin_config)?;
     FEE_POOL.save(deps.storage, &0)?;
 
     // Set up checkpoint index
@@ -67,27 +74,76 @@ pub fn execute(
             btc_tx,
             btc_height,
             btc_proof,
+            block_index,
             btc_vout,
             sigset_index,
             dest,
         } => relay_deposit(
             env,
+            deps.api,
             deps.storage,
             btc_tx,
             btc_height,
             btc_proof,
+            block_index,
             btc_vout,
             sigset_index,
             dest,
         ),
+        ExecuteMsg::RelayDepositMulti {
+            btc_tx,
+            btc_height,
+            btc_proof,
+            outputs,
+            sigset_index,
+        } => relay_deposit_multi(
+            env,
+            deps.api,
+            deps.storage,
+            btc_tx,
+            btc_height,
+            btc_proof,
+            outputs,
+            sigset_index,
+        ),
         ExecuteMsg::RelayCheckpoint {
             btc_height,
             btc_proof,
             cp_index,
-        } => relay_checkpoint(deps.storage, btc_height, btc_proof, cp_index),
+            observed_fee_rate,
+        } => relay_checkpoint(
+            deps.storage,
+            info,
+            btc_height,
+            btc_proof,
+            cp_index,
+            observed_fee_rate,
+        ),
+        ExecuteMsg::ConfirmCheckpoint {
+            index,
+            tx,
+            height,
+            proof,
+            block_index,
+            observed_fee_rate,
+        } => confirm_checkpoint(
+            deps.storage,
+            info,
+            index,
+            tx,
+            height,
+            proof,
+            block_index,
+            observed_fee_rate,
+        ),
         ExecuteMsg::WithdrawToBitcoin { script_pubkey } => {
             withdraw_to_bitcoin(deps.storage, info, env, script_pubkey)
         }
+        ExecuteMsg::CancelWithdrawal {
+            checkpoint_index,
+            output_index,
+        } => cancel_withdrawal(deps.storage, info, checkpoint_index, output_index),
+        ExecuteMsg::Transfer { to } => transfer(deps.api, deps.storage, info, env, to),
         ExecuteMsg::RelayHeaders { headers } => relay_headers(deps.storage, headers),
         ExecuteMsg::UpdateHeaderConfig { config } => {
             update_header_config(deps.storage, info, config)
@@ -102,25 +158,58 @@ pub fn execute(
             xpub,
             sigs,
             checkpoint_index,
-            btc_height,
-        } => submit_checkpoint_signature(
+            btc_height: _,
+        } => submit_signatures(
             deps.api,
             deps.storage,
+            info,
             xpub,
-            sigs,
+            sigs.into_iter().map(|sig| Binary::from(sig.0)).collect(),
             checkpoint_index,
-            btc_height,
+            SignatureEncoding::Compact,
+        ),
+        ExecuteMsg::SubmitSignatures {
+            xpub,
+            sigs,
+            sigset_index,
+            encoding,
+        } => submit_signatures(
+            deps.api,
+            deps.storage,
+            info,
+            xpub,
+            sigs,
+            sigset_index,
+            encoding.unwrap_or_default(),
         ),
         ExecuteMsg::SubmitRecoverySignature { xpub, sigs } => {
             submit_recovery_signature(deps.api, deps.storage, xpub, sigs)
         }
         ExecuteMsg::SetSignatoryKey { xpub } => set_signatory_key(deps.storage, info, xpub),
+        ExecuteMsg::SetRecoveryScript { script } => set_recovery_script(deps.storage, info, script),
         ExecuteMsg::AddValidators { addrs, infos } => {
             add_validators(deps.storage, info, addrs, infos)
         }
+        ExecuteMsg::RegisterValidator {
+            operator,
+            consensus_key,
+            power,
+        } => register_validator(deps.api, deps.storage, info, operator, consensus_key, power),
+        ExecuteMsg::UnjailValidator {
+            operator,
+            consensus_key,
+        } => unjail_validator(deps.api, deps.storage, info, operator, consensus_key),
         ExecuteMsg::RegisterDenom { subdenom, metadata } => {
             register_denom(deps.storage, info, subdenom, metadata)
         }
+        ExecuteMsg::BumpCheckpointFee { index, fee_rate } => {
+            bump_checkpoint_fee(deps.storage, info, env, index, fee_rate)
+        }
+        ExecuteMsg::ClaimSignatoryRewards {} => claim_signatory_rewards(deps.storage, info),
+        ExecuteMsg::PruneCheckpoints {} => prune_checkpoints(deps.storage, info),
+        ExecuteMsg::SetIbcChannelAllowlist { channels } => {
+            set_ibc_channel_allowlist(deps.storage, info, channels)
+        }
         #[cfg(test)]
         ExecuteMsg::TriggerBeginBlock { hash } => clock_end_block(&env, deps.storage, hash),
     }
@@ -133,6 +222,11 @@ pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractE
     }
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    receive_reply(deps.storage, msg)
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
@@ -142,17 +236,128 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::WithdrawalFees { address, index } => {
             to_json_binary(&query_withdrawal_fees(deps.storage, address, index)?)
         }
+        QueryMsg::EstimateWithdrawalFee { script, amount } => to_json_binary(
+            &query_estimate_withdrawal_fee(deps.storage, script, amount)?,
+        ),
+        QueryMsg::ScriptForAddress { address } => {
+            to_json_binary(&query_script_for_address(deps.storage, address)?)
+        }
+        QueryMsg::AddressForScript { script } => {
+            to_json_binary(&query_address_for_script(deps.storage, script)?)
+        }
         QueryMsg::CompletedCheckpointTxs { limit } => {
             to_json_binary(&query_complete_checkpoint_txs(deps.storage, limit)?)
         }
         QueryMsg::SignedRecoveryTxs {} => to_json_binary(&query_signed_recovery_txs(deps.storage)?),
         QueryMsg::HeaderHeight {} => to_json_binary(&query_header_height(deps.storage)?),
+        QueryMsg::HeaderTip {} => to_json_binary(&query_header_tip(deps.storage)?),
+        QueryMsg::FeePool {} => to_json_binary(&query_fee_pool(deps.storage)?),
+        QueryMsg::VerifyDepositProof {
+            tx,
+            height,
+            proof,
+            block_index,
+        } => to_json_binary(&query_verify_deposit_proof(
+            deps.storage,
+            tx,
+            height,
+            proof,
+            block_index,
+        )?),
+        QueryMsg::CurrentFeeRate {} => to_json_binary(&query_current_fee_rate(deps.storage)?),
+        QueryMsg::ReserveValue {} => to_json_binary(&query_reserve_value(deps.storage)?),
+        QueryMsg::TokenInfo {} => to_json_binary(&query_token_info(deps.querier, deps.storage)?),
+        QueryMsg::TotalSupply {} => {
+            to_json_binary(&query_total_supply(deps.querier, deps.storage)?)
+        }
+        QueryMsg::SigningProgress { index } => {
+            to_json_binary(&query_signing_progress(deps.storage, index)?)
+        }
+        QueryMsg::DepositStatus { txid, vout } => {
+            to_json_binary(&query_deposit_status(deps.storage, txid, vout)?)
+        }
+        QueryMsg::DepositHistory {
+            address,
+            start_after,
+            limit,
+        } => to_json_binary(&query_deposit_history(
+            deps.storage,
+            address,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::SignerLiveness {} => to_json_binary(&query_signer_liveness(deps.storage)?),
+        QueryMsg::Signatories {} => to_json_binary(&query_signatories(deps.storage)?),
         QueryMsg::SidechainBlockHash {} => {
             to_json_binary(&query_sidechain_block_hash(deps.storage)?)
         }
         QueryMsg::CheckpointByIndex { index } => {
             to_json_binary(&query_checkpoint_by_index(deps.storage, index)?)
         }
+        QueryMsg::CheckpointTxid { index } => {
+            to_json_binary(&query_checkpoint_txid(deps.storage, index)?)
+        }
+        QueryMsg::CheckpointTxHex { index } => {
+            to_json_binary(&query_checkpoint_tx_hex(deps.storage, index)?)
+        }
+        QueryMsg::GetCheckpoint { index } => {
+            to_json_binary(&query_checkpoint(deps.storage, index)?)
+        }
+        QueryMsg::GetDepositAddress { dest } => {
+            to_json_binary(&query_deposit_address(deps.storage, dest)?)
+        }
+        QueryMsg::PendingDeposits {} => to_json_binary(&query_pending_deposits(deps.storage)?),
+        QueryMsg::Balance { address } => {
+            to_json_binary(&query_balance(deps.querier, deps.storage, address)?)
+        }
+        QueryMsg::ChangeRates {
+            interval_start,
+            interval_end,
+            sigset_index,
+        } => to_json_binary(&query_change_rates(
+            deps.storage,
+            interval_start,
+            interval_end,
+            sigset_index,
+        )?),
+        QueryMsg::FeeRateHistory { limit } => {
+            to_json_binary(&query_fee_rate_history(deps.storage, limit)?)
+        }
+        QueryMsg::CheckpointQueueStatus {} => {
+            to_json_binary(&query_checkpoint_queue_status(deps.storage)?)
+        }
+        QueryMsg::NextCheckpointEta {} => to_json_binary(&query_next_checkpoint_eta(deps.storage)?),
+        QueryMsg::WithdrawalsEnabled {} => {
+            to_json_binary(&query_withdrawals_enabled(deps.storage)?)
+        }
+        QueryMsg::EmergencyDisbursalLockTime {} => {
+            to_json_binary(&query_emergency_disbursal_lock_time(deps.storage)?)
+        }
+        QueryMsg::BuildingCheckpoint {} => {
+            to_json_binary(&query_building_checkpoint(deps.storage)?)
+        }
+        QueryMsg::ListCheckpoints { start_after, limit } => {
+            to_json_binary(&query_list_checkpoints(deps.storage, start_after, limit)?)
+        }
+        QueryMsg::ReserveUtxos { start_after, limit } => {
+            to_json_binary(&query_reserve_utxos(deps.storage, start_after, limit)?)
+        }
+        QueryMsg::SignatorySet { index } => {
+            to_json_binary(&query_signatory_set(deps.storage, index)?)
+        }
+        QueryMsg::SignatorySetCommitment { index } => {
+            to_json_binary(&query_signatory_set_commitment(deps.storage, index)?)
+        }
+        QueryMsg::GetDerivePubkey { xpub, sigset_index } => {
+            to_json_binary(&query_derive_pubkey(xpub, sigset_index)?)
+        }
+        QueryMsg::GetDerivePubkeys {
+            xpubs,
+            sigset_index,
+        } => to_json_binary(&query_derive_pubkeys(xpubs, sigset_index)?),
+        QueryMsg::ToSign { xpub, index } => {
+            to_json_binary(&query_to_sign(deps.storage, xpub, index)?)
+        }
         QueryMsg::SigningRecoveryTxs { xpub } => to_json_binary(&query_signing_recovery_txs(
             deps.querier,
             deps.storage,
@@ -166,6 +371,9 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             xpub,
             checkpoint_index,
         )?),
+        QueryMsg::EmergencyDisbursalTxs { start_after, limit } => to_json_binary(
+            &query_emergency_disbursal_txs(deps.querier, deps.storage, start_after, limit)?,
+        ),
         QueryMsg::CompletedIndex {} => to_json_binary(&query_completed_index(deps.storage)?),
         QueryMsg::BuildingIndex {} => to_json_binary(&query_building_index(deps.storage)?),
         QueryMsg::ConfirmedIndex {} => to_json_binary(&query_comfirmed_index(deps.storage)?),
@@ -177,7 +385,23 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    // stored parameters must still satisfy the invariants of the new code
+    CHECKPOINT_CONFIG.load(deps.storage)?.validate()?;
+    BITCOIN_CONFIG.load(deps.storage)?.validate()?;
+
+    let original_schema_version = STORED_SCHEMA_VERSION.may_load(deps.storage)?.unwrap_or(0);
+    if original_schema_version > SCHEMA_VERSION {
+        return Err(ContractError::App(format!(
+            "Cannot migrate from schema version {} to older version {}",
+            original_schema_version, SCHEMA_VERSION
+        )));
+    }
+
     let original_version =
         cw2::ensure_from_older_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    Ok(Response::new().add_attribute("new_version", original_version.to_string()))
+    STORED_SCHEMA_VERSION.save(deps.storage, &SCHEMA_VERSION)?;
+    Ok(Response::new()
+        .add_attribute("original_version", original_version.to_string())
+        .add_attribute("new_version", CONTRACT_VERSION)
+        .add_attribute("schema_version", SCHEMA_VERSION.to_string()))
 }
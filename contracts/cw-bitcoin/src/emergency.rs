@@ -0,0 +1,173 @@
+use crate::{
+    checkpoint::{BitcoinTx, Checkpoint, Input, Output},
+    error::{ContractError, ContractResult},
+    interface::CheckpointConfig,
+    state::{get_full_btc_denom, BITCOIN_CONFIG, RECOVERY_SCRIPTS},
+};
+use bitcoin::{consensus::encode::serialize, OutPoint, Script, TxOut};
+use cosmwasm_std::{Order, QuerierWrapper, Storage, Uint64};
+use std::iter::Peekable;
+
+/// Builds the emergency disbursal transactions for the given checkpoint, which
+/// pay the nBTC balance of every account with a registered recovery script out
+/// of the checkpoint's reserve.
+///
+/// The transactions form a chain and must be broadcast in order: the first
+/// spends the reserve outputs of the checkpoint transaction, and each following
+/// transaction spends the change output of the one before it, which is always
+/// its last output. Each transaction pays a batch of accounts, ordered by
+/// address, and returns the rest of the reserve to the checkpoint's signatory
+/// set in its change output. Miner fees are paid out of the reserve at the
+/// checkpoint's fee rate, so every account receives its full balance.
+///
+/// All of the transactions are locked until
+/// `emergency_disbursal_lock_time_interval` seconds after the checkpoint was
+/// created, so they can only be mined if no newer checkpoint has spent the
+/// reserve by then.
+///
+/// Returns no transactions if there are no accounts to pay out.
+pub fn disbursal_txs(
+    querier: QuerierWrapper,
+    store: &dyn Storage,
+    checkpoint: &Checkpoint,
+    config: &CheckpointConfig,
+) -> ContractResult<Vec<BitcoinTx>> {
+    let mut accounts = disbursal_outputs(querier, store, config)?
+        .into_iter()
+        .peekable();
+    if accounts.peek().is_none() {
+        return Ok(vec![]);
+    }
+
+    let sigset = &checkpoint.sigset;
+    let threshold = config.sigset_threshold;
+    let reserve_script = sigset.output_script(&[0u8], threshold)?;
+    let lock_time = u32::try_from(
+        Uint64::from(checkpoint.create_time())
+            .checked_add(Uint64::from(config.emergency_disbursal_lock_time_interval))?
+            .u64(),
+    )?;
+
+    let checkpoint_tx = checkpoint.checkpoint_tx()?;
+    let txid = checkpoint_tx.txid();
+    let mut prevouts: Vec<_> = checkpoint_tx
+        .output
+        .iter()
+        .enumerate()
+        .take_while(|(_, output)| !output.script_pubkey.is_op_return())
+        .map(|(vout, output)| (OutPoint::new(txid, vout as u32), output.value))
+        .collect();
+
+    let mut txs = vec![];
+    while accounts.peek().is_some() {
+        let mut tx = BitcoinTx::with_lock_time(lock_time);
+        for (prevout, value) in prevouts.drain(..) {
+            tx.input
+                .push(Input::new(prevout, sigset, &[0u8], value, threshold)?);
+        }
+
+        let change_output: Output = TxOut {
+            value: 0,
+            script_pubkey: reserve_script.clone(),
+        }
+        .into();
+        tx.output.push(change_output.clone());
+        let base_vsize = est_vsize(&tx)?;
+        tx.output = take_outputs(
+            &mut accounts,
+            base_vsize,
+            config.emergency_disbursal_max_tx_size,
+        );
+        tx.output.push(change_output);
+
+        let fee = checkpoint.fee_rate * est_vsize(&tx)?;
+        let input_value = tx.input.iter().map(|input| input.amount).sum::<u64>();
+        let change = input_value
+            .checked_sub(tx.value()? + fee)
+            .ok_or_else(insufficient_reserve)?;
+
+        // The change output carries the rest of the reserve on to the next
+        // transaction. The last transaction leaves a dust change to the miners.
+        let vout = tx.output.len() - 1;
+        if change >= reserve_script.dust_value().to_sat() {
+            tx.output[vout].value = change;
+            prevouts.push((OutPoint::new(tx.txid()?, vout as u32), change));
+        } else if accounts.peek().is_none() {
+            tx.output.pop();
+        } else {
+            return Err(insufficient_reserve());
+        }
+
+        txs.push(tx);
+    }
+
+    Ok(txs)
+}
+
+fn insufficient_reserve() -> ContractError {
+    ContractError::Checkpoint("Insufficient reserve for emergency disbursal".to_string())
+}
+
+/// Returns the outputs paying the nBTC balance of every account with a
+/// registered recovery script to that script, ordered by address. Accounts
+/// holding less than `emergency_disbursal_min_tx_amt` or the dust limit of their
+/// script are left out.
+fn disbursal_outputs(
+    querier: QuerierWrapper,
+    store: &dyn Storage,
+    config: &CheckpointConfig,
+) -> ContractResult<Vec<TxOut>> {
+    let units_per_sat = BITCOIN_CONFIG.load(store)?.units_per_sat;
+    let denom = get_full_btc_denom(store)?;
+
+    let mut outputs = vec![];
+    for entry in RECOVERY_SCRIPTS.range(store, None, None, Order::Ascending) {
+        let (address, script) = entry?;
+        let balance = querier.query_balance(address, denom.clone())?;
+        let value = u64::try_from(balance.amount.u128() / units_per_sat as u128)?;
+        let script_pubkey: Script = script.into_inner();
+        if value < config.emergency_disbursal_min_tx_amt
+            || value < script_pubkey.dust_value().to_sat()
+        {
+            continue;
+        }
+        outputs.push(TxOut {
+            value,
+            script_pubkey,
+        });
+    }
+
+    Ok(outputs)
+}
+
+/// Takes outputs for a single transaction of `base_vsize` virtual bytes
+/// without them, stopping before the transaction would exceed `max_tx_size`
+/// (zero for no limit). At least one output is always taken.
+fn take_outputs<I: Iterator<Item = TxOut>>(
+    outputs: &mut Peekable<I>,
+    base_vsize: u64,
+    max_tx_size: u64,
+) -> Vec<Output> {
+    // leave room for the output count to grow to a 3-byte varint
+    let mut vsize = base_vsize + 2;
+    let mut taken = vec![];
+    while let Some(output) = outputs.peek() {
+        let output_vsize = serialize(output).len() as u64;
+        if !taken.is_empty() && max_tx_size != 0 && vsize + output_vsize > max_tx_size {
+            break;
+        }
+        vsize += output_vsize;
+        taken.extend(outputs.next().map(Into::into));
+    }
+    taken
+}
+
+/// The estimated size of the transaction once all of its inputs are signed, in
+/// virtual bytes.
+fn est_vsize(tx: &BitcoinTx) -> ContractResult<u64> {
+    Ok(tx.vsize()?
+        + tx.input
+            .iter()
+            .map(|input| input.est_witness_vsize)
+            .sum::<u64>())
+}
@@ -1,26 +1,45 @@
 use crate::{
     adapter::{Adapter, HashBinary},
-    app::{Bitcoin, ConsensusKey},
-    error::ContractResult,
-    header::{HeaderList, HeaderQueue, WrappedHeader},
+    app::{validate_withdrawal_script, Bitcoin, ConsensusKey, DepositOutcome},
+    checkpoint::CheckpointQueue,
+    error::{ContractError, ContractResult},
+    header::{HeaderQueue, WrappedHeader},
     interface::{BitcoinConfig, CheckpointConfig, Dest, HeaderConfig, Xpub},
-    state::{get_full_btc_denom, BITCOIN_CONFIG, CHECKPOINT_CONFIG, CONFIG, SIGNERS, VALIDATORS},
-    threshold_sig::Signature,
+    state::{
+        get_full_btc_denom, BITCOIN_CONFIG, CHECKPOINT_CONFIG, CONFIG, IBC_CHANNEL_ALLOWLIST,
+        JAILED, SIGNERS, SIG_KEYS, VALIDATORS,
+    },
+    threshold_sig::{Signature, SignatureEncoding},
 };
 use bitcoin::{util::merkleblock::PartialMerkleTree, Transaction};
 
 use cosmwasm_std::{
-    to_json_binary, wasm_execute, Api, Env, MessageInfo, Response, Storage, WasmMsg,
+    to_json_binary, wasm_execute, Api, Binary, Env, MessageInfo, Response, Storage, Uint128,
+    WasmMsg,
 };
 use token_bindings::Metadata;
 
-/// TODO: check logic
+pub fn set_ibc_channel_allowlist(
+    store: &mut dyn Storage,
+    info: MessageInfo,
+    channels: Vec<String>,
+) -> ContractResult<Response> {
+    if info.sender != CONFIG.load(store)?.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    IBC_CHANNEL_ALLOWLIST.save(store, &channels)?;
+    Ok(Response::new().add_attribute("action", "set_ibc_channel_allowlist"))
+}
+
 pub fn update_checkpoint_config(
     store: &mut dyn Storage,
     info: MessageInfo,
     config: CheckpointConfig,
 ) -> ContractResult<Response> {
-    assert_eq!(info.sender, CONFIG.load(store)?.owner);
+    if info.sender != CONFIG.load(store)?.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    config.validate()?;
     CHECKPOINT_CONFIG.save(store, &config)?;
     Ok(Response::new().add_attribute("action", "update_checkpoint_config"))
 }
@@ -32,6 +51,7 @@ pub fn update_bitcoin_config(
     config: BitcoinConfig,
 ) -> ContractResult<Response> {
     assert_eq!(info.sender, CONFIG.load(store)?.owner);
+    config.validate()?;
     BITCOIN_CONFIG.save(store, &config)?;
     Ok(Response::new().add_attribute("action", "update_bitcoin_config"))
 }
@@ -53,35 +73,94 @@ pub fn relay_headers(
     store: &mut dyn Storage,
     headers: Vec<WrappedHeader>,
 ) -> ContractResult<Response> {
-    // let header_config = HEADER_CONFIG.load(store)?;
-    let mut header_queue = HeaderQueue::default();
-    header_queue.add(store, HeaderList::from(headers))?;
+    let mut btc = Bitcoin::default();
+    btc.relay_headers(store, headers)?;
     Ok(Response::new().add_attribute("action", "add_headers"))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn relay_deposit(
     env: Env,
+    api: &dyn Api,
     store: &mut dyn Storage,
     btc_tx: Adapter<Transaction>,
     btc_height: u32,
     btc_proof: Adapter<PartialMerkleTree>,
+    block_index: Option<u32>,
     btc_vout: u32,
     sigset_index: u32,
     dest: Dest,
 ) -> ContractResult<Response> {
-    // dest validation?
+    dest.validate_addr(api)?;
     let mut btc = Bitcoin::default();
-    let response = Response::new().add_attribute("action", "relay_deposit");
-    btc.relay_deposit(
+    let amount = btc_tx
+        .output
+        .get(btc_vout as usize)
+        .map_or(0, |output| output.value);
+    let mut response = Response::new()
+        .add_attribute("action", "relay_deposit")
+        .add_attribute("dest", dest.to_receiver_addr())
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("sigset_index", sigset_index.to_string())
+        .add_attribute("txid", btc_tx.txid().to_string());
+    let outcome = btc.relay_deposit(
         env.clone(),
         store,
         btc_tx,
         btc_height,
         btc_proof,
+        block_index,
         btc_vout,
         sigset_index,
         dest,
     )?;
+    if outcome == DepositOutcome::TooSmall {
+        response = response.add_attribute("deposit_ignored", "too_small");
+    }
+
+    Ok(response)
+}
+
+pub fn relay_deposit_multi(
+    env: Env,
+    api: &dyn Api,
+    store: &mut dyn Storage,
+    btc_tx: Adapter<Transaction>,
+    btc_height: u32,
+    btc_proof: Adapter<PartialMerkleTree>,
+    outputs: Vec<(u32, Dest)>,
+    sigset_index: u32,
+) -> ContractResult<Response> {
+    let mut btc = Bitcoin::default();
+    let mut response = Response::new()
+        .add_attribute("action", "relay_deposit_multi")
+        .add_attribute("sigset_index", sigset_index.to_string())
+        .add_attribute("txid", btc_tx.txid().to_string());
+    for (btc_vout, dest) in &outputs {
+        dest.validate_addr(api)?;
+        let amount = btc_tx
+            .output
+            .get(*btc_vout as usize)
+            .map_or(0, |output| output.value);
+        response = response
+            .add_attribute("dest", dest.to_receiver_addr())
+            .add_attribute("amount", amount.to_string());
+    }
+
+    let outcomes = btc.relay_deposit_multi(
+        env,
+        store,
+        btc_tx,
+        btc_height,
+        btc_proof,
+        outputs,
+        sigset_index,
+    )?;
+    for outcome in outcomes {
+        if outcome == DepositOutcome::TooSmall {
+            response = response.add_attribute("deposit_ignored", "too_small");
+        }
+    }
 
     Ok(response)
 }
@@ -92,16 +171,23 @@ pub fn withdraw_to_bitcoin(
     env: Env,
     script_pubkey: Adapter<bitcoin::Script>,
 ) -> ContractResult<Response> {
+    validate_withdrawal_script(&script_pubkey)?;
     let mut btc = Bitcoin::default();
 
     let mut cosmos_msgs = vec![];
+    let mut response = Response::new()
+        .add_attribute("action", "withdraw_to_bitcoin")
+        .add_attribute("script", format!("{:x}", *script_pubkey));
 
     let config = CONFIG.load(store)?;
     for fund in info.funds {
         let denom = get_full_btc_denom(store)?;
         if fund.denom == denom {
             let amount = fund.amount;
-            btc.add_withdrawal(store, script_pubkey.clone(), amount)?;
+            let fee = btc.add_withdrawal(store, &info.sender, script_pubkey.clone(), amount)?;
+            response = response
+                .add_attribute("amount", amount.to_string())
+                .add_attribute("fee", fee.to_string());
 
             // burn here
             cosmos_msgs.push(WasmMsg::Execute {
@@ -116,35 +202,187 @@ pub fn withdraw_to_bitcoin(
         }
     }
 
-    let response = Response::new().add_attribute("action", "withdraw_to_bitcoin");
     Ok(response.add_messages(cosmos_msgs))
 }
 
+/// Transfers the nBTC sent with the message to `to` through the checkpoint's
+/// pending transfers. The funds are burned now and the amount left after the
+/// transfer fee is minted to `to` once the checkpoint is signed.
+pub fn transfer(
+    api: &dyn Api,
+    store: &mut dyn Storage,
+    info: MessageInfo,
+    env: Env,
+    to: String,
+) -> ContractResult<Response> {
+    let to = api.addr_validate(&to)?;
+    let denom = get_full_btc_denom(store)?;
+    let amount = info
+        .funds
+        .iter()
+        .filter(|fund| fund.denom == denom)
+        .map(|fund| fund.amount)
+        .sum::<Uint128>();
+
+    let mut btc = Bitcoin::default();
+    let net_amount = btc.transfer(store, &info.sender, to.clone(), amount)?;
+
+    let config = CONFIG.load(store)?;
+    let msg = WasmMsg::Execute {
+        contract_addr: config.token_factory_addr.into_string(),
+        msg: to_json_binary(&tokenfactory::msg::ExecuteMsg::BurnTokens {
+            amount,
+            denom,
+            burn_from_address: env.contract.address.to_string(),
+        })?,
+        funds: vec![],
+    };
+
+    Ok(Response::new()
+        .add_attribute("action", "transfer")
+        .add_attribute("to", to)
+        .add_attribute("amount", net_amount.to_string())
+        .add_attribute("fee", (amount - net_amount).to_string())
+        .add_message(msg))
+}
+
+pub fn prune_checkpoints(store: &mut dyn Storage, info: MessageInfo) -> ContractResult<Response> {
+    if info.sender != CONFIG.load(store)?.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let pruned = CheckpointQueue::default().prune(store)?;
+    Ok(Response::new()
+        .add_attribute("action", "prune_checkpoints")
+        .add_attribute("pruned", pruned.to_string()))
+}
+
+pub fn cancel_withdrawal(
+    store: &mut dyn Storage,
+    info: MessageInfo,
+    checkpoint_index: u32,
+    output_index: u32,
+) -> ContractResult<Response> {
+    let mut btc = Bitcoin::default();
+    let amount = btc.cancel_withdrawal(store, &info.sender, checkpoint_index, output_index)?;
+
+    // re-mint the burned nBTC to the withdrawer
+    let config = CONFIG.load(store)?;
+    let msg = WasmMsg::Execute {
+        contract_addr: config.token_factory_addr.into_string(),
+        msg: to_json_binary(&tokenfactory::msg::ExecuteMsg::MintTokens {
+            denom: get_full_btc_denom(store)?,
+            amount,
+            mint_to_address: info.sender.to_string(),
+        })?,
+        funds: vec![],
+    };
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_withdrawal")
+        .add_attribute("checkpoint_index", checkpoint_index.to_string())
+        .add_attribute("output_index", output_index.to_string())
+        .add_attribute("amount", amount.to_string())
+        .add_message(msg))
+}
+
+pub fn claim_signatory_rewards(
+    store: &mut dyn Storage,
+    info: MessageInfo,
+) -> ContractResult<Response> {
+    let mut btc = Bitcoin::default();
+    let amount = btc.claim_signatory_rewards(store, &info.sender)?;
+
+    let config = CONFIG.load(store)?;
+    let msg = WasmMsg::Execute {
+        contract_addr: config.token_factory_addr.into_string(),
+        msg: to_json_binary(&tokenfactory::msg::ExecuteMsg::MintTokens {
+            denom: get_full_btc_denom(store)?,
+            amount,
+            mint_to_address: info.sender.to_string(),
+        })?,
+        funds: vec![],
+    };
+
+    Ok(Response::new()
+        .add_attribute("action", "claim_signatory_rewards")
+        .add_attribute("amount", amount.to_string())
+        .add_message(msg))
+}
+
 pub fn relay_checkpoint(
     store: &mut dyn Storage,
+    info: MessageInfo,
     btc_height: u32,
     btc_proof: Adapter<PartialMerkleTree>,
     cp_index: u32,
+    observed_fee_rate: Option<u64>,
 ) -> ContractResult<Response> {
     let mut btc = Bitcoin::default();
     let response = Response::new().add_attribute("action", "relay_checkpoint");
     btc.relay_checkpoint(store, btc_height, btc_proof, cp_index)?;
+    if let Some(fee_rate) = observed_fee_rate {
+        btc.observe_fee_rate(store, &info.sender, fee_rate)?;
+    }
     Ok(response)
 }
 
-pub fn submit_checkpoint_signature(
+#[allow(clippy::too_many_arguments)]
+pub fn confirm_checkpoint(
+    store: &mut dyn Storage,
+    info: MessageInfo,
+    index: u32,
+    tx: Binary,
+    height: u32,
+    proof: Binary,
+    block_index: u32,
+    observed_fee_rate: Option<u64>,
+) -> ContractResult<Response> {
+    let tx: Transaction = bitcoin::consensus::deserialize(tx.as_slice())?;
+    let proof: PartialMerkleTree = bitcoin::consensus::deserialize(proof.as_slice())?;
+    let mut btc = Bitcoin::default();
+    btc.confirm_checkpoint(store, index, tx, height, proof, block_index)?;
+    if let Some(fee_rate) = observed_fee_rate {
+        btc.observe_fee_rate(store, &info.sender, fee_rate)?;
+    }
+    Ok(Response::new()
+        .add_attribute("action", "confirm_checkpoint")
+        .add_attribute("index", index.to_string()))
+}
+
+/// Submits a signatory's signatures for the checkpoint whose signatory set is
+/// at `sigset_index`, on behalf of the validator which registered `xpub`.
+/// The signing height is taken from the header queue. Signatures may be
+/// compact or DER encoded, as given by `encoding`.
+pub fn submit_signatures(
     api: &dyn Api,
     store: &mut dyn Storage,
+    info: MessageInfo,
     xpub: HashBinary<Xpub>,
-    sigs: Vec<Signature>,
-    cp_index: u32,
-    btc_height: u32,
+    sigs: Vec<Binary>,
+    sigset_index: u32,
+    encoding: SignatureEncoding,
 ) -> ContractResult<Response> {
-    let btc = Bitcoin::default();
-    let mut checkpoints = btc.checkpoints;
-    checkpoints.sign(api, store, &xpub.0, sigs, cp_index, btc_height)?;
-    let response = Response::new().add_attribute("action", "submit_checkpoint_signature");
-    Ok(response)
+    let cons_key = SIGNERS
+        .load(store, info.sender.as_str())
+        .map_err(|_| ContractError::Unauthorized {})?;
+    if SIG_KEYS.may_load(store, &cons_key)? != Some(xpub.0) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut btc = Bitcoin::default();
+    let btc_height = btc.headers.height(store)?;
+    let sigs = sigs
+        .iter()
+        .map(|sig| Signature::from_encoded(sig, &encoding))
+        .collect::<ContractResult<_>>()?;
+    btc.checkpoints
+        .sign(api, store, &xpub.0, sigs, sigset_index, btc_height)?;
+    btc.credit_signatory_rewards(store, sigset_index)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "submit_signatures")
+        .add_attribute("sigset_index", sigset_index.to_string())
+        .add_attribute("btc_height", btc_height.to_string()))
 }
 
 pub fn submit_recovery_signature(
@@ -171,6 +409,18 @@ pub fn set_signatory_key(
     Ok(response)
 }
 
+pub fn set_recovery_script(
+    store: &mut dyn Storage,
+    info: MessageInfo,
+    script: Binary,
+) -> ContractResult<Response> {
+    let mut btc = Bitcoin::default();
+    let script = bitcoin::Script::from(script.to_vec());
+    btc.set_recovery_script(store, &info.sender, Adapter::new(script))?;
+    let response = Response::new().add_attribute("action", "set_recovery_script");
+    Ok(response)
+}
+
 // TODO: Add check only owners of this contract can call
 pub fn add_validators(
     store: &mut dyn Storage,
@@ -179,14 +429,119 @@ pub fn add_validators(
     infos: Vec<(u64, ConsensusKey)>,
 ) -> ContractResult<Response> {
     assert_eq!(info.sender, CONFIG.load(store)?.owner);
+    let mut response = Response::new().add_attribute("action", "add_validators");
     for (addr, (power, cons_key)) in addrs.iter().zip(infos) {
+        // jailed validators are only restored by `unjail_validator`
+        if JAILED.has(store, &cons_key) {
+            response = response.add_attribute(
+                "skipped_jailed",
+                Binary::from(cons_key.as_slice()).to_base64(),
+            );
+            continue;
+        }
         SIGNERS.save(store, addr, &cons_key)?;
         VALIDATORS.save(store, &cons_key, &(power.to_owned(), addr.to_owned()))?;
     }
-    let response = Response::new().add_attribute("action", "add_validators");
     Ok(response)
 }
 
+/// Registers a validator's consensus key and voting power, linking it to the
+/// operator address which submits its signatures. Only the owner can register
+/// validators, and each consensus key and operator can only be registered
+/// once.
+pub fn register_validator(
+    api: &dyn Api,
+    store: &mut dyn Storage,
+    info: MessageInfo,
+    operator: String,
+    consensus_key: Binary,
+    power: u64,
+) -> ContractResult<Response> {
+    if info.sender != CONFIG.load(store)?.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let operator = api.addr_validate(&operator)?;
+    let cons_key: ConsensusKey = consensus_key
+        .as_slice()
+        .try_into()
+        .map_err(|_| ContractError::App("Consensus key must be 32 bytes".to_string()))?;
+
+    if VALIDATORS.has(store, &cons_key) {
+        return Err(ContractError::App(
+            "Consensus key is already registered".to_string(),
+        ));
+    }
+    if JAILED.has(store, &cons_key) {
+        return Err(ContractError::App("Consensus key is jailed".to_string()));
+    }
+    if SIGNERS.has(store, operator.as_str()) {
+        return Err(ContractError::App(
+            "Operator is already registered".to_string(),
+        ));
+    }
+
+    SIGNERS.save(store, operator.as_str(), &cons_key)?;
+    VALIDATORS.save(store, &cons_key, &(power, operator.to_string()))?;
+
+    Ok(Response::new()
+        .add_attribute("action", "register_validator")
+        .add_attribute("operator", operator))
+}
+
+/// Restores a jailed validator, linking it to the given operator address, at
+/// the voting power it was left with after slashing. Only the owner can unjail
+/// validators. The operator has to submit its signatory key again before the
+/// validator is included in a signatory set.
+pub fn unjail_validator(
+    api: &dyn Api,
+    store: &mut dyn Storage,
+    info: MessageInfo,
+    operator: String,
+    consensus_key: Binary,
+) -> ContractResult<Response> {
+    if info.sender != CONFIG.load(store)?.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let operator = api.addr_validate(&operator)?;
+    let cons_key: ConsensusKey = consensus_key
+        .as_slice()
+        .try_into()
+        .map_err(|_| ContractError::App("Consensus key must be 32 bytes".to_string()))?;
+
+    let power = JAILED
+        .may_load(store, &cons_key)?
+        .ok_or_else(|| ContractError::App("Consensus key is not jailed".to_string()))?;
+    if SIGNERS.has(store, operator.as_str()) {
+        return Err(ContractError::App(
+            "Operator is already registered".to_string(),
+        ));
+    }
+
+    JAILED.remove(store, &cons_key);
+    SIGNERS.save(store, operator.as_str(), &cons_key)?;
+    VALIDATORS.save(store, &cons_key, &(power, operator.to_string()))?;
+
+    Ok(Response::new()
+        .add_attribute("action", "unjail_validator")
+        .add_attribute("operator", operator)
+        .add_attribute("power", power.to_string()))
+}
+
+pub fn bump_checkpoint_fee(
+    store: &mut dyn Storage,
+    info: MessageInfo,
+    env: Env,
+    index: u32,
+    fee_rate: u64,
+) -> ContractResult<Response> {
+    if info.sender != CONFIG.load(store)?.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut btc = Bitcoin::default();
+    btc.bump_checkpoint_fee(&env, store, index, fee_rate)?;
+    Ok(Response::new().add_attribute("action", "bump_checkpoint_fee"))
+}
+
 // TODO: Add check only owners of this contract can call
 pub fn register_denom(
     store: &mut dyn Storage,
@@ -1,30 +1,62 @@
-use bitcoin::{BlockHash, Transaction};
-use cosmwasm_std::{Order, QuerierWrapper, Storage};
+use bitcoin::{util::merkleblock::PartialMerkleTree, BlockHash, Transaction};
+use cosmwasm_std::{Binary, Coin, Order, QuerierWrapper, Storage, Uint128, Uint256, Uint64};
+use cw_storage_plus::Bound;
 use std::str::FromStr;
 
 use crate::{
     adapter::{Adapter, HashBinary},
     app::{Bitcoin, ConsensusKey},
-    checkpoint::{BuildingCheckpoint, Checkpoint, CheckpointQueue, CheckpointStatus},
+    checkpoint::{BatchType, Checkpoint, CheckpointQueue, CheckpointStatus},
+    constants::{
+        MAX_DEPOSIT_HISTORY_LIMIT, MAX_EMERGENCY_DISBURSAL_TXS_LIMIT, MAX_FEE_RATE_HISTORY_LIMIT,
+        MAX_LIST_CHECKPOINTS_LIMIT, MAX_RESERVE_UTXOS_LIMIT,
+    },
     error::{ContractError, ContractResult},
     header::HeaderQueue,
-    interface::Xpub,
+    interface::{ChangeRates, DepositRecord, Dest, Xpub},
+    msg::{
+        BuildingCheckpointResponse, CheckpointInputResponse, CheckpointOutputResponse,
+        CheckpointQueueStatusResponse, CheckpointResponse, CheckpointSummaryResponse,
+        DepositAddressResponse, DepositStatus, EmergencyDisbursalTxResponse, FeeRateResponse,
+        HeaderTipResponse, NextCheckpointEtaResponse, ReserveUtxoResponse, SignatoryInfoResponse,
+        SignatoryResponse, SignatorySetResponse, SignerLivenessResponse, SigningProgressResponse,
+        TokenInfoResponse, TotalSupplyResponse, WithdrawalFeeEstimateResponse,
+        WithdrawalsEnabledResponse,
+    },
     recovery::{RecoveryTxs, SignedRecoveryTx},
     signatory::SignatorySet,
-    state::{header_height, OUTPOINTS, SIG_KEYS},
+    state::{
+        get_full_btc_denom, header_height, BITCOIN_CONFIG, DEPOSIT_HEIGHTS, DEPOSIT_HISTORY,
+        HEADERS, INVALIDATED_DEPOSITS, JAILED, OUTPOINTS, SIGNERS, SIG_KEYS, VALIDATORS,
+    },
+    units::{MSats, Sats},
 };
 
 pub fn query_header_height(store: &dyn Storage) -> ContractResult<u32> {
     header_height(store)
 }
 
+/// Returns the last header in the header queue, which relayers should extend
+/// when submitting new headers.
+pub fn query_header_tip(store: &dyn Storage) -> ContractResult<HeaderTipResponse> {
+    let tip = HEADERS
+        .back(store)?
+        .ok_or_else(|| ContractError::Header("HeaderQueue is empty".into()))?;
+    Ok(HeaderTipResponse {
+        hash: HashBinary(tip.block_hash()),
+        height: tip.height(),
+        time: tip.time(),
+        chain_work: Uint256::from_be_bytes(tip.chain_work.to_be_bytes()).to_string(),
+    })
+}
+
 pub fn query_deposit_fees(store: &dyn Storage, index: Option<u32>) -> ContractResult<u64> {
     let btc = Bitcoin::default();
 
     let checkpoint = btc.get_checkpoint(store, index)?;
     let input_vsize = checkpoint.sigset.est_witness_vsize() + 40;
     let deposit_fees = btc.calc_minimum_deposit_fees(store, input_vsize, checkpoint.fee_rate)?;
-    Ok(deposit_fees)
+    Ok(deposit_fees.0)
 }
 
 pub fn query_withdrawal_fees(
@@ -39,7 +71,326 @@ pub fn query_withdrawal_fees(
     let script = btc_address.script_pubkey();
     let withdrawal_fees =
         btc.calc_minimum_withdrawal_fees(store, script.len() as u64, checkpoint.fee_rate)?;
-    Ok(withdrawal_fees)
+    Ok(withdrawal_fees.0)
+}
+
+/// Estimates the miner fee which would be deducted from a withdrawal of
+/// `amount` to `script` at the `Building` checkpoint's fee rate.
+pub fn query_estimate_withdrawal_fee(
+    store: &dyn Storage,
+    script: Binary,
+    amount: Uint128,
+) -> ContractResult<WithdrawalFeeEstimateResponse> {
+    let btc = Bitcoin::default();
+    let config = btc.config(store)?;
+    let fee_rate = btc.checkpoints.building(store)?.fee_rate;
+    let fee: Uint128 = btc
+        .calc_minimum_withdrawal_fees(store, script.len() as u64, fee_rate)?
+        .into();
+    let net_amount = amount.checked_sub(fee).map_err(|_| {
+        ContractError::App("Withdrawal is too small to pay its miner fee".to_string())
+    })?;
+    if config.units_to_sat(MSats::try_from(net_amount)?)? < Sats(config.min_withdrawal_amount) {
+        return Err(ContractError::App(
+            "Withdrawal is smaller than minimum amount".to_string(),
+        ));
+    }
+
+    Ok(WithdrawalFeeEstimateResponse {
+        gross_amount: amount,
+        fee,
+        net_amount,
+    })
+}
+
+/// Parses a Bitcoin address for the configured network and returns its
+/// scriptPubKey.
+pub fn query_script_for_address(store: &dyn Storage, address: String) -> ContractResult<Binary> {
+    let btc = Bitcoin::default();
+    let address = bitcoin::Address::from_str(address.as_str())?;
+    if !address.is_valid_for_network(btc.network(store)?) {
+        return Err(ContractError::Address(
+            "Address is not valid for the configured network".to_string(),
+        ));
+    }
+    Ok(Binary::from(address.script_pubkey().into_bytes()))
+}
+
+/// Returns the address of the given scriptPubKey on the configured network.
+pub fn query_address_for_script(store: &dyn Storage, script: Binary) -> ContractResult<String> {
+    let btc = Bitcoin::default();
+    let script = bitcoin::Script::from(script.to_vec());
+    let address = bitcoin::Address::from_script(&script, btc.network(store)?)
+        .ok_or_else(|| ContractError::Address("Script has no address form".to_string()))?;
+    Ok(address.to_string())
+}
+
+/// Returns the fee pool balance, in nBTC units. Negative if more has been paid
+/// in miner fees than has been collected from users.
+pub fn query_fee_pool(store: &dyn Storage) -> ContractResult<i64> {
+    Bitcoin::default().fee_pool(store)
+}
+
+/// Checks that `proof` matches the merkle root of the header at `height` and
+/// proves `tx` is included at `block_index`, without relaying anything.
+pub fn query_verify_deposit_proof(
+    store: &dyn Storage,
+    tx: Binary,
+    height: u32,
+    proof: Binary,
+    block_index: u32,
+) -> ContractResult<bool> {
+    let tx: Transaction = bitcoin::consensus::deserialize(tx.as_slice())?;
+    let proof: PartialMerkleTree = bitcoin::consensus::deserialize(proof.as_slice())?;
+    let header = HeaderQueue::default()
+        .get_by_height(store, height)?
+        .ok_or_else(|| ContractError::App("Invalid bitcoin block height".to_string()))?;
+
+    let mut txids = vec![];
+    let mut block_indexes = vec![];
+    let merkle_root = proof
+        .extract_matches(&mut txids, &mut block_indexes)
+        .map_err(|_| ContractError::BitcoinMerkleBlockError)?;
+    if merkle_root != header.merkle_root() {
+        return Err(ContractError::BitcoinMerkleBlockError);
+    }
+
+    let txid = tx.txid();
+    Ok(txids
+        .into_iter()
+        .zip(block_indexes)
+        .any(|(id, index)| id == txid && index == block_index))
+}
+
+/// Returns the total value held in reserve by the confirmed checkpoint chain,
+/// in satoshis.
+pub fn query_reserve_value(store: &dyn Storage) -> ContractResult<u64> {
+    Bitcoin::default().reserve_value(store)
+}
+
+/// Returns the nBTC denom, its supply and its number of decimals.
+pub fn query_token_info(
+    querier: QuerierWrapper,
+    store: &dyn Storage,
+) -> ContractResult<TokenInfoResponse> {
+    let config = BITCOIN_CONFIG.load(store)?;
+    let denom = get_full_btc_denom(store)?;
+    Ok(TokenInfoResponse {
+        total_supply: querier.query_supply(denom.clone())?.amount,
+        denom,
+        decimals: 8 + config.units_per_sat.ilog10(),
+    })
+}
+
+/// Returns the nBTC supply, which is the sum of all account balances, in both
+/// nBTC units and satoshis.
+pub fn query_total_supply(
+    querier: QuerierWrapper,
+    store: &dyn Storage,
+) -> ContractResult<TotalSupplyResponse> {
+    let config = BITCOIN_CONFIG.load(store)?;
+    let denom = get_full_btc_denom(store)?;
+    let units = querier.query_supply(denom.clone())?.amount;
+    Ok(TotalSupplyResponse {
+        denom,
+        units,
+        sats: config.units_to_sat(MSats::try_from(units)?)?.0,
+    })
+}
+
+/// Returns the reserve outputs of the last checkpoint confirmed on the Bitcoin
+/// blockchain, which are the UTXOs the bridge controls, ordered by outpoint
+/// and starting after the outpoint `start_after` (as `txid:vout`).
+pub fn query_reserve_utxos(
+    store: &dyn Storage,
+    start_after: Option<String>,
+    limit: u32,
+) -> ContractResult<Vec<ReserveUtxoResponse>> {
+    let checkpoints = CheckpointQueue::default();
+    let index = match checkpoints.confirmed_index(store) {
+        Some(index) => index,
+        None => return Ok(vec![]),
+    };
+    let checkpoint = checkpoints.get(store, index)?;
+    let txid = checkpoint.txid()?;
+    let start_after = start_after
+        .map(|outpoint| bitcoin::OutPoint::from_str(&outpoint))
+        .transpose()?;
+
+    let mut utxos: Vec<_> = checkpoint
+        .reserve_outputs()?
+        .into_iter()
+        .enumerate()
+        .map(|(vout, output)| (bitcoin::OutPoint::new(txid, vout as u32), output.value))
+        .filter(|(outpoint, _)| start_after.map_or(true, |start| *outpoint > start))
+        .collect();
+    utxos.sort();
+
+    Ok(utxos
+        .into_iter()
+        .take(limit.min(MAX_RESERVE_UTXOS_LIMIT) as usize)
+        .map(|(outpoint, amount)| ReserveUtxoResponse {
+            txid: HashBinary(outpoint.txid),
+            vout: outpoint.vout,
+            amount,
+            sigset_index: checkpoint.sigset.index,
+        })
+        .collect())
+}
+
+/// Returns the fee rate paid by the `Building` checkpoint, along with the
+/// parameters it is adjusted by.
+pub fn query_current_fee_rate(store: &dyn Storage) -> ContractResult<FeeRateResponse> {
+    let checkpoints = CheckpointQueue::default();
+    let config = checkpoints.config(store);
+    Ok(FeeRateResponse {
+        fee_rate: checkpoints.building(store)?.fee_rate,
+        target_checkpoint_inclusion: config.target_checkpoint_inclusion,
+        min_fee_rate: config.min_fee_rate,
+        max_fee_rate: config.max_fee_rate,
+    })
+}
+
+/// Returns how close the checkpoint at `index` is to being fully signed. A
+/// checkpoint is only signed once all of its inputs are, so the signed voting
+/// power is reported for the input furthest from its threshold.
+pub fn query_signing_progress(
+    store: &dyn Storage,
+    index: u32,
+) -> ContractResult<SigningProgressResponse> {
+    let checkpoint = CheckpointQueue::default().get(store, index)?;
+    let inputs: Vec<_> = checkpoint
+        .batches
+        .iter()
+        .flat_map(|batch| batch.iter())
+        .flat_map(|tx| tx.input.iter())
+        .collect();
+
+    let signed_inputs = inputs
+        .iter()
+        .filter(|input| input.signatures.signed())
+        .count() as u32;
+    let (signed_voting_power, threshold_voting_power) = inputs
+        .iter()
+        .map(|input| (input.signatures.signed, input.signatures.threshold))
+        .min_by_key(|(signed, threshold)| *signed as i128 - *threshold as i128)
+        .unwrap_or_default();
+
+    Ok(SigningProgressResponse {
+        signed_voting_power,
+        threshold_voting_power,
+        signed_inputs,
+        total_inputs: inputs.len() as u32,
+        threshold_met: signed_inputs as usize == inputs.len(),
+    })
+}
+
+/// Returns the status of the deposit at output `vout` of `txid`.
+pub fn query_deposit_status(
+    store: &dyn Storage,
+    txid: String,
+    vout: u32,
+) -> ContractResult<DepositStatus> {
+    let outpoint = bitcoin::OutPoint::from_str(&format!("{}:{}", txid, vout))?;
+    if INVALIDATED_DEPOSITS.has(store, &outpoint.to_string()) {
+        return Ok(DepositStatus::Invalidated);
+    }
+    let (height, checkpoint_index) = match DEPOSIT_HEIGHTS.may_load(store, &outpoint.to_string())? {
+        Some(deposit) => deposit,
+        None => return Ok(DepositStatus::Unseen),
+    };
+
+    let btc = Bitcoin::default();
+    let min_confirmations = btc.config(store)?.min_confirmations;
+    if btc.headers.height(store)?.saturating_sub(height) < min_confirmations {
+        return Ok(DepositStatus::Seen);
+    }
+
+    if let Some(index) = checkpoint_index {
+        // checkpoints are only pruned once they are complete
+        if index < btc.checkpoints.first_index(store)?
+            || btc.checkpoints.get(store, index)?.status == CheckpointStatus::Complete
+        {
+            return Ok(DepositStatus::Credited);
+        }
+    }
+    Ok(DepositStatus::Confirmed)
+}
+
+/// Returns the deposits credited to `address`, oldest first, starting after
+/// the record with index `start_after`.
+pub fn query_deposit_history(
+    store: &dyn Storage,
+    address: String,
+    start_after: Option<u64>,
+    limit: u32,
+) -> ContractResult<Vec<DepositRecord>> {
+    let limit = limit.min(MAX_DEPOSIT_HISTORY_LIMIT) as usize;
+    DEPOSIT_HISTORY
+        .prefix(&address)
+        .range(
+            store,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|entry| Ok(entry?.1))
+        .collect()
+}
+
+/// Returns how many checkpoints in a row each registered signer has missed,
+/// along with the signers which have been jailed.
+pub fn query_signer_liveness(store: &dyn Storage) -> ContractResult<Vec<SignerLivenessResponse>> {
+    let btc = Bitcoin::default();
+    let mut liveness = vec![];
+    for entry in SIGNERS.range(store, None, None, Order::Ascending) {
+        let (address, cons_key) = entry?;
+        let missed_checkpoints = match SIG_KEYS.may_load(store, &cons_key)? {
+            Some(xpub) => btc.missed_checkpoints(store, &xpub)?,
+            None => 0,
+        };
+        liveness.push(SignerLivenessResponse {
+            address: Some(address),
+            cons_key,
+            missed_checkpoints,
+            jailed: JAILED.has(store, &cons_key),
+        });
+    }
+
+    // jailed signers are removed from the signer set, so are listed separately
+    for cons_key in JAILED.keys(store, None, None, Order::Ascending) {
+        let cons_key = cons_key?;
+        if liveness.iter().any(|signer| signer.cons_key == cons_key) {
+            continue;
+        }
+        liveness.push(SignerLivenessResponse {
+            address: None,
+            cons_key,
+            missed_checkpoints: 0,
+            jailed: true,
+        });
+    }
+
+    Ok(liveness)
+}
+
+/// Returns every registered signer along with their xpub and voting power.
+pub fn query_signatories(store: &dyn Storage) -> ContractResult<Vec<SignatoryInfoResponse>> {
+    SIGNERS
+        .range(store, None, None, Order::Ascending)
+        .map(|entry| {
+            let (address, cons_key) = entry?;
+            let voting_power = VALIDATORS
+                .may_load(store, &cons_key)?
+                .map_or(0, |(power, _)| power);
+            Ok(SignatoryInfoResponse {
+                address,
+                xpub: SIG_KEYS.may_load(store, &cons_key)?.map(HashBinary),
+                voting_power,
+            })
+        })
+        .collect()
 }
 
 pub fn query_sidechain_block_hash(store: &dyn Storage) -> ContractResult<HashBinary<BlockHash>> {
@@ -49,16 +400,324 @@ pub fn query_sidechain_block_hash(store: &dyn Storage) -> ContractResult<HashBin
     Ok(hash)
 }
 
+pub fn query_checkpoint_txid(
+    store: &dyn Storage,
+    index: u32,
+) -> ContractResult<HashBinary<bitcoin::Txid>> {
+    let checkpoints = CheckpointQueue::default();
+    let checkpoint = checkpoints.get(store, index)?;
+    Ok(HashBinary(checkpoint.txid()?))
+}
+
+/// Returns the fully-signed transaction of the checkpoint at `index` as
+/// lowercase hex, ready to be broadcast to the Bitcoin network.
+pub fn query_checkpoint_tx_hex(store: &dyn Storage, index: u32) -> ContractResult<String> {
+    let checkpoints = CheckpointQueue::default();
+    let checkpoint = checkpoints.get(store, index)?;
+    Ok(bitcoin::consensus::encode::serialize_hex(
+        &checkpoint.signed_checkpoint_tx()?,
+    ))
+}
+
 pub fn query_checkpoint_by_index(store: &dyn Storage, index: u32) -> ContractResult<Checkpoint> {
     let checkpoints = CheckpointQueue::default();
     let checkpoint = checkpoints.get(store, index)?;
     Ok(checkpoint)
 }
 
-pub fn query_building_checkpoint(store: &dyn Storage) -> ContractResult<BuildingCheckpoint> {
+/// Returns the checkpoint transaction and its signing metadata at the given
+/// index, defaulting to the last completed checkpoint.
+pub fn query_checkpoint(
+    store: &dyn Storage,
+    index: Option<u32>,
+) -> ContractResult<CheckpointResponse> {
+    let checkpoints = CheckpointQueue::default();
+    let index = match index {
+        Some(index) => index,
+        None => checkpoints.last_completed_index(store)?,
+    };
+    let checkpoint = checkpoints.get(store, index)?;
+    Ok(CheckpointResponse {
+        index,
+        tx: checkpoint.checkpoint_tx()?,
+        sigset_index: checkpoint.sigset.index(),
+        fee_rate: checkpoint.fee_rate,
+        status: checkpoint.status,
+    })
+}
+
+/// Returns the deposit script and address committing to the given
+/// destination, secured by the signatory set of the `Building` checkpoint.
+pub fn query_deposit_address(
+    store: &dyn Storage,
+    dest: Dest,
+) -> ContractResult<DepositAddressResponse> {
+    dest.validate()?;
+    dest.validate_channel(store)?;
+    let btc = Bitcoin::default();
+    let sigset = btc.checkpoints.building(store)?.sigset.clone();
+    let threshold = btc.checkpoints.config(store).sigset_threshold;
+    let script = sigset.output_script(&dest.commitment_bytes()?, threshold)?;
+    let address = bitcoin::Address::from_script(&script, btc.network(store)?)
+        .ok_or_else(|| ContractError::Checkpoint("Unable to derive deposit address".to_string()))?;
+    Ok(DepositAddressResponse {
+        address: address.to_string(),
+        script: Adapter::new(script),
+        sigset_index: sigset.index(),
+    })
+}
+
+/// Returns the pending transfers of confirmed checkpoints which have not been
+/// handled yet, leaving them in place.
+pub fn query_pending_deposits(store: &dyn Storage) -> ContractResult<Vec<Vec<(Dest, Coin)>>> {
+    let btc = Bitcoin::default();
+    btc.pending_confirmed(store)
+}
+
+/// Returns the nBTC held by `address`. Credited deposits are minted through the
+/// token factory, so this is the address's bank balance of the nBTC denom, in
+/// `units_per_sat` units.
+pub fn query_balance(
+    querier: QuerierWrapper,
+    store: &dyn Storage,
+    address: String,
+) -> ContractResult<Coin> {
+    let denom = get_full_btc_denom(store)?;
+    Ok(querier.query_balance(address, denom)?)
+}
+
+/// Returns the rates at which the reserve and the signatory set changed between
+/// `interval_start` and `interval_end`, ignoring changes from signatory sets at
+/// or before `sigset_index`.
+pub fn query_change_rates(
+    store: &dyn Storage,
+    interval_start: u64,
+    interval_end: u64,
+    sigset_index: u32,
+) -> ContractResult<ChangeRates> {
+    if interval_end < interval_start {
+        return Err(ContractError::App(
+            "Interval end must not be before interval start".to_string(),
+        ));
+    }
+
+    let btc = Bitcoin::default();
+    btc.change_rates(
+        store,
+        interval_end - interval_start,
+        interval_end,
+        sigset_index,
+    )
+}
+
+/// Returns `(index, fee_rate, confirmed)` for the most recent completed
+/// checkpoints, newest first.
+pub fn query_fee_rate_history(
+    store: &dyn Storage,
+    limit: u32,
+) -> ContractResult<Vec<(u32, u64, bool)>> {
+    let checkpoints = CheckpointQueue::default();
+    let limit = limit.min(MAX_FEE_RATE_HISTORY_LIMIT);
+    let completed = checkpoints.completed(store, limit)?;
+    if completed.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let first_index = checkpoints.last_completed_index(store)? + 1 - completed.len() as u32;
+    let confirmed_index = checkpoints.confirmed_index(store);
+    Ok(completed
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, checkpoint)| {
+            let index = first_index + i as u32;
+            let confirmed = confirmed_index.map_or(false, |confirmed| index <= confirmed);
+            (index, checkpoint.fee_rate, confirmed)
+        })
+        .collect())
+}
+
+/// Returns the public key a signatory with the given xpub uses for the
+/// signatory set at `sigset_index`, as compressed secp256k1 bytes.
+pub fn query_derive_pubkey(xpub: HashBinary<Xpub>, sigset_index: u32) -> ContractResult<Binary> {
+    let pubkey = xpub.derive_pubkey(sigset_index)?;
+    Ok(Binary::from(pubkey.serialize().to_vec()))
+}
+
+/// Like `query_derive_pubkey`, but derives the public keys of several
+/// signatories at once, returned in the same order as `xpubs`.
+pub fn query_derive_pubkeys(
+    xpubs: Vec<HashBinary<Xpub>>,
+    sigset_index: u32,
+) -> ContractResult<Vec<Binary>> {
+    xpubs
+        .into_iter()
+        .map(|xpub| query_derive_pubkey(xpub, sigset_index))
+        .collect()
+}
+
+/// Returns the signatory set securing the checkpoint at the given index, along
+/// with the signing threshold its scripts were built with.
+pub fn query_signatory_set(
+    store: &dyn Storage,
+    index: u32,
+) -> ContractResult<SignatorySetResponse> {
+    let checkpoints = CheckpointQueue::default();
+    // the pubkeys were derived from the signatories' xpubs at this index when
+    // the checkpoint was created
+    let sigset = checkpoints.get(store, index)?.sigset;
+    Ok(SignatorySetResponse {
+        index: sigset.index(),
+        signatories: sigset
+            .iter()
+            .map(|signatory| SignatoryResponse {
+                pubkey: Binary::from(signatory.pubkey.as_slice()),
+                voting_power: signatory.voting_power,
+            })
+            .collect(),
+        present_vp: sigset.present_vp(),
+        possible_vp: sigset.possible_vp(),
+        threshold: checkpoints.config(store).sigset_threshold,
+    })
+}
+
+/// Returns the commitment to the signatory set of the checkpoint at `index`
+/// (see `SignatorySet::commitment`).
+pub fn query_signatory_set_commitment(store: &dyn Storage, index: u32) -> ContractResult<Binary> {
+    let sigset = CheckpointQueue::default().get(store, index)?.sigset;
+    Ok(Binary::from(sigset.commitment().to_vec()))
+}
+
+/// Summarizes the state of the checkpoint queue, including how close it is to
+/// `max_unconfirmed_checkpoints`.
+pub fn query_checkpoint_queue_status(
+    store: &dyn Storage,
+) -> ContractResult<CheckpointQueueStatusResponse> {
+    let checkpoints = CheckpointQueue::default();
+    let building_tx = checkpoints.building(store)?.checkpoint_tx()?;
+    Ok(CheckpointQueueStatusResponse {
+        index: checkpoints.index(store),
+        confirmed_index: checkpoints.confirmed_index(store),
+        first_unhandled_confirmed_cp_index: checkpoints.first_unhandled_confirmed_index(store),
+        num_unconfirmed: checkpoints.num_unconfirmed(store)?,
+        max_unconfirmed_checkpoints: checkpoints.config(store).max_unconfirmed_checkpoints,
+        building_inputs: building_tx.input.len() as u32,
+        building_outputs: building_tx.output.len() as u32,
+    })
+}
+
+/// Returns the earliest and latest times at which the `Building` checkpoint can
+/// advance to `Signing`, based on when it was created and the configured
+/// checkpoint intervals.
+pub fn query_next_checkpoint_eta(store: &dyn Storage) -> ContractResult<NextCheckpointEtaResponse> {
+    let checkpoints = CheckpointQueue::default();
+    let create_time = checkpoints.building(store)?.create_time();
+    let config = checkpoints.config(store);
+    Ok(NextCheckpointEtaResponse {
+        earliest: create_time.saturating_add(config.min_checkpoint_interval),
+        latest: create_time.saturating_add(config.max_checkpoint_interval),
+    })
+}
+
+/// Returns whether withdrawals are enabled, along with the number of
+/// checkpoints produced so far and the number required.
+pub fn query_withdrawals_enabled(
+    store: &dyn Storage,
+) -> ContractResult<WithdrawalsEnabledResponse> {
+    let btc = Bitcoin::default();
+    let checkpoints = btc.checkpoints.len(store)?;
+    let min_withdrawal_checkpoints = btc.config(store)?.min_withdrawal_checkpoints;
+    Ok(WithdrawalsEnabledResponse {
+        enabled: checkpoints >= min_withdrawal_checkpoints,
+        checkpoints,
+        min_withdrawal_checkpoints,
+    })
+}
+
+/// Returns the absolute lock time, in seconds since the Unix epoch, at which an
+/// emergency disbursal of the latest confirmed checkpoint's reserve may be
+/// broadcast.
+pub fn query_emergency_disbursal_lock_time(store: &dyn Storage) -> ContractResult<u64> {
+    let checkpoints = CheckpointQueue::default();
+    let index = checkpoints
+        .confirmed_index(store)
+        .ok_or_else(|| ContractError::Checkpoint("No confirmed checkpoint".to_string()))?;
+    let checkpoint = checkpoints.get(store, index)?;
+    let interval = checkpoints
+        .config(store)
+        .emergency_disbursal_lock_time_interval;
+    Ok(Uint64::from(checkpoint.create_time())
+        .checked_add(Uint64::from(interval))?
+        .u64())
+}
+
+/// Returns the inputs and outputs added to the `Building` checkpoint so far,
+/// along with its collected fees and number of pending transfers.
+pub fn query_building_checkpoint(
+    store: &dyn Storage,
+) -> ContractResult<BuildingCheckpointResponse> {
     let checkpoints = CheckpointQueue::default();
     let checkpoint = checkpoints.building(store)?;
-    Ok(checkpoint)
+    let checkpoint_tx = &checkpoint.batches[BatchType::Checkpoint][0];
+
+    Ok(BuildingCheckpointResponse {
+        index: checkpoints.index(store),
+        inputs: checkpoint_tx
+            .input
+            .iter()
+            .map(|input| CheckpointInputResponse {
+                txid: HashBinary(input.prevout.txid),
+                vout: input.prevout.vout,
+                amount: input.amount,
+            })
+            .collect(),
+        outputs: checkpoint_tx
+            .output
+            .iter()
+            .map(|output| CheckpointOutputResponse {
+                script: Adapter::new(output.script_pubkey.clone()),
+                amount: output.value,
+            })
+            .collect(),
+        fees_collected: checkpoint.fees_collected,
+        pending: checkpoint.pending.len() as u32,
+    })
+}
+
+/// Returns summaries of the checkpoints still held in the queue, newest first
+/// and starting before the checkpoint with index `start_after`.
+pub fn query_list_checkpoints(
+    store: &dyn Storage,
+    start_after: Option<u32>,
+    limit: u32,
+) -> ContractResult<Vec<CheckpointSummaryResponse>> {
+    let checkpoints = CheckpointQueue::default();
+    let queue_len = checkpoints.len(store)?;
+    if queue_len == 0 {
+        return Ok(vec![]);
+    }
+
+    let end = checkpoints.index(store) + 1;
+    let first_index = end - queue_len;
+    let end = start_after.map_or(end, |index| index.clamp(first_index, end));
+    let confirmed_index = checkpoints.confirmed_index(store);
+
+    (first_index..end)
+        .rev()
+        .take(limit.min(MAX_LIST_CHECKPOINTS_LIMIT) as usize)
+        .map(|index| {
+            let checkpoint = checkpoints.get(store, index)?;
+            let checkpoint_tx = &checkpoint.batches[BatchType::Checkpoint][0];
+            Ok(CheckpointSummaryResponse {
+                index,
+                status: checkpoint.status,
+                fee_rate: checkpoint.fee_rate,
+                input_count: checkpoint_tx.input.len() as u32,
+                output_count: checkpoint_tx.output.len() as u32,
+                confirmed: confirmed_index.map_or(false, |confirmed| index <= confirmed),
+            })
+        })
+        .collect()
 }
 
 pub fn query_est_witness_vsize(store: &dyn Storage) -> ContractResult<u64> {
@@ -158,6 +817,22 @@ pub fn query_checkpoint_len(store: &dyn Storage) -> ContractResult<u32> {
     Ok(len)
 }
 
+/// Returns the sighash messages the signatory with the given xpub still has to
+/// sign for the checkpoint at `index`, so they can be signed externally. Empty
+/// if the checkpoint is not `Signing`.
+pub fn query_to_sign(
+    store: &dyn Storage,
+    xpub: HashBinary<Xpub>,
+    index: u32,
+) -> ContractResult<Vec<([u8; 32], u32)>> {
+    let checkpoints = CheckpointQueue::default();
+    let checkpoint = checkpoints.get(store, index)?;
+    if checkpoint.status != CheckpointStatus::Signing {
+        return Ok(vec![]);
+    }
+    checkpoint.to_sign(&xpub.0)
+}
+
 pub fn query_signing_txs_at_checkpoint_index(
     store: &dyn Storage,
     xpub: HashBinary<Xpub>,
@@ -170,3 +845,25 @@ pub fn query_signing_txs_at_checkpoint_index(
     }
     Ok(checkpoint.to_sign(&xpub.0)?)
 }
+
+/// Returns the emergency disbursal transactions for the reserve of the latest
+/// confirmed checkpoint, in the order they must be broadcast, starting after
+/// the transaction at index `start_after`. Accounts are paid in order of
+/// address, so the pages are stable as long as balances and recovery scripts
+/// don't change.
+pub fn query_emergency_disbursal_txs(
+    querier: QuerierWrapper,
+    store: &dyn Storage,
+    start_after: Option<u32>,
+    limit: u32,
+) -> ContractResult<Vec<EmergencyDisbursalTxResponse>> {
+    let start = start_after.map_or(0, |index| index + 1);
+    Ok(Bitcoin::default()
+        .emergency_disbursal_txs(querier, store)?
+        .into_iter()
+        .zip(0..)
+        .skip(start as usize)
+        .take(limit.min(MAX_EMERGENCY_DISBURSAL_TXS_LIMIT) as usize)
+        .map(|(tx, index)| EmergencyDisbursalTxResponse { index, tx })
+        .collect())
+}
@@ -1,9 +1,15 @@
 use crate::{
     app::Bitcoin,
-    error::ContractResult,
-    state::{CONFIG, VALIDATORS},
+    constants::{MAX_PENDING_CHECKPOINTS, RECEIVE_HOOK_GAS_LIMIT},
+    error::{ContractError, ContractResult},
+    interface::Dest,
+    msg::ReceiverExecuteMsg,
+    state::{CONFIG, NEXT_RECEIVE_ID, PENDING_RECEIVES, VALIDATORS},
+};
+use cosmwasm_std::{
+    to_json_binary, Addr, BankMsg, Binary, Env, Reply, Response, Storage, SubMsg, SubMsgResult,
+    WasmMsg,
 };
-use cosmwasm_std::{to_json_binary, Binary, Env, Response, Storage, WasmMsg};
 
 pub fn clock_end_block(
     env: &Env,
@@ -12,7 +18,7 @@ pub fn clock_end_block(
 ) -> ContractResult<Response> {
     let mut btc = Bitcoin::default();
 
-    let pending_nbtc_transfers = btc.take_pending_completed(storage)?;
+    let pending_nbtc_transfers = btc.take_pending_completed(storage, MAX_PENDING_CHECKPOINTS)?;
 
     let config = CONFIG.load(storage)?;
     let token_factory = config.token_factory_addr;
@@ -20,24 +26,120 @@ pub fn clock_end_block(
     let mut msgs = vec![];
     for pending in pending_nbtc_transfers {
         for (dest, coin) in pending {
-            msgs.push(WasmMsg::Execute {
+            // nBTC for a contract or IBC destination is minted to the bridge,
+            // then handed to the contract's receive hook or sent over ICS-20
+            let mint_to_address = match &dest {
+                Dest::Contract { .. } | Dest::Ibc(_) => env.contract.address.to_string(),
+                Dest::Address(addr) => addr.to_string(),
+            };
+            msgs.push(SubMsg::new(WasmMsg::Execute {
                 contract_addr: token_factory.to_string(),
                 msg: to_json_binary(&tokenfactory::msg::ExecuteMsg::MintTokens {
                     denom: coin.denom.to_owned(),
                     amount: coin.amount,
-                    mint_to_address: dest.to_source_addr(),
+                    mint_to_address,
                 })?,
                 funds: vec![],
-            });
+            }));
+            if let Dest::Contract { addr, msg } = dest {
+                let id = NEXT_RECEIVE_ID.may_load(storage)?.unwrap_or_default();
+                NEXT_RECEIVE_ID.save(storage, &(id + 1))?;
+                PENDING_RECEIVES.save(storage, id, &(addr.clone(), coin.clone()))?;
+                let receive = ReceiverExecuteMsg::Receive(cw20::Cw20ReceiveMsg {
+                    sender: env.contract.address.to_string(),
+                    amount: coin.amount,
+                    msg,
+                });
+                // the hook's gas is bounded so that a receiver can't fail the
+                // end block by running out of gas, which replies can't catch
+                msgs.push(
+                    SubMsg::reply_always(
+                        WasmMsg::Execute {
+                            contract_addr: addr.into_string(),
+                            msg: to_json_binary(&receive)?,
+                            funds: vec![coin],
+                        },
+                        id,
+                    )
+                    .with_gas_limit(RECEIVE_HOOK_GAS_LIMIT),
+                );
+            } else if let Dest::Ibc(dest) = dest {
+                let id = NEXT_RECEIVE_ID.may_load(storage)?.unwrap_or_default();
+                NEXT_RECEIVE_ID.save(storage, &(id + 1))?;
+                PENDING_RECEIVES.save(
+                    storage,
+                    id,
+                    &(Addr::unchecked(&dest.sender), coin.clone()),
+                )?;
+                msgs.push(SubMsg::reply_always(
+                    dest.transfer_msg(env.contract.address.as_str(), &coin),
+                    id,
+                ));
+            }
         }
     }
 
-    let offline_signers = btc.begin_block_step(env.clone(), storage, hash.to_vec())?;
+    let checkpoints_before = btc.checkpoints.len(storage)?;
+    let expired = btc
+        .checkpoints
+        .signing_expired(storage, env.block.time.seconds())?;
+    // until a signatory registers a key there is nothing to checkpoint, which
+    // must not halt the chain's end blocker
+    let offline_signers = match btc.begin_block_step(env.clone(), storage, hash.to_vec()) {
+        Err(ContractError::EmptySignatorySet) => vec![],
+        res => res?,
+    };
 
+    let mut response = Response::new();
+    if let Some(index) = expired {
+        response = response.add_attribute("checkpoint_expired", index.to_string());
+    }
+    // report the voting power left out of a newly pushed signatory set for
+    // being below `min_signatory_power`
+    if btc.checkpoints.len(storage)? > checkpoints_before {
+        let pruned_vp = btc.checkpoints.building(storage)?.sigset.pruned_vp;
+        if pruned_vp > 0 {
+            response = response.add_attribute("pruned_signatory_power", pruned_vp.to_string());
+        }
+    }
     for cons_key in &offline_signers {
         let (_, address) = VALIDATORS.load(storage, cons_key)?;
-        btc.punish_validator(storage, cons_key, address)?;
+        let slashed = btc.punish_validator(storage, cons_key, address)?;
+        response = response
+            .add_attribute("jailed", Binary::from(cons_key.as_slice()).to_base64())
+            .add_attribute("slashed", slashed.to_string());
     }
 
-    Ok(Response::new().add_messages(msgs))
+    if btc.checkpoints.fee_rate_ceiling_stuck(storage)? {
+        response = response.add_attribute("fee_rate_ceiling_stuck", "true");
+    }
+    if btc.checkpoints.halted(storage)? {
+        response = response.add_attribute("checkpoint_halted", "true");
+    }
+
+    Ok(response.add_submessages(msgs))
+}
+
+/// Handles the reply to a `Dest::Contract` receive hook or a `Dest::Ibc`
+/// transfer. If it failed, the nBTC minted for the deposit is still held by
+/// the bridge, so it is sent to the contract, or to the IBC destination's
+/// `sender`, as a plain balance credit instead.
+pub fn receive_reply(storage: &mut dyn Storage, reply: Reply) -> ContractResult<Response> {
+    let (addr, coin) = PENDING_RECEIVES.load(storage, reply.id)?;
+    PENDING_RECEIVES.remove(storage, reply.id);
+
+    let response = Response::new()
+        .add_attribute("action", "receive_reply")
+        .add_attribute("recipient", addr.as_str());
+    match reply.result {
+        SubMsgResult::Ok(_) => Ok(response),
+        SubMsgResult::Err(err) => {
+            Ok(response
+                .add_attribute("receive_error", err)
+                .add_message(BankMsg::Send {
+                    to_address: addr.into_string(),
+                    amount: vec![coin],
+                }))
+        }
+    }
 }
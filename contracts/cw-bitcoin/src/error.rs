@@ -38,6 +38,8 @@ pub enum ContractError {
     Bip32(#[from] bitcoin::util::bip32::Error),
     #[error("{0}")]
     Checkpoint(String),
+    #[error("empty signatory set")]
+    EmptySignatorySet,
     #[error(transparent)]
     Sighash(#[from] bitcoin::util::sighash::Error),
     #[error(transparent)]
@@ -48,6 +50,8 @@ pub enum ContractError {
     Secp(#[from] bitcoin::secp256k1::Error),
     #[error("Could not verify merkle proof")]
     BitcoinMerkleBlockError,
+    #[error("Merkle proof places the transaction at block index {proof_index}, not {block_index}")]
+    BlockIndexMismatch { block_index: u32, proof_index: u32 },
     #[error("{0}")]
     Header(String),
     #[error("{0}")]
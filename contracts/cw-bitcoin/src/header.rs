@@ -1,10 +1,12 @@
 use crate::adapter::Adapter;
-use crate::constants::MAX_HEADERS_RELAY_ONE_TIME;
+use crate::app::NETWORK;
+use crate::constants::{MAX_FORK_HEADERS, MAX_HEADERS_RELAY_ONE_TIME};
 use crate::error::ContractError;
 use crate::error::ContractResult;
 use crate::interface::HeaderConfig;
 use crate::state::header_height;
 use crate::state::CURRENT_WORK;
+use crate::state::FORK_HEADERS;
 use crate::state::HEADERS;
 use crate::state::HEADER_CONFIG;
 use bitcoin::blockdata::block::BlockHeader;
@@ -14,7 +16,7 @@ use bitcoin::BlockHash;
 use bitcoin::TxMerkleNode;
 use cosmwasm_schema::schemars::JsonSchema;
 use cosmwasm_schema::serde::{Deserialize, Serialize};
-use cosmwasm_std::Storage;
+use cosmwasm_std::{Order, Storage};
 // use ed::Terminated;
 
 /// A wrapper around a bitcoin::BlockHeader that implements the core orga
@@ -221,11 +223,15 @@ impl HeaderQueue {
         let headers: Vec<_> = headers.into();
 
         if headers.len() as u64 > MAX_HEADERS_RELAY_ONE_TIME {
-            return Err(ContractError::App(
-                "Exceeded maximum amount of relayed headers".to_string(),
+            return Err(ContractError::Header(
+                "Exceeded maximum amount of relayed headers".into(),
             ));
         }
 
+        // Check the batch is internally consistent before touching the queue,
+        // so an invalid header anywhere in the batch rejects all of it.
+        Self::validate_batch(&headers)?;
+
         self.add_into_iter(store, headers)
             .map_err(|err| ContractError::App(err.to_string()))
     }
@@ -239,6 +245,11 @@ impl HeaderQueue {
     /// headers start from a height lower than the current height, the header
     /// queue will be reorged to the new chain.
     ///
+    /// Headers which branch off the queue (or extend a fork stored earlier)
+    /// without having more work than it are stored as a competing fork, and
+    /// the queue is reorged to the fork once it is extended past the queue's
+    /// work.
+    ///
     /// If the headers are invalid (e.g. by not including a valid proof-of-work,
     /// using a difficulty other than what was expected, using invalid
     /// timestamps, etc.), an error will be returned and the header queue will
@@ -255,29 +266,164 @@ impl HeaderQueue {
             .first()
             .ok_or_else(|| ContractError::Header("Passed header list empty".into()))?;
 
+        // The full branch from the queue to the end of the batch, including
+        // any stored fork headers the batch extends.
+        let branch = self.fork_branch(store, headers, config.max_reorg_depth)?;
+        let branch_start = branch[0].height;
+
         let mut removed_work = Uint256::default();
-        if first.height <= current_height {
+        if branch_start <= current_height {
+            if current_height - branch_start >= config.max_reorg_depth {
+                return Err(ContractError::Header(
+                    "Reorg exceeds maximum reorg depth".into(),
+                ));
+            }
+
             let first_replaced = self
-                .get_by_height(store, first.height)?
+                .get_by_height(store, branch_start)?
                 .ok_or_else(|| ContractError::Header("Header not found".into()))?;
 
-            if first_replaced.block_hash() == first.block_hash() {
+            if first_replaced.block_hash() == branch[0].block_hash() {
                 return Err(ContractError::Header("Provided redundant header.".into()));
             }
 
-            removed_work = self.pop_back_to(store, first.height)?;
+            // keep a fork which does not have more work yet, so it can be
+            // extended by later batches
+            let branch_work = branch
+                .iter()
+                .fold(Uint256::default(), |work, header| work + header.work());
+            let mut replaced_work = Uint256::default();
+            for height in branch_start..=current_height {
+                if let Some(header) = self.get_by_height(store, height)? {
+                    replaced_work = replaced_work + header.work();
+                }
+            }
+            if branch_work <= replaced_work {
+                return self.store_fork(store, &branch);
+            }
+
+            removed_work = self.pop_back_to(store, branch_start)?;
         }
 
-        let added_work = self.verify_and_add_headers(store, &headers)?;
+        let added_work = self.verify_and_add_headers(store, &branch)?;
         if added_work <= removed_work {
             return Err(ContractError::Header(
                 "New best chain must include more work than old best chain.".into(),
             ));
         }
 
+        for header in &branch {
+            FORK_HEADERS.remove(store, &header.block_hash().to_string());
+        }
+        self.prune_forks(store)?;
+
         // Prune the header queue if it has grown too large.
+        self.prune(store, u32::MAX)
+    }
+
+    /// Prepends to `headers` the stored fork headers it extends, back to the
+    /// header in the queue the fork branches off of, which must be no more
+    /// than `max_reorg_depth` below the tip.
+    fn fork_branch(
+        &self,
+        store: &dyn Storage,
+        mut headers: Vec<WrappedHeader>,
+        max_reorg_depth: u32,
+    ) -> ContractResult<Vec<WrappedHeader>> {
+        let current_height = self.height(store)?;
+        loop {
+            let first = &headers[0];
+            let parent_height = first
+                .height
+                .checked_sub(1)
+                .ok_or_else(|| ContractError::Header("Headers must start after height 0".into()))?;
+            if let Some(parent) = self.get_by_height(store, parent_height)? {
+                if parent.block_hash() == first.prev_blockhash() {
+                    return Ok(headers);
+                }
+            }
+            if current_height.saturating_sub(parent_height) >= max_reorg_depth {
+                return Err(ContractError::Header("Headers not connect to chain".into()));
+            }
+            match FORK_HEADERS.may_load(store, &first.prev_blockhash().to_string())? {
+                Some(parent) if parent.height() == parent_height => headers.insert(0, parent),
+                _ => return Err(ContractError::Header("Headers not connect to chain".into())),
+            }
+        }
+    }
+
+    /// Stores the headers of a fork which does not have more work than the
+    /// queue yet, after checking that each one meets the target expected from
+    /// its parent. At most `MAX_FORK_HEADERS` fork headers are kept.
+    fn store_fork(&self, store: &mut dyn Storage, branch: &[WrappedHeader]) -> ContractResult<()> {
+        let parent = self
+            .get_by_height(store, branch[0].height - 1)?
+            .ok_or_else(|| ContractError::Header("Headers not connect to chain".into()))?
+            .header;
+        let prev_headers = std::iter::once(&parent).chain(branch.iter());
+        for (prev_header, header) in prev_headers.zip(branch.iter()) {
+            let target = self.get_next_target(store, header, prev_header)?;
+            header.validate_pow(&target)?;
+        }
+
+        let mut new_headers = vec![];
+        for header in branch {
+            let hash = header.block_hash().to_string();
+            if !FORK_HEADERS.has(store, &hash) {
+                new_headers.push((hash, header));
+            }
+        }
+        let stored = FORK_HEADERS
+            .keys(store, None, None, Order::Ascending)
+            .take(MAX_FORK_HEADERS)
+            .count();
+        if stored + new_headers.len() > MAX_FORK_HEADERS {
+            return Err(ContractError::Header(
+                "Too many fork headers are stored".into(),
+            ));
+        }
+        for (hash, header) in new_headers {
+            FORK_HEADERS.save(store, &hash, header)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the stored fork headers which are too far below the tip to
+    /// ever be reorged to.
+    fn prune_forks(&self, store: &mut dyn Storage) -> ContractResult<()> {
+        let min_height = self
+            .height(store)?
+            .saturating_sub(self.config(store)?.max_reorg_depth);
+        let stale = FORK_HEADERS
+            .range(store, None, None, Order::Ascending)
+            .filter_map(|entry| match entry {
+                Ok((hash, header)) if header.height() <= min_height => Some(Ok(hash)),
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for hash in stale {
+            FORK_HEADERS.remove(store, &hash);
+        }
+        Ok(())
+    }
+
+    /// Removes the oldest headers while the queue is longer than the
+    /// configured `max_length`, but never removes a header at or above
+    /// `retain_from_height`.
+    pub fn prune(
+        &mut self,
+        store: &mut dyn Storage,
+        retain_from_height: u32,
+    ) -> ContractResult<()> {
+        let config = self.config(store)?;
         let mut queue_len = self.len(store)?;
         while queue_len > config.max_length {
+            match HEADERS.front(store)? {
+                Some(header) if header.height() < retain_from_height => {}
+                _ => break,
+            };
             let header = match HEADERS.pop_front(store)? {
                 Some(inner) => inner,
                 None => {
@@ -295,6 +441,35 @@ impl HeaderQueue {
         Ok(())
     }
 
+    /// Checks that a batch of headers is consecutive, that each header
+    /// references the hash of the one before it, and that each header meets
+    /// the proof-of-work target it claims.
+    ///
+    /// This does not check the headers against the queue itself, which is done
+    /// as they are added.
+    fn validate_batch(headers: &[WrappedHeader]) -> ContractResult<()> {
+        for pair in headers.windows(2) {
+            let (prev_header, header) = (&pair[0], &pair[1]);
+            if header.height() != prev_header.height() + 1 {
+                return Err(ContractError::Header(
+                    "Non-consecutive headers passed".into(),
+                ));
+            }
+
+            if header.prev_blockhash() != prev_header.block_hash() {
+                return Err(ContractError::Header(
+                    "Passed header references incorrect previous block hash".into(),
+                ));
+            }
+        }
+
+        for header in headers {
+            header.validate_pow(&header.target())?;
+        }
+
+        Ok(())
+    }
+
     /// Verify and add a list of headers to the header queue, returning the
     /// amount of additional estimated work added to the header queue.
     fn verify_and_add_headers(
@@ -369,6 +544,11 @@ impl HeaderQueue {
         previous_header: &WrappedHeader,
     ) -> ContractResult<Uint256> {
         let config = self.config(store)?;
+        // regtest never retargets, so every block keeps the previous target
+        if config.network == bitcoin::Network::Regtest {
+            return Ok(previous_header.target());
+        }
+
         if header.height() % config.retarget_interval == 0 {
             let first_reorg_height = header.height() - config.retarget_interval;
             return self.calculate_next_target(store, previous_header, first_reorg_height);
@@ -427,7 +607,9 @@ impl HeaderQueue {
             }
         };
 
-        let mut timespan = header.time() - prev_retarget;
+        // timestamps are not strictly increasing, so the last header of the
+        // period may be earlier than the first; that clamps to the lower bound
+        let mut timespan = header.time().saturating_sub(prev_retarget);
 
         if timespan < config.target_timespan / 4 {
             timespan = config.target_timespan / 4;
@@ -464,6 +646,9 @@ impl HeaderQueue {
             work = work + header.work();
         }
 
+        let current_work = *self.current_work(store)? - work;
+        CURRENT_WORK.save(store, &Adapter::new(current_work))?;
+
         Ok(work)
     }
 
@@ -473,34 +658,7 @@ impl HeaderQueue {
         store: &dyn Storage,
         current_header: &WrappedHeader,
     ) -> ContractResult<()> {
-        let mut prev_stamps: Vec<u32> = Vec::with_capacity(11);
-
-        for i in 0..11 {
-            let index = self.height(store)? - i;
-
-            let current_item = match self.get_by_height(store, index)? {
-                Some(inner) => inner,
-                None => {
-                    return Err(ContractError::Header(
-                        "Deque does not contain any elements".into(),
-                    ))
-                }
-            };
-            prev_stamps.push(current_item.time());
-        }
-
-        prev_stamps.sort_unstable();
-
-        let median_stamp = match prev_stamps.get(5) {
-            Some(inner) => inner,
-            None => {
-                return Err(ContractError::Header(
-                    "Median timestamp does not exist".into(),
-                ));
-            }
-        };
-
-        if current_header.time() <= *median_stamp {
+        if current_header.time() <= self.median_time_past(store)? {
             return Err(ContractError::Header(
                 "Header contains an invalid timestamp".into(),
             ));
@@ -520,6 +678,31 @@ impl HeaderQueue {
         Ok(())
     }
 
+    /// The median timestamp of the last 11 headers in the header queue (or of
+    /// all of them, if the queue holds fewer).
+    ///
+    /// Unlike the timestamp of a single header, this can not be moved
+    /// arbitrarily far by one miner.
+    pub fn median_time_past(&self, store: &dyn Storage) -> ContractResult<u32> {
+        let height = self.height(store)?;
+        let count = self.len(store)?.min(11) as u32;
+
+        let mut stamps: Vec<u32> = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let header = self.get_by_height(store, height - i)?.ok_or_else(|| {
+                ContractError::Header("Deque does not contain any elements".into())
+            })?;
+            stamps.push(header.time());
+        }
+
+        stamps.sort_unstable();
+
+        stamps
+            .get(stamps.len() / 2)
+            .copied()
+            .ok_or_else(|| ContractError::Header("Median timestamp does not exist".into()))
+    }
+
     /// The height of the last header in the header queue.    
     pub fn height(&self, store: &dyn Storage) -> ContractResult<u32> {
         header_height(store)
@@ -609,9 +792,11 @@ impl HeaderQueue {
         Ok(())
     }
 
-    /// The network the header queue is configured for.
-    pub fn network(&self) -> bitcoin::Network {
-        // TODO: should be dynamic, from config
-        bitcoin::Network::Bitcoin
+    /// The network the header queue is configured for, falling back to the
+    /// default network if no config has been set.
+    pub fn network(&self, store: &dyn Storage) -> ContractResult<bitcoin::Network> {
+        Ok(HEADER_CONFIG
+            .may_load(store)?
+            .map_or(NETWORK, |config| config.network))
     }
 }
@@ -108,6 +108,7 @@ async fn relay_checkpoint(
                         btc_height: block_height,
                         btc_proof: Adapter::from(proof),
                         cp_index: checkpoint_index,
+                        observed_fee_rate: None,
                     },
                     &[],
                 )
@@ -163,6 +164,7 @@ async fn relay_recovery(
                 btc_tx: recovery_tx.tx.clone(),
                 btc_height: header.height(),
                 btc_proof: Adapter::from(proof),
+                block_index: None,
                 btc_vout: 0, // always is zero for sure
                 sigset_index: recovery_tx.sigset_index,
                 dest: recovery_tx.dest,
@@ -265,6 +267,7 @@ async fn test_full_flow_happy_case_bitcoin() {
             // Set up header config based on the header of block data
             let header_config = HeaderConfig {
                 max_length: 2000,
+                max_reorg_depth: 100,
                 max_time_increase: 8 * 60 * 60,
                 trusted_height,
                 retarget_interval: 2016,
@@ -273,6 +276,7 @@ async fn test_full_flow_happy_case_bitcoin() {
                 max_target: 0x1d00ffff,
                 retargeting: true,
                 min_difficulty_blocks: false,
+                network: bitcoin::Network::Bitcoin,
                 trusted_header: Adapter::from(trusted_header),
             };
             let _ = app
@@ -302,6 +306,7 @@ async fn test_full_flow_happy_case_bitcoin() {
                 btc_tx,
                 btc_height,
                 btc_proof,
+                block_index: None,
                 btc_vout,
                 sigset_index,
                 dest,
@@ -360,8 +365,7 @@ async fn test_full_flow_happy_case_bitcoin() {
                    sender: Addr,
                    xpriv: &ExtendedPrivKey,
                    xpub: ExtendedPubKey,
-                   cp_index: u32,
-                   btc_height: u32|
+                   cp_index: u32|
      -> Result<AppResponse, _> {
         let secp = Secp256k1::signing_only();
         let to_signs: Vec<([u8; 32], u32)> = app
@@ -378,11 +382,11 @@ async fn test_full_flow_happy_case_bitcoin() {
         app.execute(
             sender,
             bitcoin_bridge_addr.clone(),
-            &msg::ExecuteMsg::SubmitCheckpointSignature {
+            &msg::ExecuteMsg::SubmitSignatures {
                 xpub: HashBinary(Xpub::new(xpub)),
-                sigs,
-                checkpoint_index: cp_index,
-                btc_height,
+                sigs: sigs.into_iter().map(|sig| Binary::from(sig.0)).collect(),
+                sigset_index: cp_index,
+                encoding: None,
             },
             &[],
         )
@@ -616,8 +620,8 @@ async fn test_full_flow_happy_case_bitcoin() {
     assert_eq!(checkpoint.status, CheckpointStatus::Signing);
 
     // Validators submit signature
-    sign_cp(&mut app, validator_1.clone(), &xprivs[0], xpubs[0], 0, 1021).unwrap();
-    sign_cp(&mut app, validator_2.clone(), &xprivs[1], xpubs[1], 0, 1021).unwrap();
+    sign_cp(&mut app, validator_1.clone(), &xprivs[0], xpubs[0], 0).unwrap();
+    sign_cp(&mut app, validator_2.clone(), &xprivs[1], xpubs[1], 0).unwrap();
 
     // Increase block and current Signing checkpoint changed to Complete
     let checkpoint: Checkpoint = app
@@ -772,9 +776,9 @@ async fn test_full_flow_happy_case_bitcoin() {
         .unwrap();
     assert_eq!(checkpoint.status, CheckpointStatus::Signing);
 
-    sign_cp(&mut app, validator_1.clone(), &xprivs[0], xpubs[0], 1, 1025).unwrap();
-    sign_cp(&mut app, validator_2.clone(), &xprivs[1], xpubs[1], 1, 1025).unwrap();
-    sign_cp(&mut app, validator_3.clone(), &xprivs[2], xpubs[2], 1, 1025).unwrap();
+    sign_cp(&mut app, validator_1.clone(), &xprivs[0], xpubs[0], 1).unwrap();
+    sign_cp(&mut app, validator_2.clone(), &xprivs[1], xpubs[1], 1).unwrap();
+    sign_cp(&mut app, validator_3.clone(), &xprivs[2], xpubs[2], 1).unwrap();
 
     // Increase block and current Signing checkpoint changed to Complete
     increase_block(&mut app, Binary::from([5; 32])).unwrap(); // should increase number of hash to be unique
@@ -856,10 +860,6 @@ async fn test_full_flow_happy_case_bitcoin() {
     )
     .await;
     increase_block(&mut app, Binary::from([6; 32])).unwrap(); // should increase number of hash to be unique
-    let current_header: u32 = app
-        .as_querier()
-        .query_wasm_smart(bitcoin_bridge_addr.clone(), &msg::QueryMsg::HeaderHeight {})
-        .unwrap();
     let checkpoint: Checkpoint = app
         .as_querier()
         .query_wasm_smart(
@@ -868,33 +868,9 @@ async fn test_full_flow_happy_case_bitcoin() {
         )
         .unwrap();
     assert_eq!(checkpoint.status, CheckpointStatus::Signing);
-    sign_cp(
-        &mut app,
-        validator_1.clone(),
-        &xprivs[0],
-        xpubs[0],
-        2,
-        current_header,
-    )
-    .unwrap();
-    sign_cp(
-        &mut app,
-        validator_2.clone(),
-        &xprivs[1],
-        xpubs[1],
-        2,
-        current_header,
-    )
-    .unwrap();
-    sign_cp(
-        &mut app,
-        validator_3.clone(),
-        &xprivs[2],
-        xpubs[2],
-        2,
-        current_header,
-    )
-    .unwrap();
+    sign_cp(&mut app, validator_1.clone(), &xprivs[0], xpubs[0], 2).unwrap();
+    sign_cp(&mut app, validator_2.clone(), &xprivs[1], xpubs[1], 2).unwrap();
+    sign_cp(&mut app, validator_3.clone(), &xprivs[2], xpubs[2], 2).unwrap();
     // Increase block and current Signing checkpoint changed to Complete
     increase_block(&mut app, Binary::from([7; 32])).unwrap(); // should increase number of hash to be unique
     let checkpoint: Checkpoint = app
@@ -54,6 +54,7 @@ async fn reorg() {
 
     let header_config = HeaderConfig {
         max_length: 2000,
+        max_reorg_depth: 100,
         max_time_increase: 8 * 60 * 60,
         trusted_height: tip_height as u32,
         retarget_interval: 2016,
@@ -62,6 +63,7 @@ async fn reorg() {
         max_target: 0x1d00ffff,
         retargeting: true,
         min_difficulty_blocks: false,
+        network: bitcoin::Network::Bitcoin,
         trusted_header: Adapter::from(tip_header),
     };
     app.execute(
@@ -196,6 +198,7 @@ async fn reorg_competing_chain_similar() {
 
     let header_config = HeaderConfig {
         max_length: 2000,
+        max_reorg_depth: 100,
         max_time_increase: 8 * 60 * 60,
         trusted_height: tip_height as u32,
         retarget_interval: 2016,
@@ -204,6 +207,7 @@ async fn reorg_competing_chain_similar() {
         max_target: 0x1d00ffff,
         retargeting: true,
         min_difficulty_blocks: false,
+        network: bitcoin::Network::Bitcoin,
         trusted_header: Adapter::from(tip_header),
     };
     app.execute(
@@ -339,6 +343,7 @@ fn reorg_deep() {
 
     let header_config = HeaderConfig {
         max_length: 2000,
+        max_reorg_depth: 100,
         max_time_increase: 8 * 60 * 60,
         trusted_height: tip_height as u32,
         retarget_interval: 2016,
@@ -347,6 +352,7 @@ fn reorg_deep() {
         max_target: 0x1d00ffff,
         retargeting: true,
         min_difficulty_blocks: false,
+        network: bitcoin::Network::Bitcoin,
         trusted_header: Adapter::from(tip_header),
     };
     app.execute(
@@ -6,23 +6,28 @@ use cosmwasm_schema::{
     schemars::JsonSchema,
     serde::{de, ser, Deserialize, Serialize},
 };
-use cosmwasm_std::{from_json, to_json_vec, Addr, Binary, StdError, Storage, Uint128};
+use cosmwasm_std::{
+    from_json, to_json_vec, Addr, Api, Binary, Coin, CosmosMsg, StdError, Storage, Uint128,
+};
 use cw_storage_plus::Deque;
 use derive_more::{Deref, DerefMut};
 use sha2::{Digest, Sha256};
 
-use crate::adapter::Adapter;
+use crate::adapter::{Adapter, HashBinary};
 use crate::app::ConsensusKey;
 use crate::app::NETWORK;
 use crate::constants::{
-    MAX_CHECKPOINT_AGE, MAX_CHECKPOINT_INTERVAL, MAX_DEPOSIT_AGE, MAX_FEE_RATE, MAX_LENGTH,
-    MAX_TARGET, MAX_TIME_INCREASE, MIN_DEPOSIT_AMOUNT, MIN_FEE_RATE, MIN_WITHDRAWAL_AMOUNT,
-    RETARGET_INTERVAL, SIGSET_THRESHOLD, TARGET_SPACING, TARGET_TIMESPAN, TRANSFER_FEE,
-    USER_FEE_FACTOR,
+    EMERGENCY_DISBURSAL_LOCK_TIME_INTERVAL, MAX_CHECKPOINT_AGE, MAX_CHECKPOINT_INTERVAL,
+    MAX_DEPOSIT_AGE, MAX_FEE_RATE, MAX_LENGTH, MAX_MEMO_LENGTH, MAX_REORG_DEPTH, MAX_TARGET,
+    MAX_TIME_INCREASE, MIN_DEPOSIT_AMOUNT, MIN_FEE_RATE, MIN_WITHDRAWAL_AMOUNT, REGTEST_MAX_TARGET,
+    RETARGET_INTERVAL, SIGNET_MAX_TARGET, SIGNING_TIMEOUT, SIGSET_THRESHOLD, SLASH_FRACTION,
+    TARGET_SPACING, TARGET_TIMESPAN, TRANSFER_FEE, USER_FEE_FACTOR,
 };
-use crate::error::ContractResult;
+use crate::error::{ContractError, ContractResult};
 use crate::header::WorkHeader;
 use crate::header::WrappedHeader;
+use crate::state::IBC_CHANNEL_ALLOWLIST;
+use crate::units::{MSats, Sats};
 use libsecp256k1_core::curve::{Affine, ECMultContext, Field, Scalar};
 use libsecp256k1_core::util::{TAG_PUBKEY_EVEN, TAG_PUBKEY_ODD};
 
@@ -81,13 +86,98 @@ pub struct IbcDest {
     pub receiver: String,
     pub sender: String,
     pub timeout_timestamp: u64,
+    /// The memo for the ICS-20 transfer to this destination, e.g. to route it
+    /// through packet-forward-middleware. Must be empty or valid JSON, or the
+    /// deposit is rejected.
     pub memo: String,
 }
 
+impl IbcDest {
+    /// Checks that the memo is either empty or valid JSON, as expected by
+    /// packet-forward-middleware on the receiving chain.
+    pub fn validate(&self) -> ContractResult<()> {
+        if !self.memo.is_empty() {
+            from_json::<de::IgnoredAny>(self.memo.as_bytes())
+                .map_err(|err| ContractError::Ibc(format!("Invalid memo: {}", err)))?;
+        }
+        Ok(())
+    }
+
+    /// The ICS-20 `MsgTransfer` sending `token` from `sender` to this
+    /// destination. The memo is included verbatim so that
+    /// packet-forward-middleware on the receiving chain can route it onwards.
+    pub fn transfer_msg(&self, sender: &str, token: &Coin) -> CosmosMsg {
+        let mut coin = vec![];
+        encode_bytes_field(&mut coin, 1, token.denom.as_bytes());
+        encode_bytes_field(&mut coin, 2, token.amount.to_string().as_bytes());
+
+        let mut msg = vec![];
+        encode_bytes_field(&mut msg, 1, self.source_port.as_bytes());
+        encode_bytes_field(&mut msg, 2, self.source_channel.as_bytes());
+        encode_bytes_field(&mut msg, 3, &coin);
+        encode_bytes_field(&mut msg, 4, sender.as_bytes());
+        encode_bytes_field(&mut msg, 5, self.receiver.as_bytes());
+        encode_uint_field(&mut msg, 7, self.timeout_timestamp);
+        encode_bytes_field(&mut msg, 8, self.memo.as_bytes());
+
+        CosmosMsg::Stargate {
+            type_url: "/ibc.applications.transfer.v1.MsgTransfer".to_string(),
+            value: msg.into(),
+        }
+    }
+}
+
+fn encode_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Appends a protobuf length-delimited field, omitted when empty as in proto3.
+fn encode_bytes_field(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+    encode_varint(buf, field << 3 | 2);
+    encode_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Appends a protobuf varint field, omitted when zero as in proto3.
+fn encode_uint_field(buf: &mut Vec<u8>, field: u64, value: u64) {
+    if value == 0 {
+        return;
+    }
+    encode_varint(buf, field << 3);
+    encode_varint(buf, value);
+}
+
+/// A deposit credited to an address, as kept in the address's deposit
+/// history for reconciliation.
+#[cw_serde]
+pub struct DepositRecord {
+    /// The position of the record in the address's history, starting at 0.
+    pub index: u64,
+    pub txid: HashBinary<bitcoin::Txid>,
+    pub vout: u32,
+    /// The amount of nBTC credited, net of deposit fees.
+    pub amount: Uint128,
+    /// The Bitcoin height of the block containing the deposit.
+    pub height: u32,
+}
+
 #[cw_serde]
 pub enum Dest {
     Address(Addr),
     Ibc(IbcDest),
+    /// A contract credited through a cw20-style receive hook, called with
+    /// `msg` and the nBTC attached as funds.
+    Contract {
+        addr: Addr,
+        msg: Binary,
+    },
 }
 
 impl Dest {
@@ -95,13 +185,73 @@ impl Dest {
         match self {
             Self::Address(addr) => addr.to_string(),
             Self::Ibc(dest) => dest.receiver.to_string(),
+            Self::Contract { addr, .. } => addr.to_string(),
         }
     }
 
+    /// The local address the deposit is recorded against. For IBC
+    /// destinations this is `sender`, who is credited with the nBTC if the
+    /// ICS-20 transfer can't be sent.
     pub fn to_source_addr(&self) -> String {
         match self {
             Self::Address(addr) => addr.to_string(),
             Self::Ibc(dest) => dest.sender.to_string(),
+            Self::Contract { addr, .. } => addr.to_string(),
+        }
+    }
+
+    pub fn validate(&self) -> ContractResult<()> {
+        match self {
+            Self::Address(_) | Self::Contract { .. } => Ok(()),
+            Self::Ibc(dest) => dest.validate(),
+        }
+    }
+
+    /// Checks that the local address credited with the minted nBTC is valid,
+    /// since minting to an invalid address would fail every end block.
+    pub fn validate_addr(&self, api: &dyn Api) -> ContractResult<()> {
+        api.addr_validate(&self.to_source_addr())?;
+        Ok(())
+    }
+
+    /// Checks that an IBC destination's memo is no longer than
+    /// `max_memo_length` bytes.
+    pub fn validate_memo_length(&self, max_memo_length: u64) -> ContractResult<()> {
+        let Self::Ibc(dest) = self else {
+            return Ok(());
+        };
+        if dest.memo.len() as u64 > max_memo_length {
+            return Err(ContractError::Ibc(format!(
+                "Memo exceeds the maximum length of {} bytes",
+                max_memo_length
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether funds may be routed to this destination, which for an IBC
+    /// destination means its source channel is on the owner's allowlist.
+    /// Every channel is allowed until the owner first sets the allowlist.
+    pub fn channel_allowed(&self, store: &dyn Storage) -> ContractResult<bool> {
+        let Self::Ibc(dest) = self else {
+            return Ok(true);
+        };
+        Ok(match IBC_CHANNEL_ALLOWLIST.may_load(store)? {
+            Some(allowlist) => allowlist.contains(&dest.source_channel),
+            None => true,
+        })
+    }
+
+    /// Checks that an IBC destination's source channel is allowed (see
+    /// `channel_allowed`), since funds routed to a channel which doesn't exist
+    /// would be lost.
+    pub fn validate_channel(&self, store: &dyn Storage) -> ContractResult<()> {
+        match self {
+            Self::Ibc(dest) if !self.channel_allowed(store)? => Err(ContractError::Ibc(format!(
+                "Channel {} is not allowed",
+                dest.source_channel
+            ))),
+            _ => Ok(()),
         }
     }
 
@@ -109,6 +259,8 @@ impl Dest {
         let bytes = match self {
             Self::Address(addr) => addr.as_bytes().into(),
             Self::Ibc(dest) => Sha256::digest(dest.receiver.as_bytes()).to_vec(),
+            // commits to the hook message too, so it can't be swapped
+            Self::Contract { .. } => Sha256::digest(to_json_vec(self)?).to_vec(),
         };
 
         Ok(bytes)
@@ -132,12 +284,24 @@ pub struct BitcoinConfig {
     pub min_deposit_amount: u64,
     /// The minimum amount of BTC a withdrawal must withdraw, in satoshis.
     pub min_withdrawal_amount: u64,
-    /// TODO: remove this, not used
+    /// The maximum amount of BTC a single withdrawal output may pay, in
+    /// satoshis, to limit how much can be drained in one checkpoint. Zero
+    /// disables the limit.
     pub max_withdrawal_amount: u64,
     /// The maximum length of a withdrawal output script, in bytes.
     pub max_withdrawal_script_length: u64,
     /// The fee charged for an nBTC transfer, in micro-satoshis.
     pub transfer_fee: u64,
+    /// Whether nBTC transfers through the bridge are allowed. When false, only
+    /// the addresses in `transfer_exceptions` may transfer. Only
+    /// `ExecuteMsg::Transfer` is gated: nBTC is a bank denom, so plain bank
+    /// sends never reach the contract.
+    #[serde(default = "default_transfers_allowed")]
+    pub transfers_allowed: bool,
+    /// Addresses which may transfer nBTC even when `transfers_allowed` is
+    /// false.
+    #[serde(default)]
+    pub transfer_exceptions: Vec<Addr>,
     /// The minimum number of confirmations a Bitcoin block must have before it
     /// is considered finalized. Note that in the current implementation, the
     /// actual number of confirmations required is `min_confirmations + 1`.
@@ -158,12 +322,64 @@ pub struct BitcoinConfig {
     pub min_checkpoint_confirmations: u32,
     /// The maximum amount of BTC that can be held in the network, in satoshis.    
     pub capacity_limit: u64,
-
+    /// The maximum age of a deposit, in seconds. Deposits to a signatory set
+    /// older than this are paid back through a recovery transaction.
     pub max_deposit_age: u64,
-
+    /// The maximum time, in seconds, between the block of a deposit and the
+    /// current tip for the deposit to be credited. Older deposits are
+    /// rejected, unless they pay an expired signatory set and are recovered
+    /// instead.
+    #[serde(default = "default_max_deposit_block_age")]
+    pub max_deposit_block_age: u64,
+
+    /// The fee pool balance, in satoshis, above which all surplus checkpoint
+    /// fees are paid out as signatory rewards.
     pub fee_pool_target_balance: u64,
-
+    /// The fraction of surplus checkpoint fees paid out as signatory rewards
+    /// while the fee pool is below its target, as a ratio represented by a
+    /// tuple, `(numerator, denominator)`.
     pub fee_pool_reward_split: (u64, u64),
+
+    /// The fraction of a jailed signer's voting power which is slashed, as a
+    /// ratio represented by a tuple, `(numerator, denominator)`. The signer
+    /// keeps the rest of its voting power if it is unjailed.
+    #[serde(default = "default_slash_fraction")]
+    pub slash_fraction: (u64, u64),
+
+    /// Tiers of `(amount_threshold, extra_confirmations)`. A deposit of more
+    /// than `amount_threshold` satoshis needs `extra_confirmations` on top of
+    /// `min_confirmations` before it is credited, taking the largest extra of
+    /// every tier it exceeds.
+    #[serde(default)]
+    pub confirmation_tiers: Vec<(u64, u32)>,
+
+    /// The number of confirmations an IBC deposit needs on top of those of an
+    /// address deposit of the same amount, since its nBTC leaves the chain
+    /// once credited and can't be recovered after a reorg. Zero credits IBC
+    /// deposits like address deposits.
+    #[serde(default)]
+    pub ibc_extra_confirmations: u32,
+
+    /// The maximum length of the memo of an IBC deposit destination, in
+    /// bytes, to bound the size of the stored destination and its packet.
+    #[serde(default = "default_max_memo_length")]
+    pub max_memo_length: u64,
+}
+
+fn default_transfers_allowed() -> bool {
+    true
+}
+
+fn default_max_memo_length() -> u64 {
+    MAX_MEMO_LENGTH
+}
+
+fn default_max_deposit_block_age() -> u64 {
+    MAX_DEPOSIT_AGE
+}
+
+fn default_slash_fraction() -> (u64, u64) {
+    SLASH_FRACTION
 }
 
 impl BitcoinConfig {
@@ -172,18 +388,76 @@ impl BitcoinConfig {
             min_withdrawal_checkpoints: 4,
             min_deposit_amount: MIN_DEPOSIT_AMOUNT,
             min_withdrawal_amount: MIN_WITHDRAWAL_AMOUNT,
-            max_withdrawal_amount: 64,
+            max_withdrawal_amount: 0,
             max_withdrawal_script_length: 64,
             transfer_fee: TRANSFER_FEE,
+            transfers_allowed: true,
+            transfer_exceptions: vec![],
             min_confirmations: 1,
             units_per_sat: 1_000_000,
             max_offline_checkpoints: 20,
             min_checkpoint_confirmations: 0,
-            capacity_limit: 21 * 100_000_000,     // 21 BTC
+            capacity_limit: 21 * 100_000_000, // 21 BTC
             max_deposit_age: MAX_DEPOSIT_AGE, // 2 weeks. Initially there may not be many deposits & withdraws
+            max_deposit_block_age: MAX_DEPOSIT_AGE,
             fee_pool_target_balance: 100_000_000, // 1 BTC
             fee_pool_reward_split: (1, 10),
+            slash_fraction: SLASH_FRACTION,
+            confirmation_tiers: vec![],
+            ibc_extra_confirmations: 0,
+            max_memo_length: MAX_MEMO_LENGTH,
+        }
+    }
+
+    /// Checks that the parameters are usable by the bridge.
+    pub fn validate(&self) -> ContractResult<()> {
+        if self.units_per_sat == 0 {
+            return Err(ContractError::App(
+                "units_per_sat must be non-zero".to_string(),
+            ));
+        }
+        let (numerator, denominator) = self.slash_fraction;
+        if denominator == 0 || numerator > denominator {
+            return Err(ContractError::App(
+                "slash_fraction must be a fraction between 0 and 1".to_string(),
+            ));
         }
+        Ok(())
+    }
+
+    /// The number of confirmations a deposit of `amount` satoshis needs
+    /// before it is credited, regardless of its destination (see
+    /// `confirmation_tiers`).
+    pub fn amount_confirmations(&self, amount: u64) -> u32 {
+        let extra = self
+            .confirmation_tiers
+            .iter()
+            .filter(|(threshold, _)| amount > *threshold)
+            .map(|(_, extra)| *extra)
+            .max()
+            .unwrap_or(0);
+        self.min_confirmations.saturating_add(extra)
+    }
+
+    /// The number of confirmations a deposit of `amount` satoshis to `dest`
+    /// needs before it is credited (see `amount_confirmations` and
+    /// `ibc_extra_confirmations`).
+    pub fn deposit_confirmations(&self, amount: u64, dest: &Dest) -> u32 {
+        let dest_extra = match dest {
+            Dest::Ibc(_) => self.ibc_extra_confirmations,
+            Dest::Address(_) | Dest::Contract { .. } => 0,
+        };
+        self.amount_confirmations(amount).saturating_add(dest_extra)
+    }
+
+    /// Converts an amount in satoshis to nBTC units.
+    pub fn sat_to_units(&self, sats: Sats) -> ContractResult<MSats> {
+        sats.to_msats(self.units_per_sat)
+    }
+
+    /// Converts an amount in nBTC units to satoshis, rounding down.
+    pub fn units_to_sat(&self, units: MSats) -> ContractResult<Sats> {
+        units.to_sats(self.units_per_sat)
     }
 }
 
@@ -293,6 +567,105 @@ pub struct CheckpointConfig {
     /// issue is simply with relayers failing to report the confirmation of the
     /// checkpoint transactions.    
     pub max_unconfirmed_checkpoints: u32,
+
+    /// The number of consecutive unconfirmed checkpoints paying
+    /// `max_fee_rate` after which the network stops creating new checkpoints,
+    /// since raising the fee rate further is not possible. Zero disables the
+    /// check.
+    #[serde(default)]
+    pub max_fee_rate_checkpoints: u32,
+
+    /// The maximum share of voting power, in basis points, which may move to
+    /// new or growing signatories in a single checkpoint (see
+    /// `ChangeRates::sigset_change`). A checkpoint whose signatory set would
+    /// change by more than this only moves that far towards the new voting
+    /// power, converging over the following checkpoints. Zero disables the
+    /// check.
+    #[serde(default)]
+    pub max_sigset_change: u16,
+
+    /// The minimum voting power, in basis points of the signatory set's total,
+    /// a signatory needs to be included in a new signatory set. Smaller
+    /// signatories are left out to keep the reserve script small, as long as
+    /// the rest of the set still exceeds the full set's signing threshold.
+    /// Zero disables pruning.
+    #[serde(default)]
+    pub min_signatory_power: u16,
+
+    /// How to choose which inputs are spent when a checkpoint has more than
+    /// `max_inputs` inputs. The reserve input is always spent.
+    #[serde(default)]
+    pub input_selection: InputSelection,
+
+    /// The number of outputs the reserve is split evenly across, each spent
+    /// by the next checkpoint. Fewer are created if the shares would be below
+    /// the dust limit. Zero is treated as one.
+    #[serde(default)]
+    pub reserve_output_count: u32,
+
+    /// The number of seconds a checkpoint may stay in the `Signing` state
+    /// without reaching the signing threshold. Once exceeded, the checkpoint is
+    /// aborted and its inputs and outputs return to the `Building` checkpoint.
+    /// Zero disables the timeout.
+    #[serde(default)]
+    pub signing_timeout: u64,
+
+    /// The fee rate, in satoshis per virtual byte, above which a checkpoint
+    /// spends its largest inputs first regardless of `input_selection`, to
+    /// keep the transaction small. Zero disables the override.
+    #[serde(default)]
+    pub largest_first_fee_rate: u64,
+
+    /// The number of seconds after a checkpoint's creation at which an
+    /// emergency disbursal of its reserve may be broadcast, used as the
+    /// absolute lock time of the disbursal transaction.
+    #[serde(default = "default_emergency_disbursal_lock_time_interval")]
+    pub emergency_disbursal_lock_time_interval: u64,
+
+    /// The number of UTXOs spent by the `Building` checkpoint (its reserve
+    /// and carried-over deposit inputs) above which it is advanced early to
+    /// consolidate them, if it has no withdrawals or transfers of its own.
+    /// Zero disables consolidation.
+    #[serde(default)]
+    pub rebalance_utxo_threshold: u32,
+
+    /// The minimum number of signatories the `Building` checkpoint's
+    /// signatory set must have before it can advance to `Signing`, on top of
+    /// the voting power threshold. Zero disables the check.
+    #[serde(default)]
+    pub min_signatories: u32,
+
+    /// The maximum size of an emergency disbursal transaction, in virtual
+    /// bytes. Accounts are split across as many transactions as needed to
+    /// stay below it. Zero disables the limit.
+    #[serde(default)]
+    pub emergency_disbursal_max_tx_size: u64,
+
+    /// The minimum nBTC balance, in satoshis, for an account to be paid out in
+    /// an emergency disbursal. Balances below the dust limit of the account's
+    /// recovery script are always left out. Zero disables the minimum.
+    #[serde(default)]
+    pub emergency_disbursal_min_tx_amt: u64,
+}
+
+fn default_emergency_disbursal_lock_time_interval() -> u64 {
+    EMERGENCY_DISBURSAL_LOCK_TIME_INTERVAL
+}
+
+/// Strategy for choosing which inputs a checkpoint spends when it has more
+/// inputs than `max_inputs`, with the rest carried over to the next checkpoint.
+#[cw_serde]
+#[derive(Default)]
+pub enum InputSelection {
+    /// Spend inputs in the order they were added.
+    #[default]
+    Oldest,
+    /// Spend the smallest inputs first, consolidating small deposits to bound
+    /// the number of UTXOs held by the network.
+    SmallestFirst,
+    /// Spend the largest inputs first, keeping the transaction small when
+    /// fees are high and leaving small deposits to be consolidated later.
+    LargestFirst,
 }
 
 impl Default for CheckpointConfig {
@@ -309,11 +682,72 @@ impl Default for CheckpointConfig {
             user_fee_factor: USER_FEE_FACTOR, // 2.7x
             sigset_threshold: SIGSET_THRESHOLD,
             max_unconfirmed_checkpoints: 15,
+            max_fee_rate_checkpoints: 5,
+            max_sigset_change: 0,
+            min_signatory_power: 0,
             fee_rate: 0,
+            input_selection: InputSelection::default(),
+            reserve_output_count: 1,
+            signing_timeout: SIGNING_TIMEOUT,
+            largest_first_fee_rate: 0,
+            emergency_disbursal_lock_time_interval: EMERGENCY_DISBURSAL_LOCK_TIME_INTERVAL,
+            rebalance_utxo_threshold: 0,
+            min_signatories: 0,
+            emergency_disbursal_max_tx_size: 50_000,
+            emergency_disbursal_min_tx_amt: 1_000,
         }
     }
 }
 
+impl CheckpointConfig {
+    /// Checks that the parameters are consistent with each other, so that the
+    /// checkpoint queue can operate with them.
+    pub fn validate(&self) -> ContractResult<()> {
+        if self.min_fee_rate > self.max_fee_rate {
+            return Err(ContractError::Checkpoint(
+                "min_fee_rate must not exceed max_fee_rate".to_string(),
+            ));
+        }
+        let (numerator, denominator) = self.sigset_threshold;
+        if denominator == 0 || numerator > denominator {
+            return Err(ContractError::Checkpoint(
+                "sigset_threshold must be a ratio no greater than 1".to_string(),
+            ));
+        }
+        if self.min_checkpoint_interval > self.max_checkpoint_interval {
+            return Err(ContractError::Checkpoint(
+                "min_checkpoint_interval must not exceed max_checkpoint_interval".to_string(),
+            ));
+        }
+        if self.max_sigset_change > 10_000 {
+            return Err(ContractError::Checkpoint(
+                "max_sigset_change must not exceed 10000 basis points".to_string(),
+            ));
+        }
+        if self.min_signatory_power > 10_000 {
+            return Err(ContractError::Checkpoint(
+                "min_signatory_power must not exceed 10000 basis points".to_string(),
+            ));
+        }
+        if self.max_inputs == 0 || self.max_outputs == 0 {
+            return Err(ContractError::Checkpoint(
+                "max_inputs and max_outputs must be non-zero".to_string(),
+            ));
+        }
+        // the reserve outputs and the timestamping commitment must fit in the
+        // checkpoint tx, and the next one must be able to spend every reserve
+        // output
+        let reserve_outputs = self.reserve_output_count.max(1) as u64;
+        if reserve_outputs >= self.max_outputs || reserve_outputs > self.max_inputs {
+            return Err(ContractError::Checkpoint(
+                "reserve_output_count must be less than max_outputs and at most max_inputs"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
 /// A Bitcoin extended public key, used to derive Bitcoin public keys which
 /// signatories sign transactions with.
 #[derive(Copy, Clone, PartialEq, Deref, Eq, Debug, PartialOrd, Ord, Hash)]
@@ -421,6 +855,10 @@ pub struct HeaderConfig {
     /// The maximum number of headers that can be stored in the header queue
     /// before pruning.
     pub max_length: u64,
+    /// The maximum number of headers below the current tip that a competing
+    /// chain may replace.
+    #[serde(default = "default_max_reorg_depth")]
+    pub max_reorg_depth: u32,
     /// The maximum amount of time (in seconds) that can pass between the
     /// timestamp of the last header in the header queue and the timestamp of
     /// the next header to be added.
@@ -447,6 +885,19 @@ pub struct HeaderConfig {
     /// The trusted header (the header which populates the queue when it is
     /// newly created), as encoded bytes.
     pub trusted_header: Adapter<BlockHeader>,
+    /// The Bitcoin network the headers belong to, which determines address
+    /// encoding and the proof-of-work rules applied to relayed headers.
+    #[serde(default = "default_network")]
+    #[schemars(with = "String")]
+    pub network: bitcoin::Network,
+}
+
+fn default_network() -> bitcoin::Network {
+    NETWORK
+}
+
+fn default_max_reorg_depth() -> u32 {
+    MAX_REORG_DEPTH
 }
 
 impl HeaderConfig {
@@ -455,20 +906,38 @@ impl HeaderConfig {
     }
 
     pub fn from_bytes(checkpoint_json: &[u8]) -> ContractResult<Self> {
+        Self::from_bytes_for_network(checkpoint_json, bitcoin::Network::Bitcoin)
+    }
+
+    /// Builds a config trusting the `(height, header)` checkpoint in
+    /// `checkpoint_json`, using the difficulty rules of the given network.
+    pub fn from_bytes_for_network(
+        checkpoint_json: &[u8],
+        network: bitcoin::Network,
+    ) -> ContractResult<Self> {
         let checkpoint: (u32, BlockHeader) = from_json(checkpoint_json)?;
         let (height, header) = checkpoint;
 
         Ok(Self {
             max_length: MAX_LENGTH,
+            max_reorg_depth: MAX_REORG_DEPTH,
             max_time_increase: MAX_TIME_INCREASE,
             trusted_height: height,
             retarget_interval: RETARGET_INTERVAL,
             target_spacing: TARGET_SPACING,
             target_timespan: TARGET_TIMESPAN,
-            max_target: MAX_TARGET,
+            max_target: match network {
+                bitcoin::Network::Signet => SIGNET_MAX_TARGET,
+                bitcoin::Network::Regtest => REGTEST_MAX_TARGET,
+                _ => MAX_TARGET,
+            },
             trusted_header: header.into(),
-            retargeting: true,
-            min_difficulty_blocks: false,
+            retargeting: network != bitcoin::Network::Regtest,
+            min_difficulty_blocks: matches!(
+                network,
+                bitcoin::Network::Testnet | bitcoin::Network::Regtest
+            ),
+            network,
         })
     }
 
@@ -480,10 +949,15 @@ impl HeaderConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(crate = "cosmwasm_schema::serde")]
+/// Rates of change over an interval, in basis points (1/100th of a percent).
+#[cw_serde]
+#[derive(Default)]
 pub struct ChangeRates {
+    /// The decrease of the reserve output value, relative to its value at the
+    /// start of the interval.
     pub withdrawal: u16,
+    /// The share of voting power in the signatory set held by signers who
+    /// gained voting power over the interval.
     pub sigset_change: u16,
 }
 
@@ -10,11 +10,13 @@ mod interface;
 pub mod msg;
 
 mod adapter;
+mod emergency;
 mod outpoint_set;
 mod recovery;
 mod signatory;
 mod state;
 mod threshold_sig;
+mod units;
 
 pub mod contract;
 
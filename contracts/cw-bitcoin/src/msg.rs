@@ -1,16 +1,24 @@
 use bitcoin::{util::merkleblock::PartialMerkleTree, Script, Transaction};
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Binary};
+use cosmwasm_std::{Addr, Binary, Coin, Uint128};
 use token_bindings::Metadata;
 
 use crate::{
     adapter::{Adapter, HashBinary},
     app::ConsensusKey,
+    checkpoint::CheckpointStatus,
+    constants::DEFAULT_FEE_RATE_HISTORY_LIMIT,
     header::WrappedHeader,
-    interface::{BitcoinConfig, CheckpointConfig, Dest, HeaderConfig, Xpub},
-    threshold_sig::Signature,
+    interface::{
+        BitcoinConfig, ChangeRates, CheckpointConfig, DepositRecord, Dest, HeaderConfig, Xpub,
+    },
+    threshold_sig::{Signature, SignatureEncoding},
 };
 
+fn default_fee_rate_history_limit() -> u32 {
+    DEFAULT_FEE_RATE_HISTORY_LIMIT
+}
+
 #[cw_serde]
 pub struct InstantiateMsg {
     pub token_factory_addr: Addr,
@@ -35,24 +43,71 @@ pub enum ExecuteMsg {
         btc_tx: Adapter<Transaction>,
         btc_height: u32,
         btc_proof: Adapter<PartialMerkleTree>,
+        /// The position of `btc_tx` in its block. If given, a proof placing
+        /// the transaction elsewhere is rejected with `BlockIndexMismatch`.
+        #[serde(default)]
+        block_index: Option<u32>,
         btc_vout: u32,
         sigset_index: u32,
         dest: Dest,
     },
+    RelayDepositMulti {
+        btc_tx: Adapter<Transaction>,
+        btc_height: u32,
+        btc_proof: Adapter<PartialMerkleTree>,
+        outputs: Vec<(u32, Dest)>,
+        sigset_index: u32,
+    },
     RelayCheckpoint {
         btc_height: u32,
         btc_proof: Adapter<PartialMerkleTree>,
         cp_index: u32,
+        /// The mempool fee rate seen by the relayer, in satoshis per virtual
+        /// byte, which the next checkpoint's fee rate is raised towards. Only
+        /// accepted from the owner or a registered signer.
+        observed_fee_rate: Option<u64>,
+    },
+    /// Confirms a checkpoint given its consensus-serialized transaction and a
+    /// consensus-serialized merkle proof placing it at `block_index` in the
+    /// block at `height`, which must have at least
+    /// `min_checkpoint_confirmations + 1` confirmations.
+    ConfirmCheckpoint {
+        index: u32,
+        tx: Binary,
+        height: u32,
+        proof: Binary,
+        block_index: u32,
+        /// See `RelayCheckpoint::observed_fee_rate`.
+        observed_fee_rate: Option<u64>,
     },
     WithdrawToBitcoin {
         script_pubkey: Adapter<Script>,
     },
+    CancelWithdrawal {
+        checkpoint_index: u32,
+        output_index: u32,
+    },
+    Transfer {
+        to: String,
+    },
+    /// Superseded by `SubmitSignatures`, which it is handled as with compact
+    /// signatures. `btc_height` is ignored, the signing height being taken
+    /// from the header queue.
     SubmitCheckpointSignature {
         xpub: HashBinary<Xpub>,
         sigs: Vec<Signature>,
         checkpoint_index: u32,
         btc_height: u32,
     },
+    SubmitSignatures {
+        xpub: HashBinary<Xpub>,
+        sigs: Vec<Binary>,
+        /// The index of the signatory set of the checkpoint being signed,
+        /// which is the index of that checkpoint.
+        sigset_index: u32,
+        /// The encoding of `sigs`, compact if omitted.
+        encoding: Option<SignatureEncoding>,
+    },
     SubmitRecoverySignature {
         xpub: HashBinary<Xpub>,
         sigs: Vec<Signature>,
@@ -60,20 +115,54 @@ pub enum ExecuteMsg {
     SetSignatoryKey {
         xpub: HashBinary<Xpub>,
     },
+    SetRecoveryScript {
+        script: Binary,
+    },
     AddValidators {
         addrs: Vec<String>,
         infos: Vec<(u64, ConsensusKey)>,
     },
+    RegisterValidator {
+        /// The address which submits the validator's signatures.
+        operator: String,
+        /// The validator's 32-byte consensus key.
+        consensus_key: Binary,
+        power: u64,
+    },
+    /// Restores a jailed validator at the voting power it was left with after
+    /// slashing.
+    UnjailValidator {
+        /// The address which submits the validator's signatures.
+        operator: String,
+        /// The validator's 32-byte consensus key.
+        consensus_key: Binary,
+    },
     RegisterDenom {
         subdenom: String,
         metadata: Option<Metadata>,
     },
+    BumpCheckpointFee {
+        index: u32,
+        fee_rate: u64,
+    },
+    ClaimSignatoryRewards {},
+    PruneCheckpoints {},
+    SetIbcChannelAllowlist {
+        channels: Vec<String>,
+    },
     #[cfg(test)]
     TriggerBeginBlock {
         hash: Binary,
     },
 }
 
+/// The message a `Dest::Contract` is executed with when a deposit is credited
+/// to it, matching the cw20 receive hook.
+#[cw_serde]
+pub enum ReceiverExecuteMsg {
+    Receive(cw20::Cw20ReceiveMsg),
+}
+
 #[cw_serde]
 pub enum SudoMsg {
     ClockEndBlock { hash: Binary },
@@ -84,6 +173,8 @@ pub enum SudoMsg {
 pub enum QueryMsg {
     #[returns(u32)]
     HeaderHeight {},
+    #[returns(HeaderTipResponse)]
+    HeaderTip {},
     #[returns(u64)]
     DepositFees { index: Option<u32> },
     #[returns(Vec<Adapter<Transaction>>)]
@@ -92,10 +183,109 @@ pub enum QueryMsg {
     SignedRecoveryTxs {},
     #[returns(u64)]
     WithdrawalFees { address: String, index: Option<u32> },
+    #[returns(WithdrawalFeeEstimateResponse)]
+    EstimateWithdrawalFee { script: Binary, amount: Uint128 },
+    #[returns(Binary)]
+    ScriptForAddress { address: String },
+    #[returns(String)]
+    AddressForScript { script: Binary },
+    #[returns(i64)]
+    FeePool {},
+    #[returns(bool)]
+    VerifyDepositProof {
+        tx: Binary,
+        height: u32,
+        proof: Binary,
+        block_index: u32,
+    },
+    #[returns(FeeRateResponse)]
+    CurrentFeeRate {},
+    #[returns(u64)]
+    ReserveValue {},
+    #[returns(TokenInfoResponse)]
+    TokenInfo {},
+    #[returns(TotalSupplyResponse)]
+    TotalSupply {},
+    #[returns(SigningProgressResponse)]
+    SigningProgress { index: u32 },
+    #[returns(DepositStatus)]
+    DepositStatus { txid: String, vout: u32 },
+    #[returns(Vec<DepositRecord>)]
+    DepositHistory {
+        address: String,
+        start_after: Option<u64>,
+        limit: u32,
+    },
+    #[returns(Vec<SignerLivenessResponse>)]
+    SignerLiveness {},
+    #[returns(Vec<SignatoryInfoResponse>)]
+    Signatories {},
     #[returns(HashBinary<bitcoin::BlockHash>)]
     SidechainBlockHash {},
     #[returns(u64)]
     CheckpointByIndex { index: u32 },
+    #[returns(HashBinary<bitcoin::Txid>)]
+    CheckpointTxid { index: u32 },
+    #[returns(String)]
+    CheckpointTxHex { index: u32 },
+    #[returns(CheckpointResponse)]
+    GetCheckpoint { index: Option<u32> },
+    #[returns(DepositAddressResponse)]
+    GetDepositAddress { dest: Dest },
+    #[returns(Vec<Vec<(Dest, Coin)>>)]
+    PendingDeposits {},
+    #[returns(Coin)]
+    Balance { address: String },
+    #[returns(ChangeRates)]
+    ChangeRates {
+        interval_start: u64,
+        interval_end: u64,
+        sigset_index: u32,
+    },
+    #[returns(Vec<(u32, u64, bool)>)]
+    FeeRateHistory {
+        /// The number of checkpoints to return, at most
+        /// `MAX_FEE_RATE_HISTORY_LIMIT`. Defaults to
+        /// `DEFAULT_FEE_RATE_HISTORY_LIMIT` when omitted.
+        #[serde(default = "default_fee_rate_history_limit")]
+        limit: u32,
+    },
+    #[returns(CheckpointQueueStatusResponse)]
+    CheckpointQueueStatus {},
+    #[returns(NextCheckpointEtaResponse)]
+    NextCheckpointEta {},
+    #[returns(WithdrawalsEnabledResponse)]
+    WithdrawalsEnabled {},
+    #[returns(u64)]
+    EmergencyDisbursalLockTime {},
+    #[returns(BuildingCheckpointResponse)]
+    BuildingCheckpoint {},
+    #[returns(Vec<CheckpointSummaryResponse>)]
+    ListCheckpoints {
+        start_after: Option<u32>,
+        limit: u32,
+    },
+    #[returns(Vec<ReserveUtxoResponse>)]
+    ReserveUtxos {
+        start_after: Option<String>,
+        limit: u32,
+    },
+    #[returns(SignatorySetResponse)]
+    SignatorySet { index: u32 },
+    #[returns(Binary)]
+    SignatorySetCommitment { index: u32 },
+    #[returns(Binary)]
+    GetDerivePubkey {
+        xpub: HashBinary<Xpub>,
+        sigset_index: u32,
+    },
+    #[returns(Vec<Binary>)]
+    GetDerivePubkeys {
+        xpubs: Vec<HashBinary<Xpub>>,
+        sigset_index: u32,
+    },
+    #[returns(Vec<([u8; 32], u32)>)]
+    ToSign { xpub: HashBinary<Xpub>, index: u32 },
     #[returns(Vec<([u8; 32], u32)>)] // Fix: Added closing angle bracket
     SigningRecoveryTxs { xpub: HashBinary<Xpub> },
     #[returns(Vec<([u8; 32], u32)>)] // Fix: Added closing angle bracket
@@ -103,6 +293,11 @@ pub enum QueryMsg {
         xpub: HashBinary<Xpub>,
         checkpoint_index: u32,
     },
+    #[returns(Vec<EmergencyDisbursalTxResponse>)]
+    EmergencyDisbursalTxs {
+        start_after: Option<u32>,
+        limit: u32,
+    },
     // Query index
     #[returns(u32)]
     ConfirmedIndex {},
@@ -117,3 +312,235 @@ pub enum QueryMsg {
 
 #[cw_serde]
 pub struct MigrateMsg {}
+
+#[cw_serde]
+pub struct CheckpointResponse {
+    pub index: u32,
+    pub tx: Adapter<Transaction>,
+    pub sigset_index: u32,
+    pub fee_rate: u64,
+    pub status: CheckpointStatus,
+}
+
+#[cw_serde]
+pub struct CheckpointQueueStatusResponse {
+    pub index: u32,
+    pub confirmed_index: Option<u32>,
+    pub first_unhandled_confirmed_cp_index: u32,
+    pub num_unconfirmed: u32,
+    pub max_unconfirmed_checkpoints: u32,
+    pub building_inputs: u32,
+    pub building_outputs: u32,
+}
+
+/// The last header in the header queue, which relayers extend from.
+#[cw_serde]
+pub struct HeaderTipResponse {
+    pub hash: HashBinary<bitcoin::BlockHash>,
+    pub height: u32,
+    /// The timestamp of the header, in seconds.
+    pub time: u32,
+    /// The total work of the chain up to and including the header, as a
+    /// decimal string.
+    pub chain_work: String,
+}
+
+/// An unspent reserve output controlled by the bridge.
+#[cw_serde]
+pub struct ReserveUtxoResponse {
+    pub txid: HashBinary<bitcoin::Txid>,
+    pub vout: u32,
+    /// The value of the output, in satoshis.
+    pub amount: u64,
+    /// The index of the signatory set whose script the output pays to.
+    pub sigset_index: u32,
+}
+
+/// The contents of the `Building` checkpoint so far, for inspecting a
+/// checkpoint which is not advancing.
+#[cw_serde]
+pub struct BuildingCheckpointResponse {
+    pub index: u32,
+    pub inputs: Vec<CheckpointInputResponse>,
+    pub outputs: Vec<CheckpointOutputResponse>,
+    /// The miner fees collected so far, in satoshis.
+    pub fees_collected: u64,
+    /// The number of nBTC transfers to be credited once the checkpoint is
+    /// signed.
+    pub pending: u32,
+}
+
+#[cw_serde]
+pub struct CheckpointInputResponse {
+    pub txid: HashBinary<bitcoin::Txid>,
+    pub vout: u32,
+    /// The value of the spent output, in satoshis.
+    pub amount: u64,
+}
+
+#[cw_serde]
+pub struct CheckpointOutputResponse {
+    pub script: Adapter<Script>,
+    /// The value of the output, in satoshis.
+    pub amount: u64,
+}
+
+/// A summary of a checkpoint still held in the queue.
+#[cw_serde]
+pub struct CheckpointSummaryResponse {
+    pub index: u32,
+    pub status: CheckpointStatus,
+    /// The fee rate paid by the checkpoint transaction, in satoshis per
+    /// virtual byte.
+    pub fee_rate: u64,
+    pub input_count: u32,
+    pub output_count: u32,
+    /// Whether the checkpoint transaction has been confirmed on the Bitcoin
+    /// blockchain.
+    pub confirmed: bool,
+}
+
+/// The window in which the `Building` checkpoint can advance to `Signing`, as
+/// timestamps in seconds.
+#[cw_serde]
+pub struct NextCheckpointEtaResponse {
+    /// The checkpoint can't advance before `min_checkpoint_interval` has
+    /// passed since it was created.
+    pub earliest: u64,
+    /// The checkpoint advances once `max_checkpoint_interval` has passed since
+    /// it was created, even without pending deposits or withdrawals.
+    pub latest: u64,
+}
+
+/// Whether withdrawals are accepted yet, which requires the network to have
+/// produced `min_withdrawal_checkpoints` checkpoints.
+#[cw_serde]
+pub struct WithdrawalsEnabledResponse {
+    pub enabled: bool,
+    /// The number of checkpoints in the queue, including the `Building` one.
+    pub checkpoints: u32,
+    pub min_withdrawal_checkpoints: u32,
+}
+
+#[cw_serde]
+pub struct DepositAddressResponse {
+    pub address: String,
+    pub script: Adapter<Script>,
+    pub sigset_index: u32,
+}
+
+/// How far a deposit has progressed towards being credited.
+#[cw_serde]
+pub enum DepositStatus {
+    /// The deposit has not been relayed.
+    Unseen,
+    /// The deposit has been relayed, but its block has fewer than
+    /// `min_confirmations` confirmations on the current header chain.
+    Seen,
+    /// The deposit is sufficiently confirmed, but the checkpoint it was
+    /// credited into has not completed (or it was not credited at all, e.g.
+    /// because it expired or was below the minimum deposit amount).
+    Confirmed,
+    /// The checkpoint spending the deposit has completed and its nBTC has been
+    /// minted to the destination.
+    Credited,
+    /// The deposit was credited, but its block was orphaned by a reorg before
+    /// its checkpoint advanced, so the credit has been reversed. It is
+    /// credited again once relayed from its block on the new chain.
+    Invalidated,
+}
+
+#[cw_serde]
+pub struct FeeRateResponse {
+    /// The fee rate of the `Building` checkpoint, in satoshis per virtual byte.
+    pub fee_rate: u64,
+    pub target_checkpoint_inclusion: u32,
+    pub min_fee_rate: u64,
+    pub max_fee_rate: u64,
+}
+
+#[cw_serde]
+pub struct TokenInfoResponse {
+    pub denom: String,
+    /// The bank supply of the nBTC denom, in nBTC units.
+    pub total_supply: Uint128,
+    /// The number of decimal places of an nBTC unit, from BTC's 8 decimals
+    /// and `units_per_sat`.
+    pub decimals: u32,
+}
+
+/// The nBTC held by all accounts, which should equal the reserve value less
+/// pending fees while the peg is healthy.
+#[cw_serde]
+pub struct TotalSupplyResponse {
+    pub denom: String,
+    /// The total supply in nBTC units.
+    pub units: Uint128,
+    /// The total supply in satoshis, rounded down.
+    pub sats: u64,
+}
+
+#[cw_serde]
+pub struct SigningProgressResponse {
+    /// The voting power which has signed the checkpoint's least-signed input.
+    pub signed_voting_power: u64,
+    /// The voting power that input's signatures must exceed.
+    pub threshold_voting_power: u64,
+    pub signed_inputs: u32,
+    pub total_inputs: u32,
+    /// Whether every input has met its threshold.
+    pub threshold_met: bool,
+}
+
+#[cw_serde]
+pub struct WithdrawalFeeEstimateResponse {
+    pub gross_amount: Uint128,
+    pub fee: Uint128,
+    pub net_amount: Uint128,
+}
+
+#[cw_serde]
+pub struct SignatoryResponse {
+    pub pubkey: Binary,
+    pub voting_power: u64,
+}
+
+#[cw_serde]
+pub struct SignatorySetResponse {
+    pub index: u32,
+    pub signatories: Vec<SignatoryResponse>,
+    pub present_vp: u64,
+    pub possible_vp: u64,
+    pub threshold: (u64, u64),
+}
+
+#[cw_serde]
+pub struct SignatoryInfoResponse {
+    pub address: String,
+    /// The signatory's xpub, or `None` if they have not submitted one yet.
+    pub xpub: Option<HashBinary<Xpub>>,
+    /// The signatory's voting power in the validator set.
+    pub voting_power: u64,
+}
+
+#[cw_serde]
+pub struct SignerLivenessResponse {
+    /// The signer's address, or `None` for a jailed signer which is no longer
+    /// registered.
+    pub address: Option<String>,
+    pub cons_key: ConsensusKey,
+    /// The number of most recent completed checkpoints in a row the signer
+    /// did not sign.
+    pub missed_checkpoints: u32,
+    pub jailed: bool,
+}
+
+/// A transaction of an emergency disbursal.
+#[cw_serde]
+pub struct EmergencyDisbursalTxResponse {
+    /// The position of the transaction in the chain. The first transaction
+    /// spends the checkpoint's reserve outputs, and each following one spends
+    /// the change output (the last output) of the transaction before it.
+    pub index: u32,
+    pub tx: Adapter<Transaction>,
+}
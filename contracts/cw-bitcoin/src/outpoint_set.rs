@@ -50,6 +50,15 @@ impl OutpointSet {
         Ok(())
     }
 
+    /// Remove an outpoint from the set, so that it can be relayed again.
+    ///
+    /// Its entry in the expiration queue is left to be pruned. The outpoint
+    /// pays a single signatory set, so it can only be inserted again with the
+    /// same expiration.
+    pub fn remove(&mut self, store: &mut dyn Storage, outpoint: bitcoin::OutPoint) {
+        OUTPOINTS.remove(store, &outpoint.to_string());
+    }
+
     /// Remove expired outpoints from the set.
     pub fn remove_expired(&mut self, store: &mut dyn Storage, now: u64) -> ContractResult<()> {
         // TODO: use drain iterator to eliminate need to collect into vec
@@ -41,6 +41,9 @@ pub struct RecoveryTxInput<'a> {
     pub new_sigset: &'a SignatorySet,
     pub threshold: (u64, u64),
     pub fee_rate: u64,
+    /// The commitment of the expired output's script, which is either the
+    /// dest's commitment bytes or `OP_RETURN_DEPOSIT_COMMITMENT`.
+    pub commitment: Vec<u8>,
     pub dest: Dest,
 }
 
@@ -59,7 +62,7 @@ impl RecoveryTxs {
         let input = Input::new(
             OutPoint::new(args.expired_tx.txid(), args.vout),
             args.old_sigset,
-            &args.dest.commitment_bytes()?,
+            &args.commitment,
             expired_output.value,
             args.threshold,
         )?;
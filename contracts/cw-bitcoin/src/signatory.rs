@@ -1,9 +1,11 @@
 use std::cmp::Ordering;
+use std::collections::HashMap;
 
 use crate::app::ConsensusKey;
-use crate::constants::MAX_SIGNATORIES;
+use crate::constants::{MAX_SIGNATORIES, SIGSET_THRESHOLD};
 use crate::interface::Xpub;
 use crate::state::get_validators;
+use crate::state::CHECKPOINT_CONFIG;
 use crate::state::SIG_KEYS;
 use crate::state::XPUBS;
 
@@ -20,6 +22,7 @@ use bitcoin_script::bitcoin_script as script;
 use cosmwasm_schema::serde::{Deserialize, Serialize};
 use cosmwasm_std::Order;
 use cosmwasm_std::Storage;
+use sha2::{Digest, Sha256};
 // use ed::Encode;
 
 /// The maximum number of signatories in a signatory set.
@@ -74,6 +77,17 @@ pub struct SignatorySet {
 
     /// The signatories in this set, sorted by voting power.
     pub signatories: Vec<Signatory>,
+
+    /// The total voting power of the signatories left out of this set for
+    /// being below `CheckpointConfig::min_signatory_power`. It is not counted
+    /// in `present_vp`, but still counts towards the signature threshold.
+    #[serde(default)]
+    pub pruned_vp: u64,
+
+    /// The signature threshold ratio configured when this set was created, or
+    /// `None` for sets created before it was recorded.
+    #[serde(default)]
+    pub threshold: Option<(u64, u64)>,
 }
 
 impl SignatorySet {
@@ -89,6 +103,8 @@ impl SignatorySet {
             possible_vp: 0,
             index,
             signatories: vec![],
+            pruned_vp: 0,
+            threshold: None,
         };
 
         let val_set = get_validators(store)?;
@@ -110,6 +126,10 @@ impl SignatorySet {
 
         sigset.sort_and_truncate();
 
+        let config = CHECKPOINT_CONFIG.load(store)?;
+        sigset.prune(config.min_signatory_power, config.sigset_threshold);
+        sigset.threshold = Some(config.sigset_threshold);
+
         Ok(sigset)
     }
 
@@ -271,6 +291,8 @@ impl SignatorySet {
             possible_vp: total_vp,
             create_time: 0,
             index: 0,
+            pruned_vp: 0,
+            threshold: Some(threshold_ratio),
         };
 
         for _ in 0..100 {
@@ -312,10 +334,39 @@ impl SignatorySet {
         }
     }
 
+    /// Removes signatories whose voting power is below `min_power` basis points
+    /// of the set's present voting power, smallest first, to keep the reserve
+    /// script small. Pruning stops before the remaining voting power would no
+    /// longer exceed the signature threshold of the unpruned set, so the
+    /// remaining signatories can still meet it on their own.
+    fn prune(&mut self, min_power: u16, threshold_ratio: (u64, u64)) {
+        if min_power == 0 {
+            return;
+        }
+
+        let min_vp = (self.present_vp as u128 * min_power as u128 / 10_000) as u64;
+        let unpruned_threshold = self.signature_threshold(threshold_ratio);
+        // signatories are sorted by descending voting power
+        while self.signatories.len() > 1 {
+            let power = self.signatories[self.signatories.len() - 1].voting_power;
+            if power >= min_vp || self.present_vp - power <= unpruned_threshold {
+                break;
+            }
+            self.signatories.pop();
+            self.present_vp -= power;
+            self.pruned_vp += power;
+        }
+    }
+
     /// The voting power threshold required to spend outputs secured by this
     /// signatory set.
+    ///
+    /// This is taken from the voting power of the set before pruning, so
+    /// pruning small signatories never lowers the share of voting power needed
+    /// to spend.
     pub fn signature_threshold(&self, (numerator, denominator): (u64, u64)) -> u64 {
-        ((self.present_vp as u128) * numerator as u128 / denominator as u128) as u64
+        ((self.present_vp + self.pruned_vp) as u128 * numerator as u128 / denominator as u128)
+            as u64
     }
 
     /// The quorum threshold required for the signatory set to be valid.
@@ -463,6 +514,34 @@ impl SignatorySet {
         self.signatories.iter()
     }
 
+    /// A canonical commitment to the signatories and signing threshold of this
+    /// set, for other chains to verify the bridge's signer set against.
+    ///
+    /// This is the SHA256 hash of each signatory's compressed pubkey followed
+    /// by its voting power as a big-endian `u64`, in ascending order of
+    /// pubkey, followed by the signature threshold as a big-endian `u64`. The
+    /// threshold uses the ratio recorded when the set was created, falling back
+    /// to [`SIGSET_THRESHOLD`] for older sets, so later config changes don't
+    /// alter it. It doesn't depend on the order the signatories were inserted
+    /// in.
+    pub fn commitment(&self) -> [u8; 32] {
+        let threshold = self.threshold.unwrap_or(SIGSET_THRESHOLD);
+        let mut signatories: Vec<_> = self
+            .signatories
+            .iter()
+            .map(|signatory| (signatory.pubkey.as_slice(), signatory.voting_power))
+            .collect();
+        signatories.sort();
+
+        let mut hasher = Sha256::new();
+        for (pubkey, voting_power) in signatories {
+            hasher.update(pubkey);
+            hasher.update(voting_power.to_be_bytes());
+        }
+        hasher.update(self.signature_threshold(threshold).to_be_bytes());
+        hasher.finalize().into()
+    }
+
     /// The estimated size of a witness containing the redeem script and
     /// signatures for this signatory set, in virtual bytes.
     ///
@@ -473,6 +552,95 @@ impl SignatorySet {
     pub fn est_witness_vsize(&self) -> u64 {
         self.signatories.len() as u64 * 79 + 39
     }
+
+    /// The share of this set's present voting power held by each registered
+    /// signatory, keyed by the encoding of their xpub so that sets derived at
+    /// different indexes can be compared.
+    fn vp_shares(&self, store: &dyn Storage) -> ContractResult<HashMap<[u8; 78], f64>> {
+        let total_vp = self.present_vp() as f64;
+        let fractions: HashMap<_, _> = self
+            .iter()
+            .map(|v| (v.pubkey.as_slice(), v.voting_power as f64 / total_vp))
+            .collect();
+        let mut shares = HashMap::new();
+        for entry in SIG_KEYS.range_raw(store, None, None, Order::Ascending) {
+            let (_, xpub) = entry?;
+            let pubkey: Pubkey = xpub.derive_pubkey(self.index)?.into();
+            shares.insert(
+                xpub.key.encode(),
+                *fractions.get(pubkey.as_slice()).unwrap_or(&0.0),
+            );
+        }
+
+        Ok(shares)
+    }
+
+    /// The amount of voting power which changed hands between `prev` and this
+    /// signatory set, in basis points. This is the sum of the share increases
+    /// of every signatory whose share grew.
+    pub fn change_since(&self, store: &dyn Storage, prev: &SignatorySet) -> ContractResult<u16> {
+        let now_shares = self.vp_shares(store)?;
+        let prev_shares = prev.vp_shares(store)?;
+        let change = now_shares.iter().fold(0.0, |acc, (k, v)| {
+            let prev_share = prev_shares.get(k).unwrap_or(&0.0);
+            if v > prev_share {
+                acc + (v - prev_share)
+            } else {
+                acc
+            }
+        });
+
+        Ok((change * 10_000.0) as u16)
+    }
+
+    /// Moves this set's voting power only part of the way from `prev` so that
+    /// it changes by no more than `max_change` basis points (see
+    /// `change_since`).
+    ///
+    /// Each registered signatory's share is interpolated between its share in
+    /// `prev` and its share in this set, and the signatories are re-derived at
+    /// this set's index, so the set keeps its own index and creation time.
+    /// Repeated over several checkpoints, the shares converge on those of the
+    /// validator set.
+    pub fn limit_change(
+        &mut self,
+        store: &dyn Storage,
+        prev: &SignatorySet,
+        max_change: u16,
+    ) -> ContractResult<()> {
+        let change = self.change_since(store, prev)?;
+        if change <= max_change {
+            return Ok(());
+        }
+        let step = max_change as f64 / change as f64;
+
+        let now_shares = self.vp_shares(store)?;
+        let prev_shares = prev.vp_shares(store)?;
+        let total_vp = self.present_vp as f64;
+
+        self.signatories.clear();
+        self.present_vp = 0;
+        for entry in SIG_KEYS.range_raw(store, None, None, Order::Ascending) {
+            let (_, xpub) = entry?;
+            let key = xpub.key.encode();
+            let now_share = *now_shares.get(&key).unwrap_or(&0.0);
+            let prev_share = *prev_shares.get(&key).unwrap_or(&0.0);
+            let share = prev_share + (now_share - prev_share) * step;
+
+            let voting_power = (share * total_vp).round() as u64;
+            if voting_power == 0 {
+                continue;
+            }
+            self.insert(Signatory {
+                voting_power,
+                pubkey: xpub.derive_pubkey(self.index)?.into(),
+            });
+        }
+
+        self.sort_and_truncate();
+
+        Ok(())
+    }
 }
 
 /// A collection storing the signatory extended public keys of each validator
@@ -515,7 +683,7 @@ impl SignatoryKeys {
         normalized_xpub.key.parent_fingerprint = Default::default();
         let xpub_key = &normalized_xpub.encode();
         if XPUBS.has(store, xpub_key) {
-            return Err(ContractError::App("Duplicate signatory key".to_string()));
+            return Err(ContractError::Signer("Duplicate signatory key".to_string()));
         }
 
         SIG_KEYS.save(store, &consensus_key, &xpub)?;
@@ -1,5 +1,6 @@
 use bitcoin::util::uint::Uint256;
-use cosmwasm_std::{Order, Storage};
+use bitcoin::Script;
+use cosmwasm_std::{Addr, Coin, Order, Storage, Uint128};
 use cw_storage_plus::{Item, Map};
 
 use crate::{
@@ -8,9 +9,10 @@ use crate::{
     checkpoint::Checkpoint,
     constants::BTC_NATIVE_TOKEN_DENOM,
     error::ContractResult,
-    header::WorkHeader,
+    header::{WorkHeader, WrappedHeader},
     interface::{
-        BitcoinConfig, CheckpointConfig, Config, DequeExtension, HeaderConfig, Validator, Xpub,
+        BitcoinConfig, CheckpointConfig, Config, DepositRecord, DequeExtension, Dest, HeaderConfig,
+        Validator, Xpub,
     },
     recovery::RecoveryTx,
 };
@@ -25,6 +27,11 @@ pub const BITCOIN_CONFIG: Item<BitcoinConfig> = Item::new("bitcoin_config");
 /// Mapping validator ConsensusKey => (power, Address)
 pub const VALIDATORS: Map<&ConsensusKey, (u64, String)> = Map::new("validators");
 
+/// Mapping of validator ConsensusKeys jailed for failing to sign checkpoints
+/// => their voting power left after slashing, which they are restored at if
+/// unjailed
+pub const JAILED: Map<&ConsensusKey, u64> = Map::new("jailed");
+
 /// Mapping validator Address => ConsensusKey
 pub const SIGNERS: Map<&str, ConsensusKey> = Map::new("signers");
 
@@ -49,6 +56,11 @@ pub const XPUBS: Map<&[u8], ()> = Map::new("xpubs");
 /// configured pruning level based on the `max_length` config parameter).
 pub const HEADERS: DequeExtension<WorkHeader> = DequeExtension::new("headers");
 
+/// Mapping block hash => header of competing forks which branch off the
+/// header queue within `max_reorg_depth` but do not yet have more work than
+/// it. A fork replaces the queue's tip once it is extended past its work.
+pub const FORK_HEADERS: Map<&str, WrappedHeader> = Map::new("fork_headers");
+
 pub const RECOVERY_TXS: DequeExtension<RecoveryTx> = DequeExtension::new("recovery_txs");
 
 /// A queue of outpoints to expire, sorted by expiration timestamp.
@@ -57,8 +69,66 @@ pub const EXPIRATION_QUEUE: Map<(u64, &str), ()> = Map::new("expiration_queue");
 /// A set of outpoints.
 pub const OUTPOINTS: Map<&str, ()> = Map::new("outpoints");
 
+/// Mapping outpoint => (sigset index, dest, value in sats) of deposits which
+/// were below `min_deposit_amount` and so were not credited or spent into the
+/// reserve, kept so they can be swept later.
+pub const SMALL_DEPOSITS: Map<&str, (u32, Dest, u64)> = Map::new("small_deposits");
+
+/// Mapping outpoint => (Bitcoin height of the block containing the deposit,
+/// index of the checkpoint it was credited into) for every relayed deposit.
+/// The checkpoint index is `None` for deposits which were not credited.
+pub const DEPOSIT_HEIGHTS: Map<&str, (u32, Option<u32>)> = Map::new("deposit_heights");
+
+/// Mapping (hash of the Bitcoin block containing the deposit, outpoint) =>
+/// (dest, nBTC credited) for every credited deposit, so the deposits in a
+/// block orphaned by a reorg can be reversed. The checkpoint each deposit was
+/// credited into is kept in `DEPOSIT_HEIGHTS`.
+pub const BLOCK_DEPOSITS: Map<(&str, &str), (Dest, Coin)> = Map::new("block_deposits");
+
+/// A set of outpoints of credited deposits whose block was orphaned by a
+/// reorg.
+pub const INVALIDATED_DEPOSITS: Map<&str, ()> = Map::new("invalidated_deposits");
+
+/// Append-only log of the deposits credited to each address, keyed by
+/// (address, index in the address's history).
+pub const DEPOSIT_HISTORY: Map<(&str, u64), DepositRecord> = Map::new("deposit_history");
+
+/// Mapping address => scriptPubKey the account's funds should be paid to in
+/// an emergency disbursal.
+pub const RECOVERY_SCRIPTS: Map<&str, Adapter<Script>> = Map::new("recovery_scripts");
+
 pub const FEE_POOL: Item<i64> = Item::new("fee_pool");
 
+/// The `SCHEMA_VERSION` the stored state was last written with. Missing for
+/// contracts instantiated before it was introduced.
+pub const STORED_SCHEMA_VERSION: Item<u32> = Item::new("schema_version");
+
+/// Mapping submessage id => (recipient, nBTC sent) of deposits handed to a
+/// `Dest::Contract` receive hook, or sent to a `Dest::Ibc` over ICS-20 with
+/// `sender` as recipient, whose reply is still pending.
+pub const PENDING_RECEIVES: Map<u64, (Addr, Coin)> = Map::new("pending_receives");
+/// The id of the next submessage whose reply is handled, shared by receive
+/// hooks and IBC transfers.
+pub const NEXT_RECEIVE_ID: Item<u64> = Item::new("next_receive_id");
+
+/// The IBC source channels deposits may be routed through, set by the owner.
+/// Every channel is allowed until it is first set.
+pub const IBC_CHANNEL_ALLOWLIST: Item<Vec<String>> = Item::new("ibc_channel_allowlist");
+
+/// Mapping signatory validator Address => nBTC rewards accrued from checkpoint
+/// fee surpluses
+pub const SIGNATORY_REWARDS: Map<&str, Uint128> = Map::new("signatory_rewards");
+
+/// Mapping checkpoint index => (withdrawer, nBTC amount, miner fee) of each
+/// withdrawal output of that checkpoint, in output order. Kept while the
+/// checkpoint is `Building`, so that withdrawals can still be canceled, and
+/// while it is `Signing`, in case signing expires.
+pub const WITHDRAWALS: Map<u32, Vec<(Addr, Uint128, Uint128)>> = Map::new("withdrawals");
+
+/// The highest mempool fee rate reported by relayers since the last checkpoint
+/// was advanced, in satoshis per virtual byte.
+pub const OBSERVED_FEE_RATE: Item<u64> = Item::new("observed_fee_rate");
+
 pub const CHECKPOINTS: DequeExtension<Checkpoint> = DequeExtension::new("checkpoints");
 /// Checkpoint building index
 pub const BUILDING_INDEX: Item<u32> = Item::new("building_index");
@@ -1,29 +1,53 @@
 use super::helper::sign;
-use adapter::Adapter;
-use app::Bitcoin;
+use adapter::{Adapter, HashBinary};
+use app::{validate_withdrawal_script, Bitcoin, DepositOutcome};
+use bitcoin::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_PUSHNUM_1};
+use bitcoin::blockdata::script::Builder;
+use bitcoin::hashes::hex::FromHex;
 use bitcoin::hashes::Hash;
 use bitcoin::util::bip32::ExtendedPubKey;
 use bitcoin::util::merkleblock::PartialMerkleTree;
 use bitcoin::util::uint::{self};
 use bitcoin::{
-    secp256k1::Secp256k1, util::bip32::ExtendedPrivKey, BlockHash, BlockHeader, OutPoint,
-    TxMerkleNode, Txid,
+    secp256k1::{ecdsa, Secp256k1},
+    util::bip32::ExtendedPrivKey,
+    BlockHash, BlockHeader, OutPoint, Sequence, TxMerkleNode, TxOut, Txid,
 };
 use bitcoin::{Script, Transaction};
-use checkpoint::{BatchType, Input};
-use constants::BTC_NATIVE_TOKEN_DENOM;
-use cosmwasm_std::testing::{mock_dependencies, mock_env};
-use cosmwasm_std::{Addr, Api, Coin, DepsMut, Env, Storage, Uint128};
-use error::ContractResult;
-use interface::{BitcoinConfig, CheckpointConfig, Dest, HeaderConfig, Xpub};
+use checkpoint::{adjust_fee_rate, BatchType, Checkpoint, CheckpointStatus, Input};
+use constants::{
+    BTC_NATIVE_TOKEN_DENOM, DEFAULT_FEE_RATE, MAX_FEE_RATE, MAX_PENDING_CHECKPOINTS,
+    OP_RETURN_DEPOSIT_COMMITMENT, RECEIVE_HOOK_GAS_LIMIT, SIGSET_THRESHOLD, SLASH_FRACTION,
+};
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi};
+use cosmwasm_std::{
+    to_json_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, DepsMut, Env, QuerierWrapper,
+    Reply, ReplyOn, Storage, SubMsgResult, Uint128, WasmMsg,
+};
+use entrypoints::{
+    query_address_for_script, query_balance, query_building_checkpoint, query_change_rates,
+    query_checkpoint_tx_hex, query_checkpoint_txid, query_current_fee_rate, query_deposit_address,
+    query_deposit_history, query_deposit_status, query_derive_pubkey, query_derive_pubkeys,
+    query_fee_pool, query_next_checkpoint_eta, query_pending_deposits, query_reserve_utxos,
+    query_reserve_value, query_script_for_address, query_signatories,
+    query_signatory_set_commitment, query_signer_liveness, query_signing_progress, query_to_sign,
+    query_token_info, query_total_supply, query_withdrawals_enabled,
+};
+use error::{ContractError, ContractResult};
+use interface::{BitcoinConfig, ChangeRates, CheckpointConfig, Config, Dest, HeaderConfig, Xpub};
+use msg::{DepositStatus, ExecuteMsg, ReceiverExecuteMsg};
 use state::{
-    BITCOIN_CONFIG, BUILDING_INDEX, CHECKPOINT_CONFIG, CONFIRMED_INDEX, FEE_POOL,
-    FIRST_UNHANDLED_CONFIRMED_INDEX, HEADERS, HEADER_CONFIG, SIGNERS, VALIDATORS,
+    get_full_btc_denom, BITCOIN_CONFIG, BUILDING_INDEX, CHECKPOINTS, CHECKPOINT_CONFIG, CONFIG,
+    CONFIRMED_INDEX, FEE_POOL, FIRST_UNHANDLED_CONFIRMED_INDEX, HEADERS, HEADER_CONFIG,
+    IBC_CHANNEL_ALLOWLIST, JAILED, OBSERVED_FEE_RATE, RECOVERY_TXS, SIGNATORY_REWARDS, SIGNERS,
+    SIG_KEYS, SMALL_DEPOSITS, VALIDATORS,
 };
 use std::cell::RefCell;
 use tests::helper::set_time;
+use threshold_sig::{SignatureEncoding, ThresholdSig};
 
 use crate::interface::IbcDest;
+use crate::units::{MSats, Sats};
 
 use crate::{
     header::{WorkHeader, WrappedHeader},
@@ -71,8 +95,8 @@ fn relay_height_validity() -> ContractResult<()> {
         let btc_tx = Transaction {
             input: vec![],
             lock_time: bitcoin::PackedLockTime(0),
-            output: vec![],
-            version: 0,
+            output: vec![TxOut::default()],
+            version: 1,
         };
         let btc_proof = PartialMerkleTree::from_txids(&[Txid::all_zeros()], &[true]);
 
@@ -84,6 +108,7 @@ fn relay_height_validity() -> ContractResult<()> {
             Adapter::new(btc_tx),
             height,
             Adapter::new(btc_proof),
+            None,
             0,
             0,
             Dest::Address(Addr::unchecked("")),
@@ -102,501 +127,4406 @@ fn relay_height_validity() -> ContractResult<()> {
     Ok(())
 }
 
-#[test]
-fn check_change_rates() -> ContractResult<()> {
-    let mut deps = mock_dependencies();
-
+/// Sets up a single signer with a `Building` checkpoint ready to receive
+/// deposits.
+fn setup_deposits(
+    store: &mut dyn Storage,
+    bitcoin_config: &BitcoinConfig,
+) -> ContractResult<Bitcoin> {
     let header_config = HeaderConfig::mainnet()?;
-    HEADER_CONFIG.save(deps.as_mut().storage, &header_config)?;
-    HEADERS.push_back(deps.as_mut().storage, &header_config.work_header())?;
-
-    let bitcoin_config = BitcoinConfig::default();
-    BITCOIN_CONFIG.save(deps.as_mut().storage, &bitcoin_config)?;
-    FEE_POOL.save(deps.as_mut().storage, &0)?;
-    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
-
-    BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
-
-    let consensus_key1 = [0; 32];
-    let consensus_key2 = [1; 32];
+    HEADER_CONFIG.save(store, &header_config)?;
+    HEADERS.push_back(store, &header_config.work_header())?;
 
-    let addr = ["validator1", "validator2"];
-
-    VALIDATORS.save(
-        deps.as_mut().storage,
-        &consensus_key1,
-        &(100, addr[0].to_string()),
-    )?;
-    VALIDATORS.save(
-        deps.as_mut().storage,
-        &consensus_key2,
-        &(10, addr[1].to_string()),
+    CONFIG.save(
+        store,
+        &Config {
+            token_factory_addr: Addr::unchecked("token_factory"),
+            owner: Addr::unchecked("owner"),
+            bridge_wasm_addr: None,
+        },
     )?;
+    BITCOIN_CONFIG.save(store, bitcoin_config)?;
+    FEE_POOL.save(store, &0)?;
+    CHECKPOINT_CONFIG.save(store, &CheckpointConfig::default())?;
+    BUILDING_INDEX.save(store, &0)?;
 
-    SIGNERS.save(deps.as_mut().storage, addr[0], &consensus_key1)?;
-    SIGNERS.save(deps.as_mut().storage, addr[1], &consensus_key2)?;
+    let consensus_key = [0; 32];
+    VALIDATORS.save(store, &consensus_key, &(100, "validator1".to_string()))?;
+    SIGNERS.save(store, "validator1", &consensus_key)?;
 
-    let btc = RefCell::new(Bitcoin::default());
+    let mut btc = Bitcoin::default();
     let secp = Secp256k1::new();
-    let network = btc.borrow().network();
-    let xpriv = vec![
-        ExtendedPrivKey::new_master(network, &[0])?,
-        ExtendedPrivKey::new_master(network, &[1])?,
-    ];
-    let xpub = vec![
-        ExtendedPubKey::from_priv(&secp, &xpriv[0]),
-        ExtendedPubKey::from_priv(&secp, &xpriv[1]),
-    ];
-
-    let push_deposit = |store: &mut dyn Storage| -> ContractResult<()> {
-        let btc = btc.borrow();
-        let sigset = &btc.checkpoints.building(store)?.sigset;
-        let input = Input::new(
-            OutPoint {
-                txid: Txid::from_slice(&[0; 32])?,
-                vout: 0,
-            },
-            sigset,
-            &[0u8],
-            100_000_000,
-            (9, 10),
-        )?;
+    let xpriv = ExtendedPrivKey::new_master(btc.network(store)?, &[0])?;
+    btc.set_signatory_key(
+        store,
+        Addr::unchecked("validator1"),
+        Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv)),
+    )?;
+    btc.begin_block_step(set_time(0), store, vec![1, 2, 3])?;
 
-        let mut building_mut = btc.checkpoints.building(store)?;
-        building_mut.fees_collected = 100_000_000;
-        let building_checkpoint_batch = &mut building_mut.batches[BatchType::Checkpoint];
-        let checkpoint_tx = building_checkpoint_batch.get_mut(0).unwrap();
-        checkpoint_tx.input.push(input);
-        let index = btc.checkpoints.index(store);
-        btc.checkpoints.set(store, index, &building_mut)?;
-        Ok(())
-    };
+    Ok(btc)
+}
 
-    let push_withdrawal = |store: &mut dyn Storage| -> ContractResult<()> {
-        let mut btc = btc.borrow_mut();
-        btc.add_withdrawal(
-            store,
-            Adapter::new(Script::new()),
-            459_459_927_000_000u128.into(),
-        )?;
+/// Creates a transaction paying `value` to the building checkpoint's
+/// signatory set for `dest`, includes it in a new block and confirms it with
+/// one more. Returns the transaction and the height of its block.
+fn mine_deposit(
+    btc: &Bitcoin,
+    store: &mut dyn Storage,
+    value: u64,
+    dest: &Dest,
+) -> ContractResult<(Transaction, u32)> {
+    mine_deposit_outputs(btc, store, &[(value, dest.clone())])
+}
 
-        let mut building_mut = btc.checkpoints.building(store)?;
-        building_mut.fees_collected = 100_000_000;
-        let index = btc.checkpoints.index(store);
-        btc.checkpoints.set(store, index, &building_mut)?;
-        Ok(())
+/// Like `mine_deposit`, but the transaction has one deposit output for each
+/// of the given values and dests.
+fn mine_deposit_outputs(
+    btc: &Bitcoin,
+    store: &mut dyn Storage,
+    outputs: &[(u64, Dest)],
+) -> ContractResult<(Transaction, u32)> {
+    let sigset = btc.checkpoints.building(store)?.sigset.clone();
+    let threshold = btc.checkpoints.config(store).sigset_threshold;
+    let btc_tx = Transaction {
+        input: vec![],
+        lock_time: bitcoin::PackedLockTime(0),
+        output: outputs
+            .iter()
+            .map(|(value, dest)| {
+                Ok(TxOut {
+                    value: *value,
+                    script_pubkey: sigset.output_script(&dest.commitment_bytes()?, threshold)?,
+                })
+            })
+            .collect::<ContractResult<_>>()?,
+        version: 1,
     };
+    let deposit_height = mine_tx(btc, store, &btc_tx)?;
 
-    let sign_batch = |api: &dyn Api, store: &mut dyn Storage, btc_height| -> ContractResult<()> {
-        let mut btc = btc.borrow_mut();
-        let cp = btc.checkpoints.signing(store)?.unwrap();
-        let sigset_index = cp.sigset.index;
-        for i in 0..2 {
-            let Some(cp) = btc.checkpoints.signing(store)? else {
-                break;
-            };
-            let to_sign = cp.to_sign(&Xpub::new(xpub[i]))?;
-            let secp2 = Secp256k1::signing_only();
-            let sigs = sign(&secp2, &xpriv[i], &to_sign)?;
-            btc.checkpoints.sign(
-                api,
-                store,
-                &Xpub::new(xpub[i]),
-                sigs,
-                sigset_index,
-                btc_height,
-            )?;
-        }
-        Ok(())
-    };
-    let sign_cp = |deps: DepsMut, btc_height| -> ContractResult<()> {
-        if btc
-            .borrow()
-            .checkpoints
-            .signing(deps.storage)
-            .unwrap()
-            .is_some()
-        {
-            sign_batch(deps.api, deps.storage, btc_height)?;
-        }
-        Ok(())
-    };
-    let maybe_step = |env: Env, store: &mut dyn Storage| -> ContractResult<()> {
-        let mut btc = btc.borrow_mut();
-        btc.begin_block_step(env, store, vec![1, 2, 3])?;
-        Ok(())
-    };
+    Ok((btc_tx, deposit_height))
+}
 
-    let env = set_time(0);
-    for i in 0..2 {
-        btc.borrow_mut().set_signatory_key(
-            deps.as_mut().storage,
-            Addr::unchecked(addr[i]),
-            Xpub::new(xpub[i]),
-        )?;
+/// Includes `btc_tx` alone in a new block and confirms it with one more.
+/// Returns the height of its block.
+fn mine_tx(btc: &Bitcoin, store: &mut dyn Storage, btc_tx: &Transaction) -> ContractResult<u32> {
+    let deposit_height = btc.headers.height(store)? + 1;
+    for (i, merkle_root) in [
+        TxMerkleNode::from_inner(btc_tx.txid().into_inner()),
+        TxMerkleNode::all_zeros(),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let header = WorkHeader::new(
+            WrappedHeader::new(
+                Adapter::new(BlockHeader {
+                    bits: 0,
+                    merkle_root,
+                    nonce: 0,
+                    prev_blockhash: BlockHash::all_zeros(),
+                    time: 0,
+                    version: 0,
+                }),
+                deposit_height + i as u32,
+            ),
+            uint::Uint256([0, 0, 0, 0]),
+        );
+        HEADERS.push_back(store, &header)?;
     }
 
-    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 0);
-    maybe_step(env, deps.as_mut().storage)?;
-    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 1);
-
-    let env = set_time(1000);
-    push_deposit(deps.as_mut().storage)?;
-    maybe_step(env, deps.as_mut().storage)?;
-    sign_cp(deps.as_mut(), 10)?;
-
-    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 2);
-
-    let env = set_time(2000);
-    push_deposit(deps.as_mut().storage)?;
-    maybe_step(env, deps.as_mut().storage)?;
-    let change_rates = btc
-        .borrow()
-        .change_rates(deps.as_mut().storage, 2000, 2100, 0)?;
-    assert_eq!(change_rates.withdrawal, 0);
-    assert_eq!(change_rates.sigset_change, 0);
-    sign_cp(deps.as_mut(), 10)?;
+    Ok(deposit_height)
+}
 
-    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 3);
+fn relay_mined_deposit(
+    btc: &mut Bitcoin,
+    store: &mut dyn Storage,
+    btc_tx: &Transaction,
+    height: u32,
+    dest: &Dest,
+) -> ContractResult<DepositOutcome> {
+    btc.relay_deposit(
+        set_time(0),
+        store,
+        Adapter::new(btc_tx.clone()),
+        height,
+        Adapter::new(PartialMerkleTree::from_txids(&[btc_tx.txid()], &[true])),
+        None,
+        0,
+        0,
+        dest.clone(),
+    )
+}
 
-    // Change the sigset
-    VALIDATORS.save(
-        deps.as_mut().storage,
-        &consensus_key2,
-        &(100, addr[1].to_string()),
-    )?;
+#[test]
+fn relay_malformed_transaction() -> ContractResult<()> {
+    // nothing is stored, so any check past the structural one would fail
+    // differently
+    let mut deps = mock_dependencies();
+    let mut try_relay = |btc_tx: Transaction| {
+        let btc_proof = PartialMerkleTree::from_txids(&[btc_tx.txid()], &[true]);
+        Bitcoin::default().relay_deposit(
+            mock_env(),
+            deps.as_mut().storage,
+            Adapter::new(btc_tx),
+            1,
+            Adapter::new(btc_proof),
+            None,
+            0,
+            0,
+            Dest::Address(Addr::unchecked("depositor")),
+        )
+    };
 
-    let env = set_time(3000);
-    push_deposit(deps.as_mut().storage)?;
-    maybe_step(env, deps.as_mut().storage)?;
-    let change_rates = btc
-        .borrow()
-        .change_rates(deps.as_mut().storage, 3000, 3100, 0)?;
-    assert_eq!(change_rates.withdrawal, 0);
-    assert_eq!(change_rates.sigset_change, 0);
-    sign_cp(deps.as_mut(), 10)?;
+    let no_outputs = Transaction {
+        input: vec![],
+        lock_time: bitcoin::PackedLockTime(0),
+        output: vec![],
+        version: 1,
+    };
+    assert_eq!(
+        try_relay(no_outputs).unwrap_err().to_string(),
+        "App Error: malformed transaction"
+    );
 
-    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 4);
+    let version_zero = Transaction {
+        input: vec![],
+        lock_time: bitcoin::PackedLockTime(0),
+        output: vec![TxOut::default()],
+        version: 0,
+    };
+    assert_eq!(
+        try_relay(version_zero).unwrap_err().to_string(),
+        "App Error: malformed transaction"
+    );
 
-    let env = set_time(4000);
-    push_deposit(deps.as_mut().storage)?;
-    maybe_step(env, deps.as_mut().storage)?;
-    let change_rates = btc
-        .borrow()
-        .change_rates(deps.as_mut().storage, 3000, 4100, 0)?;
-    assert_eq!(change_rates.withdrawal, 0);
-    assert_eq!(change_rates.sigset_change, 4090);
-    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 5);
+    Ok(())
+}
 
-    sign_cp(deps.as_mut(), 10)?;
+#[test]
+fn relay_deposit_replay() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
 
-    let env = set_time(5000);
-    push_deposit(deps.as_mut().storage)?;
-    maybe_step(env, deps.as_mut().storage)?;
-    let change_rates = btc
-        .borrow()
-        .change_rates(deps.as_mut().storage, 3000, 5100, 0)?;
-    assert_eq!(change_rates.withdrawal, 0);
-    assert_eq!(change_rates.sigset_change, 4090);
-    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 6);
-    sign_cp(deps.as_mut(), 10)?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
 
-    let env = set_time(6000);
-    push_withdrawal(deps.as_mut().storage)?;
-    maybe_step(env, deps.as_mut().storage)?;
-    let change_rates = btc
-        .borrow()
-        .change_rates(deps.as_mut().storage, 3000, 5100, 0)?;
-    assert_eq!(change_rates.withdrawal, 8664);
-    assert_eq!(change_rates.sigset_change, 4090);
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
     assert_eq!(
-        btc.borrow()
-            .checkpoints
-            .signing(deps.as_ref().storage)?
-            .unwrap()
-            .sigset
-            .index,
-        5
+        relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)
+            .unwrap_err()
+            .to_string(),
+        "App Error: Output has already been relayed",
     );
-    let change_rates = btc
-        .borrow()
-        .change_rates(deps.as_mut().storage, 3000, 5100, 5)?;
-    assert_eq!(change_rates.withdrawal, 0);
-    assert_eq!(change_rates.sigset_change, 0);
+
+    let building = btc.checkpoints.building(store)?;
+    assert_eq!(building.pending.len(), 1);
 
     Ok(())
 }
 
 #[test]
-fn test_take_pending() -> ContractResult<()> {
+fn relay_deposit_capacity_limit() -> ContractResult<()> {
     let mut deps = mock_dependencies();
-    let header_config = HeaderConfig::mainnet()?;
-    HEADER_CONFIG.save(deps.as_mut().storage, &header_config)?;
-    HEADERS.push_back(deps.as_mut().storage, &header_config.work_header())?;
+    let store = deps.as_mut().storage;
+    let bitcoin_config = BitcoinConfig {
+        capacity_limit: 100_000_000,
+        ..Default::default()
+    };
+    let mut btc = setup_deposits(store, &bitcoin_config)?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
 
-    let bitcoin_config = BitcoinConfig::default();
-    BITCOIN_CONFIG.save(deps.as_mut().storage, &bitcoin_config)?;
-    FEE_POOL.save(deps.as_mut().storage, &0)?;
-    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
-    FIRST_UNHANDLED_CONFIRMED_INDEX.save(deps.as_mut().storage, &0)?;
+    let (btc_tx, height) = mine_deposit(&btc, store, 60_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
 
-    BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+    // the first deposit's value is counted towards the limit
+    let (btc_tx, height) = mine_deposit(&btc, store, 50_000_000, &dest)?;
+    assert_eq!(
+        relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)
+            .unwrap_err()
+            .to_string(),
+        "App Error: capacity limit reached",
+    );
 
-    let consensus_key1 = [0; 32];
-    let consensus_key2 = [1; 32];
+    // only the amount left after fees are deducted is counted
+    let (btc_tx, height) = mine_deposit(&btc, store, 40_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
 
+    let building = btc.checkpoints.building(store)?;
+    assert_eq!(building.checkpoint_tx()?.input.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn relay_deposit_multiple_outputs() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let alice = Dest::Address(Addr::unchecked("alice"));
+    let bob = Dest::Address(Addr::unchecked("bob"));
+
+    let (btc_tx, height) = mine_deposit_outputs(
+        &btc,
+        store,
+        &[(10_000_000, alice.clone()), (20_000_000, bob.clone())],
+    )?;
+    let btc_proof = PartialMerkleTree::from_txids(&[btc_tx.txid()], &[true]);
+
+    // output 1 pays bob's deposit script, not alice's
+    let err = btc
+        .relay_deposit_multi(
+            set_time(0),
+            store,
+            Adapter::new(btc_tx.clone()),
+            height,
+            Adapter::new(btc_proof.clone()),
+            vec![(0, alice.clone()), (1, alice.clone())],
+            0,
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "App Error: Output script does not match signature set"
+    );
+
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let (btc_tx, height) = mine_deposit_outputs(
+        &btc,
+        store,
+        &[(10_000_000, alice.clone()), (20_000_000, bob.clone())],
+    )?;
+    let btc_proof = PartialMerkleTree::from_txids(&[btc_tx.txid()], &[true]);
+    let outcomes = btc.relay_deposit_multi(
+        set_time(0),
+        store,
+        Adapter::new(btc_tx),
+        height,
+        Adapter::new(btc_proof),
+        vec![(0, alice.clone()), (1, bob.clone())],
+        0,
+    )?;
+    assert_eq!(outcomes, vec![DepositOutcome::Credited; 2]);
+
+    let building = btc.checkpoints.building(store)?;
+    assert_eq!(building.checkpoint_tx()?.input.len(), 2);
+    assert_eq!(building.pending.len(), 2);
+    assert_eq!(building.pending[0].0, alice);
+    assert_eq!(building.pending[1].0, bob);
+    assert!(building.pending[0].1.amount < building.pending[1].1.amount);
+
+    Ok(())
+}
+
+#[test]
+fn relay_deposit_below_minimum() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    let (btc_tx, height) = mine_deposit(&btc, store, 4_000, &dest)?;
+    assert_eq!(
+        relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?,
+        DepositOutcome::TooSmall
+    );
+
+    // nothing is credited or added to the reserve
+    let building = btc.checkpoints.building(store)?;
+    assert!(building.pending.is_empty());
+    assert!(building.checkpoint_tx()?.input.is_empty());
+
+    let outpoint = OutPoint::new(btc_tx.txid(), 0).to_string();
+    assert_eq!(
+        SMALL_DEPOSITS.load(store, &outpoint)?,
+        (0, dest.clone(), 4_000)
+    );
+    let err = relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "App Error: Output has already been relayed"
+    );
+
+    let (btc_tx, height) = mine_deposit(&btc, store, 5_000, &dest)?;
+    let res = entrypoints::relay_deposit(
+        set_time(0),
+        &MockApi::default(),
+        store,
+        Adapter::new(btc_tx.clone()),
+        height,
+        Adapter::new(PartialMerkleTree::from_txids(&[btc_tx.txid()], &[true])),
+        None,
+        0,
+        0,
+        dest,
+    )?;
+    assert!(res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "deposit_ignored" && attr.value == "too_small"));
+
+    Ok(())
+}
+
+#[test]
+fn relay_deposit_block_index() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    // the deposit is mined alone in its block, at index 0
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    let mut relay = |store: &mut dyn Storage, block_index| {
+        btc.relay_deposit(
+            set_time(0),
+            store,
+            Adapter::new(btc_tx.clone()),
+            height,
+            Adapter::new(PartialMerkleTree::from_txids(&[btc_tx.txid()], &[true])),
+            Some(block_index),
+            0,
+            0,
+            dest.clone(),
+        )
+    };
+
+    let err = relay(store, 1).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Merkle proof places the transaction at block index 0, not 1"
+    );
+    assert_eq!(relay(store, 0)?, DepositOutcome::Credited);
+
+    Ok(())
+}
+
+#[test]
+fn reserve_script_for_sigset() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let reserve_script = btc.reserve_script_for_sigset(store, 0)?;
+
+    // an output paying the reserve script is not a deposit
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+    let btc_tx = Transaction {
+        input: vec![],
+        lock_time: bitcoin::PackedLockTime(0),
+        output: vec![TxOut {
+            value: 100_000_000,
+            script_pubkey: reserve_script.clone(),
+        }],
+        version: 1,
+    };
+    let height = mine_tx(&btc, store, &btc_tx)?;
+    let err = relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "App Error: Output pays the reserve script, not a deposit address"
+    );
+
+    // the checkpoint tx pays its reserve to the same script
+    setup_signing_checkpoint(&mut btc, store)?;
+    let checkpoint_tx = btc.checkpoints.get(store, 0)?.checkpoint_tx()?;
+    assert_eq!(checkpoint_tx.output[0].script_pubkey, reserve_script);
+
+    Ok(())
+}
+
+#[test]
+fn verify_deposit_proof() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    let tx = Binary::from(bitcoin::consensus::serialize(&btc_tx));
+    let proof = Binary::from(bitcoin::consensus::serialize(
+        &PartialMerkleTree::from_txids(&[btc_tx.txid()], &[true]),
+    ));
+    assert!(query_verify_deposit_proof(
+        store,
+        tx.clone(),
+        height,
+        proof.clone(),
+        0
+    )?);
+    assert!(!query_verify_deposit_proof(
+        store,
+        tx.clone(),
+        height,
+        proof.clone(),
+        1
+    )?);
+
+    let (other_tx, _) = mine_deposit(&btc, store, 1_000_000, &dest)?;
+    let other_tx = Binary::from(bitcoin::consensus::serialize(&other_tx));
+    assert!(!query_verify_deposit_proof(
+        store,
+        other_tx,
+        height,
+        proof.clone(),
+        0
+    )?);
+
+    // the block after the deposit has a different merkle root
+    let err = query_verify_deposit_proof(store, tx, height + 1, proof, 0).unwrap_err();
+    assert!(matches!(err, ContractError::BitcoinMerkleBlockError));
+
+    Ok(())
+}
+
+#[test]
+fn balance_query() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+    let (_, credited) = btc.checkpoints.building(store)?.pending[0].clone();
+    assert!(credited.amount < Uint128::from(100_000_000u128 * 1_000_000));
+
+    // the pending transfer is minted to the depositor once the checkpoint is
+    // signed
+    deps.querier
+        .update_balance("depositor", vec![credited.clone()]);
+
+    let querier = QuerierWrapper::new(&deps.querier);
+    assert_eq!(
+        query_balance(querier, &deps.storage, "depositor".to_string())?,
+        credited
+    );
+    assert_eq!(
+        query_balance(querier, &deps.storage, "stranger".to_string())?,
+        Coin {
+            denom: credited.denom,
+            amount: Uint128::zero(),
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn completed_deposit_is_minted() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+
+    // complete the checkpoint holding the deposit
+    let mut checkpoint = btc.checkpoints.building(store)?;
+    let (_, credited) = checkpoint.pending[0].clone();
+    checkpoint.status = CheckpointStatus::Complete;
+    btc.checkpoints.set(store, 0, &checkpoint)?;
+    CHECKPOINTS.push_back(store, &Checkpoint::new(checkpoint.sigset.clone())?)?;
+    BUILDING_INDEX.save(store, &1)?;
+
+    let res = entrypoints::clock_end_block(&set_time(0), store, Binary::from(vec![1, 2, 3]))?;
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token_factory".to_string(),
+            msg: to_json_binary(&tokenfactory::msg::ExecuteMsg::MintTokens {
+                denom: credited.denom,
+                amount: credited.amount,
+                mint_to_address: "depositor".to_string(),
+            })?,
+            funds: vec![],
+        })
+    );
+
+    // the transfer is only minted once
+    let res = entrypoints::clock_end_block(&set_time(0), store, Binary::from(vec![1, 2, 3]))?;
+    assert!(res.messages.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn total_supply() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let config = BitcoinConfig::default();
+    setup_deposits(&mut deps.storage, &config)?;
+    let denom = get_full_btc_denom(&deps.storage)?;
+
+    let supply = query_total_supply(QuerierWrapper::new(&deps.querier), &deps.storage)?;
+    assert_eq!(supply.denom, denom);
+    assert_eq!(supply.units, Uint128::zero());
+    assert_eq!(supply.sats, 0);
+
+    // the supply is the denom's bank supply, including nBTC minted outside
+    // the bridge's own mint paths
+    let units_per_sat = config.units_per_sat as u128;
+    deps.querier.update_balance(
+        "alice",
+        vec![Coin::new(100_000_000 * units_per_sat, &denom)],
+    );
+    deps.querier.update_balance(
+        "bob",
+        vec![
+            Coin::new(50_000_000 * units_per_sat + 1, &denom),
+            Coin::new(1_000, "uother"),
+        ],
+    );
+    let supply = query_total_supply(QuerierWrapper::new(&deps.querier), &deps.storage)?;
+    assert_eq!(supply.units, Uint128::from(150_000_000 * units_per_sat + 1));
+    assert_eq!(supply.sats, 150_000_000);
+
+    Ok(())
+}
+
+#[test]
+fn deposit_to_contract() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let hook_msg = to_json_binary(&"hook")?;
+    let dest = Dest::Contract {
+        addr: Addr::unchecked("receiver_contract"),
+        msg: hook_msg.clone(),
+    };
+
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+
+    let mut checkpoint = btc.checkpoints.building(store)?;
+    let (_, credited) = checkpoint.pending[0].clone();
+    checkpoint.status = CheckpointStatus::Complete;
+    btc.checkpoints.set(store, 0, &checkpoint)?;
+    CHECKPOINTS.push_back(store, &Checkpoint::new(checkpoint.sigset.clone())?)?;
+    BUILDING_INDEX.save(store, &1)?;
+
+    // the nBTC is minted to the bridge, then sent to the receive hook
+    let env = set_time(0);
+    let res = entrypoints::clock_end_block(&env, store, Binary::from(vec![1, 2, 3]))?;
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token_factory".to_string(),
+            msg: to_json_binary(&tokenfactory::msg::ExecuteMsg::MintTokens {
+                denom: credited.denom.clone(),
+                amount: credited.amount,
+                mint_to_address: env.contract.address.to_string(),
+            })?,
+            funds: vec![],
+        })
+    );
+    let receive = &res.messages[1];
+    assert_eq!(receive.reply_on, ReplyOn::Always);
+    assert_eq!(receive.gas_limit, Some(RECEIVE_HOOK_GAS_LIMIT));
+    assert_eq!(
+        receive.msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "receiver_contract".to_string(),
+            msg: to_json_binary(&ReceiverExecuteMsg::Receive(cw20::Cw20ReceiveMsg {
+                sender: env.contract.address.to_string(),
+                amount: credited.amount,
+                msg: hook_msg,
+            }))?,
+            funds: vec![credited.clone()],
+        })
+    );
+
+    // if the hook fails, the nBTC is credited to the contract's balance
+    let res = entrypoints::receive_reply(
+        store,
+        Reply {
+            id: receive.id,
+            result: SubMsgResult::Err("hook failed".to_string()),
+        },
+    )?;
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "receiver_contract".to_string(),
+            amount: vec![credited],
+        })
+    );
+    assert!(entrypoints::receive_reply(
+        store,
+        Reply {
+            id: receive.id,
+            result: SubMsgResult::Err("hook failed".to_string()),
+        },
+    )
+    .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn deposit_to_ibc() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let memo =
+        r#"{"forward":{"receiver":"osmo1receiver","port":"transfer","channel":"channel-1"}}"#;
+    let dest = Dest::Ibc(IbcDest {
+        source_port: "transfer".to_string(),
+        source_channel: "channel-0".to_string(),
+        receiver: "receiver".to_string(),
+        sender: "sender".to_string(),
+        timeout_timestamp: 10,
+        memo: memo.to_string(),
+    });
+
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+
+    let mut checkpoint = btc.checkpoints.building(store)?;
+    let (_, credited) = checkpoint.pending[0].clone();
+    checkpoint.status = CheckpointStatus::Complete;
+    btc.checkpoints.set(store, 0, &checkpoint)?;
+    CHECKPOINTS.push_back(store, &Checkpoint::new(checkpoint.sigset.clone())?)?;
+    BUILDING_INDEX.save(store, &1)?;
+
+    // the nBTC is minted to the bridge, then sent over ICS-20 with the memo
+    let env = set_time(0);
+    let res = entrypoints::clock_end_block(&env, store, Binary::from(vec![1, 2, 3]))?;
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token_factory".to_string(),
+            msg: to_json_binary(&tokenfactory::msg::ExecuteMsg::MintTokens {
+                denom: credited.denom.clone(),
+                amount: credited.amount,
+                mint_to_address: env.contract.address.to_string(),
+            })?,
+            funds: vec![],
+        })
+    );
+    let transfer = &res.messages[1];
+    assert_eq!(transfer.reply_on, ReplyOn::Always);
+    let CosmosMsg::Stargate { type_url, value } = &transfer.msg else {
+        panic!("expected a stargate message");
+    };
+    assert_eq!(type_url, "/ibc.applications.transfer.v1.MsgTransfer");
+    let contains = |bytes: &[u8]| value.windows(bytes.len()).any(|window| window == bytes);
+    assert!(contains(memo.as_bytes()));
+    assert!(contains(b"receiver"));
+    assert!(contains(b"channel-0"));
+    assert!(contains(credited.denom.as_bytes()));
+    assert!(contains(env.contract.address.as_bytes()));
+
+    // if the transfer can't be sent, the nBTC is credited to the sender
+    let res = entrypoints::receive_reply(
+        store,
+        Reply {
+            id: transfer.id,
+            result: SubMsgResult::Err("channel closed".to_string()),
+        },
+    )?;
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "sender".to_string(),
+            amount: vec![credited],
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn signatory_set_commitment() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let btc = setup_deposits(store, &BitcoinConfig::default())?;
+
+    let sigset = btc.checkpoints.get(store, 0)?.sigset;
+    assert_eq!(sigset.threshold, Some(SIGSET_THRESHOLD));
+    let commitment = query_signatory_set_commitment(store, 0)?;
+    assert_eq!(commitment, Binary::from(sigset.commitment().to_vec()));
+
+    // changing the configured threshold doesn't change existing commitments
+    let mut config = CHECKPOINT_CONFIG.load(store)?;
+    config.sigset_threshold = (3, 4);
+    CHECKPOINT_CONFIG.save(store, &config)?;
+    assert_eq!(query_signatory_set_commitment(store, 0)?, commitment);
+
+    Ok(())
+}
+
+#[test]
+fn deposit_status() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    let txid = btc_tx.txid().to_string();
+    assert_eq!(
+        query_deposit_status(store, txid.clone(), 0)?,
+        DepositStatus::Unseen
+    );
+
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+    assert_eq!(
+        query_deposit_status(store, txid.clone(), 0)?,
+        DepositStatus::Confirmed
+    );
+    // other outputs of the same transaction are unaffected
+    assert_eq!(
+        query_deposit_status(store, txid.clone(), 1)?,
+        DepositStatus::Unseen
+    );
+
+    // raising the confirmation requirement puts the deposit back to seen
+    let mut bitcoin_config = BITCOIN_CONFIG.load(store)?;
+    bitcoin_config.min_confirmations += 1;
+    BITCOIN_CONFIG.save(store, &bitcoin_config)?;
+    assert_eq!(
+        query_deposit_status(store, txid.clone(), 0)?,
+        DepositStatus::Seen
+    );
+    bitcoin_config.min_confirmations -= 1;
+    BITCOIN_CONFIG.save(store, &bitcoin_config)?;
+
+    let mut checkpoint = btc.checkpoints.building(store)?;
+    checkpoint.status = CheckpointStatus::Complete;
+    btc.checkpoints.set(store, 0, &checkpoint)?;
+    assert_eq!(
+        query_deposit_status(store, txid.clone(), 0)?,
+        DepositStatus::Credited
+    );
+
+    // the deposit stays credited once its checkpoint is pruned
+    CHECKPOINTS.push_back(store, &Checkpoint::new(checkpoint.sigset.clone())?)?;
+    BUILDING_INDEX.save(store, &1)?;
+    CHECKPOINTS.pop_front(store)?;
+    assert_eq!(btc.checkpoints.first_index(store)?, 1);
+    assert_eq!(
+        query_deposit_status(store, txid, 0)?,
+        DepositStatus::Credited
+    );
+
+    Ok(())
+}
+
+#[test]
+fn deposit_and_withdrawal_attributes() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let bitcoin_config = BitcoinConfig {
+        min_withdrawal_checkpoints: 1,
+        ..Default::default()
+    };
+    let btc = setup_deposits(store, &bitcoin_config)?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    // deposits to an address which couldn't be minted to are rejected
+    let invalid_dest = Dest::Address(Addr::unchecked("Not A Valid Address"));
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &invalid_dest)?;
+    let err = entrypoints::relay_deposit(
+        set_time(0),
+        &MockApi::default(),
+        store,
+        Adapter::new(btc_tx.clone()),
+        height,
+        Adapter::new(PartialMerkleTree::from_txids(&[btc_tx.txid()], &[true])),
+        None,
+        0,
+        0,
+        invalid_dest,
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Std(_)));
+
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    let res = entrypoints::relay_deposit(
+        set_time(0),
+        &MockApi::default(),
+        store,
+        Adapter::new(btc_tx.clone()),
+        height,
+        Adapter::new(PartialMerkleTree::from_txids(&[btc_tx.txid()], &[true])),
+        None,
+        0,
+        0,
+        dest,
+    )?;
+    let attr = |res: &cosmwasm_std::Response, key: &str| {
+        res.attributes
+            .iter()
+            .find(|attr| attr.key == key)
+            .map(|attr| attr.value.clone())
+    };
+    assert_eq!(attr(&res, "action").as_deref(), Some("relay_deposit"));
+    assert_eq!(attr(&res, "dest").as_deref(), Some("depositor"));
+    assert_eq!(attr(&res, "amount").as_deref(), Some("100000000"));
+    assert_eq!(attr(&res, "sigset_index").as_deref(), Some("0"));
+    assert_eq!(attr(&res, "txid"), Some(btc_tx.txid().to_string()));
+
+    let script = Builder::new()
+        .push_int(0)
+        .push_slice(&[1; 20])
+        .into_script();
+    let amount = Uint128::from(10_000_000_000_000u128);
+    let denom = get_full_btc_denom(store)?;
+    let res = entrypoints::withdraw_to_bitcoin(
+        store,
+        mock_info("withdrawer", &[Coin { denom, amount }]),
+        mock_env(),
+        Adapter::new(script.clone()),
+    )?;
+    let fee = Bitcoin::default().calc_minimum_withdrawal_fees(
+        store,
+        script.len() as u64,
+        btc.checkpoints.building(store)?.fee_rate,
+    )?;
+    assert_eq!(attr(&res, "action").as_deref(), Some("withdraw_to_bitcoin"));
+    assert_eq!(attr(&res, "script"), Some(format!("{:x}", script)));
+    assert_eq!(attr(&res, "amount"), Some(amount.to_string()));
+    assert_eq!(attr(&res, "fee"), Some(fee.0.to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn cancel_withdrawal() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let bitcoin_config = BitcoinConfig {
+        min_withdrawal_checkpoints: 1,
+        ..Default::default()
+    };
+    let mut btc = setup_deposits(store, &bitcoin_config)?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+    let fees_before = btc.checkpoints.building(store)?.fees_collected;
+
+    let script = Builder::new()
+        .push_int(0)
+        .push_slice(&[1; 20])
+        .into_script();
+    let amount = Uint128::from(10_000_000_000_000u128);
+    let denom = get_full_btc_denom(store)?;
+    for withdrawer in ["alice", "bob"] {
+        entrypoints::withdraw_to_bitcoin(
+            store,
+            mock_info(withdrawer, &[Coin::new(amount.u128(), denom.clone())]),
+            mock_env(),
+            Adapter::new(script.clone()),
+        )?;
+    }
+    assert_eq!(
+        btc.checkpoints
+            .building(store)?
+            .checkpoint_tx()?
+            .output
+            .len(),
+        2
+    );
+
+    let err = entrypoints::cancel_withdrawal(store, mock_info("bob", &[]), 0, 0).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    // the burned amount, including the miner fee, is minted back
+    let res = entrypoints::cancel_withdrawal(store, mock_info("alice", &[]), 0, 0)?;
+    assert_eq!(res.messages.len(), 1);
+    assert!(res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "amount" && attr.value == amount.to_string()));
+
+    // bob's withdrawal moves down to output 0
+    let building = btc.checkpoints.building(store)?;
+    assert_eq!(building.checkpoint_tx()?.output.len(), 1);
+    entrypoints::cancel_withdrawal(store, mock_info("bob", &[]), 0, 0)?;
+    let building = btc.checkpoints.building(store)?;
+    assert!(building.checkpoint_tx()?.output.is_empty());
+    assert_eq!(building.fees_collected, fees_before);
+
+    let err = entrypoints::cancel_withdrawal(store, mock_info("bob", &[]), 0, 0).unwrap_err();
+    assert!(matches!(err, ContractError::App(_)));
+
+    // nothing can be canceled once the checkpoint is signing
+    entrypoints::withdraw_to_bitcoin(
+        store,
+        mock_info("alice", &[Coin::new(amount.u128(), denom)]),
+        mock_env(),
+        Adapter::new(script),
+    )?;
+    let mut building = btc.checkpoints.building(store)?;
+    building.status = CheckpointStatus::Signing;
+    btc.checkpoints.set(store, 0, &building)?;
+    let err = entrypoints::cancel_withdrawal(store, mock_info("alice", &[]), 0, 0).unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+
+    Ok(())
+}
+
+#[test]
+fn withdrawal_script_validation() {
+    let p2wpkh = Builder::new()
+        .push_int(0)
+        .push_slice(&[1; 20])
+        .into_script();
+    assert!(validate_withdrawal_script(&p2wpkh).is_ok());
+
+    let p2wsh = Builder::new()
+        .push_int(0)
+        .push_slice(&[1; 32])
+        .into_script();
+    assert!(validate_withdrawal_script(&p2wsh).is_ok());
+
+    let p2tr = Builder::new()
+        .push_opcode(OP_PUSHNUM_1)
+        .push_slice(&[1; 32])
+        .into_script();
+    assert!(validate_withdrawal_script(&p2tr).is_ok());
+
+    let bare_multisig = Builder::new()
+        .push_opcode(OP_PUSHNUM_1)
+        .push_slice(&[2; 33])
+        .push_opcode(OP_PUSHNUM_1)
+        .push_opcode(OP_CHECKMULTISIG)
+        .into_script();
+    assert!(matches!(
+        validate_withdrawal_script(&bare_multisig),
+        Err(ContractError::OutputError(_))
+    ));
+}
+
+#[test]
+fn transfers_allowed() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(
+        store,
+        &BitcoinConfig {
+            transfer_fee: 1_000,
+            transfers_allowed: false,
+            transfer_exceptions: vec![Addr::unchecked("exception")],
+            ..Default::default()
+        },
+    )?;
+    let denom = get_full_btc_denom(store)?;
+
+    // senders outside the exceptions are rejected while transfers are disabled
+    let err = entrypoints::transfer(
+        &MockApi::default(),
+        store,
+        mock_info("sender", &[Coin::new(1_000_000, denom.clone())]),
+        set_time(0),
+        "recipient".to_string(),
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "Account Error: Transfers are not allowed");
+    assert!(btc.checkpoints.building(store)?.pending.is_empty());
+
+    // an exception may still transfer
+    let net = btc.transfer(
+        store,
+        &Addr::unchecked("exception"),
+        Addr::unchecked("recipient"),
+        1_000_000u128.into(),
+    )?;
+    assert_eq!(net, Uint128::from(999_000u128));
+
+    // once transfers are allowed, anyone may transfer
+    let mut config = BITCOIN_CONFIG.load(store)?;
+    config.transfers_allowed = true;
+    BITCOIN_CONFIG.save(store, &config)?;
+    entrypoints::transfer(
+        &MockApi::default(),
+        store,
+        mock_info("sender", &[Coin::new(1_000_000, denom.clone())]),
+        set_time(0),
+        "recipient".to_string(),
+    )?;
+
+    let pending = btc.checkpoints.building(store)?.pending;
+    assert_eq!(pending.len(), 2);
+    assert_eq!(
+        pending[1],
+        (
+            Dest::Address(Addr::unchecked("recipient")),
+            Coin::new(999_000, denom)
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn check_change_rates() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+
+    let header_config = HeaderConfig::mainnet()?;
+    HEADER_CONFIG.save(deps.as_mut().storage, &header_config)?;
+    HEADERS.push_back(deps.as_mut().storage, &header_config.work_header())?;
+
+    let bitcoin_config = BitcoinConfig::default();
+    BITCOIN_CONFIG.save(deps.as_mut().storage, &bitcoin_config)?;
+    FEE_POOL.save(deps.as_mut().storage, &0)?;
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+
+    BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+
+    let consensus_key1 = [0; 32];
+    let consensus_key2 = [1; 32];
+
+    let addr = ["validator1", "validator2"];
+
+    VALIDATORS.save(
+        deps.as_mut().storage,
+        &consensus_key1,
+        &(100, addr[0].to_string()),
+    )?;
+    VALIDATORS.save(
+        deps.as_mut().storage,
+        &consensus_key2,
+        &(10, addr[1].to_string()),
+    )?;
+
+    SIGNERS.save(deps.as_mut().storage, addr[0], &consensus_key1)?;
+    SIGNERS.save(deps.as_mut().storage, addr[1], &consensus_key2)?;
+
+    let btc = RefCell::new(Bitcoin::default());
+    let secp = Secp256k1::new();
+    let network = btc.borrow().network(deps.as_ref().storage)?;
+    let xpriv = vec![
+        ExtendedPrivKey::new_master(network, &[0])?,
+        ExtendedPrivKey::new_master(network, &[1])?,
+    ];
+    let xpub = vec![
+        ExtendedPubKey::from_priv(&secp, &xpriv[0]),
+        ExtendedPubKey::from_priv(&secp, &xpriv[1]),
+    ];
+
+    let push_deposit = |store: &mut dyn Storage| -> ContractResult<()> {
+        let btc = btc.borrow();
+        let sigset = &btc.checkpoints.building(store)?.sigset;
+        let input = Input::new(
+            OutPoint {
+                txid: Txid::from_slice(&[0; 32])?,
+                vout: 0,
+            },
+            sigset,
+            &[0u8],
+            100_000_000,
+            (9, 10),
+        )?;
+
+        let mut building_mut = btc.checkpoints.building(store)?;
+        building_mut.fees_collected = 100_000_000;
+        let building_checkpoint_batch = &mut building_mut.batches[BatchType::Checkpoint];
+        let checkpoint_tx = building_checkpoint_batch.get_mut(0).unwrap();
+        checkpoint_tx.input.push(input);
+        let index = btc.checkpoints.index(store);
+        btc.checkpoints.set(store, index, &building_mut)?;
+        Ok(())
+    };
+
+    let push_withdrawal = |store: &mut dyn Storage| -> ContractResult<()> {
+        let mut btc = btc.borrow_mut();
+        btc.add_withdrawal(
+            store,
+            &Addr::unchecked("withdrawer"),
+            Adapter::new(Script::new()),
+            459_459_927_000_000u128.into(),
+        )?;
+
+        let mut building_mut = btc.checkpoints.building(store)?;
+        building_mut.fees_collected = 100_000_000;
+        let index = btc.checkpoints.index(store);
+        btc.checkpoints.set(store, index, &building_mut)?;
+        Ok(())
+    };
+
+    let sign_batch = |api: &dyn Api, store: &mut dyn Storage, btc_height| -> ContractResult<()> {
+        let mut btc = btc.borrow_mut();
+        let cp = btc.checkpoints.signing(store)?.unwrap();
+        let sigset_index = cp.sigset.index;
+        for i in 0..2 {
+            let Some(cp) = btc.checkpoints.signing(store)? else {
+                break;
+            };
+            let to_sign = cp.to_sign(&Xpub::new(xpub[i]))?;
+            let secp2 = Secp256k1::signing_only();
+            let sigs = sign(&secp2, &xpriv[i], &to_sign)?;
+            btc.checkpoints.sign(
+                api,
+                store,
+                &Xpub::new(xpub[i]),
+                sigs,
+                sigset_index,
+                btc_height,
+            )?;
+        }
+        Ok(())
+    };
+    let sign_cp = |deps: DepsMut, btc_height| -> ContractResult<()> {
+        if btc
+            .borrow()
+            .checkpoints
+            .signing(deps.storage)
+            .unwrap()
+            .is_some()
+        {
+            sign_batch(deps.api, deps.storage, btc_height)?;
+        }
+        Ok(())
+    };
+    let maybe_step = |env: Env, store: &mut dyn Storage| -> ContractResult<()> {
+        let mut btc = btc.borrow_mut();
+        btc.begin_block_step(env, store, vec![1, 2, 3])?;
+        Ok(())
+    };
+
+    let env = set_time(0);
+    for i in 0..2 {
+        btc.borrow_mut().set_signatory_key(
+            deps.as_mut().storage,
+            Addr::unchecked(addr[i]),
+            Xpub::new(xpub[i]),
+        )?;
+    }
+
+    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 0);
+    maybe_step(env, deps.as_mut().storage)?;
+    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 1);
+
+    let env = set_time(1000);
+    push_deposit(deps.as_mut().storage)?;
+    maybe_step(env, deps.as_mut().storage)?;
+    sign_cp(deps.as_mut(), 10)?;
+
+    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 2);
+
+    let env = set_time(2000);
+    push_deposit(deps.as_mut().storage)?;
+    maybe_step(env, deps.as_mut().storage)?;
+    let change_rates = btc
+        .borrow()
+        .change_rates(deps.as_mut().storage, 2000, 2100, 0)?;
+    assert_eq!(change_rates.withdrawal, 0);
+    assert_eq!(change_rates.sigset_change, 0);
+    sign_cp(deps.as_mut(), 10)?;
+
+    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 3);
+
+    // Change the sigset
+    VALIDATORS.save(
+        deps.as_mut().storage,
+        &consensus_key2,
+        &(100, addr[1].to_string()),
+    )?;
+
+    let env = set_time(3000);
+    push_deposit(deps.as_mut().storage)?;
+    maybe_step(env, deps.as_mut().storage)?;
+    let change_rates = btc
+        .borrow()
+        .change_rates(deps.as_mut().storage, 3000, 3100, 0)?;
+    assert_eq!(change_rates.withdrawal, 0);
+    assert_eq!(change_rates.sigset_change, 0);
+    sign_cp(deps.as_mut(), 10)?;
+
+    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 4);
+
+    let env = set_time(4000);
+    push_deposit(deps.as_mut().storage)?;
+    maybe_step(env, deps.as_mut().storage)?;
+    let change_rates = btc
+        .borrow()
+        .change_rates(deps.as_mut().storage, 3000, 4100, 0)?;
+    assert_eq!(change_rates.withdrawal, 0);
+    assert_eq!(change_rates.sigset_change, 4090);
+    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 5);
+
+    sign_cp(deps.as_mut(), 10)?;
+
+    let env = set_time(5000);
+    push_deposit(deps.as_mut().storage)?;
+    maybe_step(env, deps.as_mut().storage)?;
+    let change_rates = btc
+        .borrow()
+        .change_rates(deps.as_mut().storage, 3000, 5100, 0)?;
+    assert_eq!(change_rates.withdrawal, 0);
+    assert_eq!(change_rates.sigset_change, 4090);
+    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 6);
+    sign_cp(deps.as_mut(), 10)?;
+
+    let env = set_time(6000);
+    push_withdrawal(deps.as_mut().storage)?;
+    maybe_step(env, deps.as_mut().storage)?;
+    let change_rates = btc
+        .borrow()
+        .change_rates(deps.as_mut().storage, 3000, 5100, 0)?;
+    assert_eq!(change_rates.withdrawal, 8664);
+    assert_eq!(change_rates.sigset_change, 4090);
+    assert_eq!(
+        query_change_rates(deps.as_ref().storage, 2100, 5100, 0)?,
+        ChangeRates {
+            withdrawal: 8664,
+            sigset_change: 4090,
+        }
+    );
+    assert_eq!(
+        btc.borrow()
+            .checkpoints
+            .signing(deps.as_ref().storage)?
+            .unwrap()
+            .sigset
+            .index,
+        5
+    );
+    let change_rates = btc
+        .borrow()
+        .change_rates(deps.as_mut().storage, 3000, 5100, 5)?;
+    assert_eq!(change_rates.withdrawal, 0);
+    assert_eq!(change_rates.sigset_change, 0);
+
+    Ok(())
+}
+
+#[test]
+fn change_rates_with_dust_reserve() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let btc = setup_deposits(store, &BitcoinConfig::default())?;
+
+    // the completed checkpoint's reserve was dropped as dust, leaving only
+    // the timestamping commitment
+    let mut completed = btc.checkpoints.building(store)?;
+    completed.status = CheckpointStatus::Complete;
+    completed.batches[BatchType::Checkpoint][0].output = vec![TxOut {
+        value: 0,
+        script_pubkey: Script::new_op_return(&[0; 32]),
+    }];
+    assert_eq!(completed.reserve_value()?, 0);
+    btc.checkpoints.set(store, 0, &completed)?;
+
+    let mut sigset = completed.sigset.clone();
+    for index in 1..=2 {
+        sigset.index = index;
+        CHECKPOINTS.push_back(store, &Checkpoint::new(sigset.clone())?)?;
+    }
+    BUILDING_INDEX.save(store, &2)?;
+    let mut signing = btc.checkpoints.get(store, 1)?;
+    signing.status = CheckpointStatus::Signing;
+    btc.checkpoints.set(store, 1, &signing)?;
+
+    let change_rates = query_change_rates(store, 0, 1000, 0)?;
+    assert_eq!(change_rates.withdrawal, 0);
+    assert_eq!(change_rates.sigset_change, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_take_pending() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let header_config = HeaderConfig::mainnet()?;
+    HEADER_CONFIG.save(deps.as_mut().storage, &header_config)?;
+    HEADERS.push_back(deps.as_mut().storage, &header_config.work_header())?;
+
+    let bitcoin_config = BitcoinConfig::default();
+    BITCOIN_CONFIG.save(deps.as_mut().storage, &bitcoin_config)?;
+    FEE_POOL.save(deps.as_mut().storage, &0)?;
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+    FIRST_UNHANDLED_CONFIRMED_INDEX.save(deps.as_mut().storage, &0)?;
+
+    BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+
+    let consensus_key1 = [0; 32];
+    let consensus_key2 = [1; 32];
+
+    let addr = ["validator1", "validator2"];
+
+    VALIDATORS.save(
+        deps.as_mut().storage,
+        &consensus_key1,
+        &(100, addr[0].to_string()),
+    )?;
+    VALIDATORS.save(
+        deps.as_mut().storage,
+        &consensus_key2,
+        &(10, addr[1].to_string()),
+    )?;
+
+    SIGNERS.save(deps.as_mut().storage, addr[0], &consensus_key1)?;
+    SIGNERS.save(deps.as_mut().storage, addr[1], &consensus_key2)?;
+
+    let btc = RefCell::new(Bitcoin::default());
+    let secp = Secp256k1::new();
+    let network = btc.borrow().network(deps.as_ref().storage)?;
+    let xpriv = vec![
+        ExtendedPrivKey::new_master(network, &[0])?,
+        ExtendedPrivKey::new_master(network, &[1])?,
+    ];
+    let xpub = vec![
+        ExtendedPubKey::from_priv(&secp, &xpriv[0]),
+        ExtendedPubKey::from_priv(&secp, &xpriv[1]),
+    ];
+
+    let push_deposit = |store: &mut dyn Storage, dest: Dest, coin: Coin| -> ContractResult<()> {
+        let fixed_amount: u64 = 100_000_000;
+        assert_eq!(coin.amount.le(&Uint128::new(fixed_amount.into())), true);
+        let input = Input::new(
+            OutPoint {
+                txid: Txid::from_slice(&[0; 32])?,
+                vout: 0,
+            },
+            &btc.borrow().checkpoints.building(store)?.sigset,
+            &[0u8],
+            fixed_amount,
+            (9, 10),
+        )?;
+        let btc = btc.borrow_mut();
+        let mut building_mut = btc.checkpoints.building(store)?;
+        building_mut.fees_collected += 100_000_000u64 - (coin.amount.u128() as u64);
+        building_mut.pending.push((dest, coin));
+        let building_checkpoint_batch = &mut building_mut.batches[BatchType::Checkpoint];
+        let checkpoint_tx = building_checkpoint_batch.get_mut(0).unwrap();
+        checkpoint_tx.input.push(input);
+        let index = btc.checkpoints.index(store);
+        btc.checkpoints.set(store, index, &building_mut)?;
+        Ok(())
+    };
+
+    let sign_batch = |api: &dyn Api, store: &mut dyn Storage, btc_height| -> ContractResult<()> {
+        let mut btc = btc.borrow_mut();
+        let queue = &mut btc.checkpoints;
+        let cp = queue.signing(store)?.unwrap();
+        let sigset_index = cp.sigset.index;
+        for i in 0..2 {
+            let Some(cp) = queue.signing(store)? else {
+                break;
+            };
+
+            let to_sign = cp.to_sign(&Xpub::new(xpub[i]))?;
+            let secp2 = Secp256k1::signing_only();
+            let sigs = sign(&secp2, &xpriv[i], &to_sign)?;
+            queue.sign(
+                api,
+                store,
+                &Xpub::new(xpub[i]),
+                sigs,
+                sigset_index,
+                btc_height,
+            )?;
+        }
+
+        Ok(())
+    };
+    let sign_cp = |deps: DepsMut, btc_height| -> ContractResult<()> {
+        if btc.borrow().checkpoints.signing(deps.storage)?.is_some() {
+            sign_batch(deps.api, deps.storage, btc_height)?;
+        }
+
+        Ok(())
+    };
+
+    let confirm_cp = |store: &mut dyn Storage, confirmed_index: u32| {
+        let btc = btc.borrow_mut();
+        let current_checkpoint_index = btc.checkpoints.index(store);
+        assert_eq!(current_checkpoint_index, confirmed_index + 1);
+        let confirmed_checkpoint_index = btc.checkpoints.last_completed_index(store).unwrap();
+        assert_eq!(confirmed_checkpoint_index, confirmed_index);
+        CONFIRMED_INDEX.save(store, &confirmed_index).unwrap();
+    };
+
+    let take_pending = |store: &mut dyn Storage| -> ContractResult<_> {
+        let mut btc = btc.borrow_mut();
+        let pending = btc.take_pending_completed(store, MAX_PENDING_CHECKPOINTS)?;
+        Ok(pending)
+    };
+
+    let maybe_step = |env: Env, store: &mut dyn Storage| -> ContractResult<()> {
+        let mut btc = btc.borrow_mut();
+
+        btc.begin_block_step(env, store, vec![1, 2, 3])?;
+
+        Ok(())
+    };
+
+    let env = set_time(0);
+    for i in 0..2 {
+        btc.borrow_mut().set_signatory_key(
+            deps.as_mut().storage,
+            Addr::unchecked(addr[i]),
+            Xpub::new(xpub[i]),
+        )?;
+    }
+
+    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 0);
+    maybe_step(env, deps.as_mut().storage)?;
+    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 1);
+    let env = set_time(1000);
+
+    let mut dest = IbcDest {
+        source_port: "transfer".to_string(),
+        source_channel: "channel-0".to_string(),
+        sender: "sender1".to_string(),
+        receiver: "receiver".to_string(),
+        timeout_timestamp: 10u64,
+        memo: "".to_string(),
+    };
+
+    // initially, there should not be any confirmed checkpoints -> return empty array for pending dests
+    assert_eq!(query_pending_deposits(deps.as_ref().storage)?.len(), 0);
+    assert_eq!(take_pending(deps.as_mut().storage)?.len(), 0);
+    // fixture: create 2 confirmed checkpoints having deposits so we can validate later
+    push_deposit(
+        deps.as_mut().storage,
+        Dest::Ibc(dest.clone()),
+        Coin {
+            denom: BTC_NATIVE_TOKEN_DENOM.to_string(),
+            amount: 95_000_000u128.into(),
+        },
+    )?;
+    dest.sender = "sender2".to_string();
+    push_deposit(
+        deps.as_mut().storage,
+        Dest::Ibc(dest.clone()),
+        Coin {
+            denom: BTC_NATIVE_TOKEN_DENOM.to_string(),
+            amount: 95_000_000u128.into(),
+        },
+    )?;
+    maybe_step(env, deps.as_mut().storage)?;
+
+    // validate current checkpoint is on signing state
+    let checkpoint_signing = btc.borrow().checkpoints.signing(deps.as_ref().storage)?;
+
+    match checkpoint_signing {
+        Some(checkpoint_data) => {
+            println!("Checkpoint Data: {:?}", checkpoint_data.fees_collected);
+            println!("{:?}", checkpoint_data.pending);
+            assert_eq!(checkpoint_data.fees_collected > 0, true);
+        }
+        None => {
+            panic!("Checkpoint not found");
+        }
+    }
+    sign_cp(deps.as_mut(), 10)?;
+    confirm_cp(deps.as_mut().storage, 0);
+
+    let env = set_time(2000);
+    push_deposit(
+        deps.as_mut().storage,
+        Dest::Ibc(dest.clone()),
+        Coin {
+            denom: BTC_NATIVE_TOKEN_DENOM.to_string(),
+            amount: 98_000_000u128.into(),
+        },
+    )?;
+    maybe_step(env, deps.as_mut().storage)?;
+    sign_cp(deps.as_mut(), 10)?;
+    confirm_cp(deps.as_mut().storage, 1);
+
+    let first_unhandled_confirmed_cp_index = FIRST_UNHANDLED_CONFIRMED_INDEX
+        .load(deps.as_ref().storage)
+        .unwrap();
+    assert_eq!(first_unhandled_confirmed_cp_index, 0);
+
+    let confirmed_index = CONFIRMED_INDEX.load(deps.as_ref().storage)?;
+    assert_eq!(confirmed_index, 1);
+    // before take pending, the confirmed checkpoints should have some pending deposits
+    assert_eq!(
+        btc.borrow()
+            .checkpoints
+            .get(deps.as_ref().storage, 0)?
+            .pending
+            .iter()
+            .count(),
+        2
+    );
+    assert_eq!(
+        btc.borrow()
+            .checkpoints
+            .get(deps.as_ref().storage, 1)?
+            .pending
+            .iter()
+            .count(),
+        1
+    );
+
+    // querying the pending deposits should not consume them
+    let pending = query_pending_deposits(deps.as_ref().storage)?;
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[0].len(), 2);
+    assert_eq!(pending[1].len(), 1);
+    assert_eq!(
+        FIRST_UNHANDLED_CONFIRMED_INDEX.load(deps.as_ref().storage)?,
+        0
+    );
+
+    // action. After take pending, the unhandled confirmed index should increase to 2 since we handled 2 confirmed checkpoints
+    let cp_dests = take_pending(deps.as_mut().storage)?;
+
+    let first_unhandled_confirmed_cp_index =
+        FIRST_UNHANDLED_CONFIRMED_INDEX.load(deps.as_ref().storage)?;
+    assert_eq!(first_unhandled_confirmed_cp_index, 2);
+    assert_eq!(cp_dests.len(), 3);
+    assert_eq!(cp_dests[0].len(), 2); // cp_dest confirmed
+    assert_eq!(cp_dests[1].len(), 1); // cp_dest confirmed
+    assert_eq!(cp_dests[2].len(), 0); // cp_dest completed
+    assert_eq!(
+        cp_dests[0][0].0,
+        Dest::Ibc(IbcDest {
+            sender: "sender1".to_string(),
+            ..dest.clone()
+        })
+    );
+    assert_eq!(cp_dests[0][0].1.amount.u128(), 95_000_000u128);
+
+    assert_eq!(
+        cp_dests[0][1].0,
+        Dest::Ibc(IbcDest {
+            sender: "sender2".to_string(),
+            ..dest.clone()
+        })
+    );
+    assert_eq!(cp_dests[0][1].1.amount.u128(), 95_000_000u128);
+
+    assert_eq!(
+        cp_dests[1][0].0,
+        Dest::Ibc(IbcDest {
+            sender: "sender2".to_string(),
+            ..dest.clone()
+        })
+    );
+    assert_eq!(cp_dests[1][0].1.amount.u128(), 98_000_000u128);
+
+    // // assert confirmed checkpoints pending. Should not have anything because we have removed them already in take_pending()
+    let checkpoints = &btc.borrow().checkpoints;
+    let first_cp = checkpoints.get(deps.as_ref().storage, 0).unwrap();
+    assert_eq!(first_cp.pending.iter().count(), 0);
+    let second_cp = checkpoints.get(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(second_cp.pending.iter().count(), 0);
+    Ok(())
+}
+
+#[test]
+fn take_pending_confirmed_cap() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = Bitcoin::default();
+
+    for index in 0..5u32 {
+        let checkpoint = Checkpoint {
+            status: CheckpointStatus::Complete,
+            pending: vec![(
+                Dest::Address(Addr::unchecked(format!("receiver{}", index))),
+                Coin::new(1000, BTC_NATIVE_TOKEN_DENOM),
+            )],
+            ..Default::default()
+        };
+        CHECKPOINTS.push_back(store, &checkpoint)?;
+    }
+    CHECKPOINTS.push_back(store, &Checkpoint::default())?;
+    BUILDING_INDEX.save(store, &5)?;
+    CONFIRMED_INDEX.save(store, &4)?;
+    FIRST_UNHANDLED_CONFIRMED_INDEX.save(store, &0)?;
+
+    assert_eq!(btc.checkpoints.unhandled_confirmed(store, 2)?, vec![0, 1]);
+    assert!(btc.checkpoints.unhandled_confirmed(store, 0)?.is_empty());
+    assert_eq!(
+        btc.checkpoints.unhandled_confirmed(store, u32::MAX)?.len(),
+        5
+    );
+
+    let pending = btc.take_pending_confirmed(store, 2)?;
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[1][0].0, Dest::Address(Addr::unchecked("receiver1")));
+    assert_eq!(FIRST_UNHANDLED_CONFIRMED_INDEX.load(store)?, 2);
+    assert_eq!(btc.checkpoints.get(store, 1)?.pending.len(), 0);
+    assert_eq!(btc.checkpoints.get(store, 2)?.pending.len(), 1);
+
+    let pending = btc.take_pending_confirmed(store, 2)?;
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[0][0].0, Dest::Address(Addr::unchecked("receiver2")));
+    assert_eq!(FIRST_UNHANDLED_CONFIRMED_INDEX.load(store)?, 4);
+
+    assert_eq!(btc.take_pending_confirmed(store, 2)?.len(), 1);
+    assert_eq!(FIRST_UNHANDLED_CONFIRMED_INDEX.load(store)?, 5);
+
+    assert_eq!(btc.take_pending_confirmed(store, 2)?.len(), 0);
+    assert_eq!(FIRST_UNHANDLED_CONFIRMED_INDEX.load(store)?, 5);
+
+    Ok(())
+}
+
+#[test]
+fn jail_offline_signer() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let header_config = HeaderConfig::mainnet()?;
+    HEADER_CONFIG.save(deps.as_mut().storage, &header_config)?;
+    HEADERS.push_back(deps.as_mut().storage, &header_config.work_header())?;
+
+    let mut bitcoin_config = BitcoinConfig::default();
+    bitcoin_config.max_offline_checkpoints = 2;
+    bitcoin_config.slash_fraction = (1, 5);
+    BITCOIN_CONFIG.save(deps.as_mut().storage, &bitcoin_config)?;
+    CONFIG.save(
+        deps.as_mut().storage,
+        &Config {
+            token_factory_addr: Addr::unchecked("token_factory"),
+            owner: Addr::unchecked("owner"),
+            bridge_wasm_addr: None,
+        },
+    )?;
+    FEE_POOL.save(deps.as_mut().storage, &0)?;
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+    BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+
+    let consensus_keys = [[0; 32], [1; 32]];
     let addr = ["validator1", "validator2"];
+    let powers = [100, 10];
+
+    let mut btc = Bitcoin::default();
+    let secp = Secp256k1::new();
+    let network = btc.network(deps.as_ref().storage)?;
+    let xpriv = vec![
+        ExtendedPrivKey::new_master(network, &[0])?,
+        ExtendedPrivKey::new_master(network, &[1])?,
+    ];
+    for i in 0..2 {
+        VALIDATORS.save(
+            deps.as_mut().storage,
+            &consensus_keys[i],
+            &(powers[i], addr[i].to_string()),
+        )?;
+        SIGNERS.save(deps.as_mut().storage, addr[i], &consensus_keys[i])?;
+        btc.set_signatory_key(
+            deps.as_mut().storage,
+            Addr::unchecked(addr[i]),
+            Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv[i])),
+        )?;
+    }
+
+    btc.begin_block_step(set_time(0), deps.as_mut().storage, vec![1, 2, 3])?;
+
+    // only the first signatory ever signs, which is enough to complete each
+    // checkpoint on its own
+    for i in 1..=3 {
+        let store = deps.as_mut().storage;
+        let mut building = btc.checkpoints.building(store)?;
+        let sigset = building.sigset.clone();
+        building.batches[BatchType::Checkpoint][0]
+            .input
+            .push(Input::new(
+                OutPoint {
+                    txid: Txid::from_slice(&[i as u8; 32])?,
+                    vout: 0,
+                },
+                &sigset,
+                &[0u8],
+                100_000_000,
+                (9, 10),
+            )?);
+        building.fees_collected = 100_000_000;
+        let index = btc.checkpoints.index(store);
+        btc.checkpoints.set(store, index, &building)?;
+
+        if i < 3 {
+            let offline = btc.begin_block_step(set_time(i * 1000), store, vec![1, 2, 3])?;
+            assert!(offline.is_empty());
+        } else {
+            // the end block jails and slashes the signer which missed the last
+            // two checkpoints
+            let res = entrypoints::clock_end_block(
+                &set_time(i * 1000),
+                store,
+                Binary::from(vec![1, 2, 3]),
+            )?;
+            let attr = |key: &str| {
+                res.attributes
+                    .iter()
+                    .find(|attr| attr.key == key)
+                    .map(|attr| attr.value.clone())
+            };
+            assert_eq!(
+                attr("jailed"),
+                Some(Binary::from(consensus_keys[1].as_slice()).to_base64())
+            );
+            assert_eq!(attr("slashed"), Some("2".to_string()));
+        }
+
+        let cp = btc.checkpoints.signing(deps.as_ref().storage)?.unwrap();
+        let sigset_index = cp.sigset.index;
+        let xpub = Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv[0]));
+        let to_sign = cp.to_sign(&xpub)?;
+        let sigs = sign(&Secp256k1::signing_only(), &xpriv[0], &to_sign)?;
+        btc.checkpoints
+            .sign(&deps.api, &mut deps.storage, &xpub, sigs, sigset_index, 10)?;
+        assert!(btc.checkpoints.signing(deps.as_ref().storage)?.is_none());
+
+        if i < 3 {
+            let liveness = query_signer_liveness(deps.as_ref().storage)?;
+            assert_eq!(liveness.len(), 2);
+            assert_eq!(liveness[0].address, Some(addr[0].to_string()));
+            assert_eq!(liveness[0].missed_checkpoints, 0);
+            assert_eq!(liveness[1].address, Some(addr[1].to_string()));
+            assert_eq!(liveness[1].missed_checkpoints, i as u32);
+            assert!(!liveness[1].jailed);
+        }
+    }
+
+    assert_eq!(JAILED.load(deps.as_ref().storage, &consensus_keys[1])?, 8);
+    assert!(!JAILED.has(deps.as_ref().storage, &consensus_keys[0]));
+    assert!(!VALIDATORS.has(deps.as_ref().storage, &consensus_keys[1]));
+
+    let liveness = query_signer_liveness(deps.as_ref().storage)?;
+    assert_eq!(liveness.len(), 2);
+    assert_eq!(liveness[1].address, None);
+    assert_eq!(liveness[1].cons_key, consensus_keys[1]);
+    assert!(liveness[1].jailed);
+
+    // a signer is only slashed once per jailing
+    let slashed = btc.punish_validator(
+        deps.as_mut().storage,
+        &consensus_keys[1],
+        addr[1].to_string(),
+    )?;
+    assert_eq!(slashed, 0);
+    assert_eq!(JAILED.load(deps.as_ref().storage, &consensus_keys[1])?, 8);
+
+    // adding a jailed validator back is skipped, and reported
+    let store = deps.as_mut().storage;
+    let res = entrypoints::add_validators(
+        store,
+        mock_info("owner", &[]),
+        vec![addr[1].to_string()],
+        vec![(10, consensus_keys[1])],
+    )?;
+    assert_eq!(
+        res.attributes[1].value,
+        Binary::from(consensus_keys[1].as_slice()).to_base64()
+    );
+    assert!(!VALIDATORS.has(store, &consensus_keys[1]));
+
+    // only the owner can unjail it, at its slashed voting power
+    let unjail = |store: &mut dyn Storage, sender: &str| {
+        entrypoints::unjail_validator(
+            &MockApi::default(),
+            store,
+            mock_info(sender, &[]),
+            addr[1].to_string(),
+            Binary::from(consensus_keys[1].as_slice()),
+        )
+    };
+    assert!(matches!(
+        unjail(store, addr[1]),
+        Err(ContractError::Unauthorized {})
+    ));
+    unjail(store, "owner")?;
+    assert!(!JAILED.has(store, &consensus_keys[1]));
+    assert_eq!(
+        VALIDATORS.load(store, &consensus_keys[1])?,
+        (8, addr[1].to_string())
+    );
+    assert_eq!(SIGNERS.load(store, addr[1])?, consensus_keys[1]);
+    assert!(unjail(store, "owner").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn bump_checkpoint_fee() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let header_config = HeaderConfig::mainnet()?;
+    HEADER_CONFIG.save(deps.as_mut().storage, &header_config)?;
+    HEADERS.push_back(deps.as_mut().storage, &header_config.work_header())?;
+
+    BITCOIN_CONFIG.save(deps.as_mut().storage, &BitcoinConfig::default())?;
+    FEE_POOL.save(deps.as_mut().storage, &0)?;
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+    BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+
+    let consensus_key = [0; 32];
+    VALIDATORS.save(
+        deps.as_mut().storage,
+        &consensus_key,
+        &(100, "validator1".to_string()),
+    )?;
+    SIGNERS.save(deps.as_mut().storage, "validator1", &consensus_key)?;
+
+    let mut btc = Bitcoin::default();
+    let secp = Secp256k1::new();
+    let xpriv = ExtendedPrivKey::new_master(btc.network(deps.as_ref().storage)?, &[0])?;
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv));
+    btc.set_signatory_key(deps.as_mut().storage, Addr::unchecked("validator1"), xpub)?;
+    btc.begin_block_step(set_time(0), deps.as_mut().storage, vec![1, 2, 3])?;
+
+    let mut building = btc.checkpoints.building(deps.as_ref().storage)?;
+    let sigset = building.sigset.clone();
+    building.batches[BatchType::Checkpoint][0]
+        .input
+        .push(Input::new(
+            OutPoint {
+                txid: Txid::from_slice(&[0; 32])?,
+                vout: 0,
+            },
+            &sigset,
+            &[0u8],
+            100_000_000,
+            (9, 10),
+        )?);
+    building.fees_collected = 100_000_000;
+    btc.checkpoints.set(deps.as_mut().storage, 0, &building)?;
+    btc.begin_block_step(set_time(1000), deps.as_mut().storage, vec![1, 2, 3])?;
+
+    let sign_cp = |btc: &mut Bitcoin, deps: DepsMut| -> ContractResult<()> {
+        let cp = btc.checkpoints.signing(deps.storage)?.unwrap();
+        let to_sign = cp.to_sign(&xpub)?;
+        let sigs = sign(&Secp256k1::signing_only(), &xpriv, &to_sign)?;
+        btc.checkpoints
+            .sign(deps.api, deps.storage, &xpub, sigs, cp.sigset.index, 10)
+    };
+    sign_cp(&mut btc, deps.as_mut())?;
+
+    let completed = btc.checkpoints.get(deps.as_ref().storage, 0)?;
+    assert_eq!(completed.status, CheckpointStatus::Complete);
+    let old_reserve = completed.reserve_output()?.unwrap().value;
+    assert!(completed
+        .checkpoint_tx()?
+        .input
+        .iter()
+        .all(|input| input.sequence == Sequence::ENABLE_RBF_NO_LOCKTIME));
+
+    // inputs stored before replaceability was signalled keep their sequence,
+    // and a checkpoint spending only those can't be bumped
+    let input = &completed.batches[BatchType::Checkpoint][0].input[0];
+    let mut legacy = serde_json::to_value(input).unwrap();
+    legacy.as_object_mut().unwrap().remove("rbf");
+    let legacy: Input = serde_json::from_value(legacy).unwrap();
+    assert!(!legacy.rbf);
+    assert_eq!(legacy.to_txin()?.sequence, Sequence::MAX);
+    let mut legacy_cp = completed.clone();
+    legacy_cp.batches[BatchType::Checkpoint][0].input = vec![legacy];
+    btc.checkpoints.set(deps.as_mut().storage, 0, &legacy_cp)?;
+    assert!(btc
+        .bump_checkpoint_fee(
+            &set_time(2000),
+            deps.as_mut().storage,
+            0,
+            completed.fee_rate * 2
+        )
+        .is_err());
+    btc.checkpoints.set(deps.as_mut().storage, 0, &completed)?;
+
+    // only the owner can bump the fee
+    CONFIG.save(
+        deps.as_mut().storage,
+        &Config {
+            token_factory_addr: Addr::unchecked("token_factory"),
+            owner: Addr::unchecked("owner"),
+            bridge_wasm_addr: None,
+        },
+    )?;
+    assert!(matches!(
+        entrypoints::bump_checkpoint_fee(
+            deps.as_mut().storage,
+            mock_info("validator1", &[]),
+            set_time(2000),
+            0,
+            completed.fee_rate * 2,
+        ),
+        Err(ContractError::Unauthorized {})
+    ));
+
+    // the fee rate can only go up, and not past the maximum
+    assert!(btc
+        .bump_checkpoint_fee(
+            &set_time(2000),
+            deps.as_mut().storage,
+            0,
+            completed.fee_rate
+        )
+        .is_err());
+    assert!(btc
+        .bump_checkpoint_fee(&set_time(2000), deps.as_mut().storage, 0, MAX_FEE_RATE + 1)
+        .is_err());
+
+    // the checkpoint started signing at time 1000, more than a signing
+    // timeout before the bump
+    let timeout = CheckpointConfig::default().signing_timeout;
+    let bumped_at = 1001 + timeout;
+    let fee_pool = FEE_POOL.load(deps.as_ref().storage)?;
+    let new_fee_rate = completed.fee_rate * 2;
+    btc.bump_checkpoint_fee(&set_time(bumped_at), deps.as_mut().storage, 0, new_fee_rate)?;
+
+    let bumped = btc.checkpoints.get(deps.as_ref().storage, 0)?;
+    assert_eq!(bumped.status, CheckpointStatus::Signing);
+    assert_eq!(bumped.fee_rate, new_fee_rate);
+    assert_eq!(bumped.signed_at_btc_height, None);
+    assert_eq!(bumped.signing_since, Some(bumped_at));
+    assert!(bumped.was_signed);
+    assert!(!bumped.signed());
+    assert!(!bumped.to_sign(&xpub)?.is_empty());
+
+    let new_reserve = bumped.reserve_output()?.unwrap().value;
+    assert!(new_reserve < old_reserve);
+    assert_eq!(
+        FEE_POOL.load(deps.as_ref().storage)?,
+        fee_pool - ((old_reserve - new_reserve) * BitcoinConfig::default().units_per_sat) as i64
+    );
+
+    // the building checkpoint now spends the replacement reserve output
+    let building = btc.checkpoints.building(deps.as_ref().storage)?;
+    let reserve_input = &building.batches[BatchType::Checkpoint][0].input[0];
+    assert_eq!(reserve_input.prevout.txid, bumped.checkpoint_tx()?.txid());
+    assert_eq!(reserve_input.amount, new_reserve);
+
+    // the bump restarted the signing timeout
+    assert_eq!(
+        btc.checkpoints
+            .signing_expired(deps.as_ref().storage, bumped_at + timeout)?,
+        None
+    );
+
+    // a replacement which times out only loses its signatures: the original
+    // transaction was signed and may have been broadcast, so its inputs and
+    // outputs are not folded back into the building checkpoint
+    let expired_at = bumped_at + timeout + 1;
+    btc.begin_block_step(set_time(expired_at), deps.as_mut().storage, vec![1, 2, 3])?;
+    assert_eq!(btc.checkpoints.index(deps.as_ref().storage), 1);
+    let replacement = btc.checkpoints.get(deps.as_ref().storage, 0)?;
+    assert_eq!(replacement.status, CheckpointStatus::Signing);
+    assert_eq!(replacement.signing_since, Some(expired_at));
+    assert_eq!(
+        replacement.checkpoint_tx()?.txid(),
+        bumped.checkpoint_tx()?.txid()
+    );
+    assert_eq!(
+        btc.checkpoints.building(deps.as_ref().storage)?.batches[BatchType::Checkpoint][0].input,
+        building.batches[BatchType::Checkpoint][0].input
+    );
+
+    sign_cp(&mut btc, deps.as_mut())?;
+    assert_eq!(
+        btc.checkpoints.get(deps.as_ref().storage, 0)?.status,
+        CheckpointStatus::Complete
+    );
+
+    Ok(())
+}
+
+/// Funds the `Building` checkpoint set up by `setup_deposits` with a reserve
+/// input and steps it into `Signing`, returning the signer's key.
+fn setup_signing_checkpoint(
+    btc: &mut Bitcoin,
+    store: &mut dyn Storage,
+) -> ContractResult<ExtendedPrivKey> {
+    let mut building = btc.checkpoints.building(store)?;
+    let sigset = building.sigset.clone();
+    building.batches[BatchType::Checkpoint][0]
+        .input
+        .push(Input::new(
+            OutPoint {
+                txid: Txid::from_slice(&[0; 32])?,
+                vout: 0,
+            },
+            &sigset,
+            &[0u8],
+            100_000_000,
+            (9, 10),
+        )?);
+    building.fees_collected = 100_000_000;
+    btc.checkpoints.set(store, 0, &building)?;
+    btc.begin_block_step(set_time(1000), store, vec![1, 2, 3])?;
+
+    Ok(ExtendedPrivKey::new_master(btc.network(store)?, &[0])?)
+}
+
+#[test]
+fn rebalance_checkpoint() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let config = CheckpointConfig {
+        rebalance_utxo_threshold: 5,
+        ..Default::default()
+    };
+    CHECKPOINT_CONFIG.save(store, &config)?;
+
+    // seeds small UTXOs which are not tied to any pending transfer
+    let seed_utxos = |btc: &mut Bitcoin,
+                      store: &mut dyn Storage,
+                      vouts: std::ops::Range<u32>|
+     -> ContractResult<()> {
+        let mut building = btc.checkpoints.building(store)?;
+        let sigset = building.sigset.clone();
+        for vout in vouts {
+            building.batches[BatchType::Checkpoint][0]
+                .input
+                .push(Input::new(
+                    OutPoint {
+                        txid: Txid::from_slice(&[1; 32])?,
+                        vout,
+                    },
+                    &sigset,
+                    &[0u8],
+                    100_000,
+                    config.sigset_threshold,
+                )?);
+        }
+        btc.checkpoints.set(store, 0, &building)?;
+        Ok(())
+    };
+
+    // at the threshold, the checkpoint keeps building until it has enough
+    // fees or the maximum interval has passed
+    seed_utxos(&mut btc, store, 0..5)?;
+    btc.begin_block_step(set_time(1), store, vec![1, 2, 3])?;
+    assert_eq!(btc.checkpoints.len(store)?, 1);
+
+    seed_utxos(&mut btc, store, 5..20)?;
+    assert!(btc.build_rebalance_checkpoint(set_time(2), store, vec![1, 2, 3])?);
+    assert_eq!(btc.checkpoints.len(store)?, 2);
+
+    let signing = btc.checkpoints.signing(store)?.unwrap();
+    let tx = signing.checkpoint_tx()?;
+    assert_eq!(tx.input.len(), 20);
+    // only the reserve and timestamping outputs
+    assert_eq!(tx.output.len(), 2);
+    assert_eq!(
+        tx.output[0].script_pubkey,
+        signing
+            .sigset
+            .output_script(&[0u8], config.sigset_threshold)?
+    );
+    assert!(tx.output[0].value > 0 && tx.output[0].value < 20 * 100_000);
+
+    // nothing left to consolidate in the new building checkpoint
+    assert!(!btc.build_rebalance_checkpoint(set_time(3), store, vec![1, 2, 3])?);
+
+    Ok(())
+}
+
+#[test]
+fn register_validator() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    setup_deposits(deps.as_mut().storage, &BitcoinConfig::default())?;
+    let consensus_key = Binary::from([2; 32]);
+
+    let err = entrypoints::register_validator(
+        &deps.api,
+        deps.as_mut().storage,
+        mock_info("stranger", &[]),
+        "operator".to_string(),
+        consensus_key.clone(),
+        50,
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    let err = entrypoints::register_validator(
+        &deps.api,
+        deps.as_mut().storage,
+        mock_info("owner", &[]),
+        "operator".to_string(),
+        Binary::from([2; 31]),
+        50,
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::App(_)));
+
+    entrypoints::register_validator(
+        &deps.api,
+        deps.as_mut().storage,
+        mock_info("owner", &[]),
+        "operator".to_string(),
+        consensus_key.clone(),
+        50,
+    )?;
+    assert_eq!(
+        VALIDATORS.load(deps.as_ref().storage, &[2; 32])?,
+        (50, "operator".to_string())
+    );
+    assert_eq!(SIGNERS.load(deps.as_ref().storage, "operator")?, [2; 32]);
+
+    // the key registered by setup_deposits, and the one just registered, can't
+    // be registered again
+    for key in [[0; 32], [2; 32]] {
+        let err = entrypoints::register_validator(
+            &deps.api,
+            deps.as_mut().storage,
+            mock_info("owner", &[]),
+            "operator2".to_string(),
+            Binary::from(key),
+            50,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "App Error: Consensus key is already registered"
+        );
+    }
+    assert_eq!(
+        VALIDATORS.load(deps.as_ref().storage, &[2; 32])?,
+        (50, "operator".to_string())
+    );
+    assert!(!SIGNERS.has(deps.as_ref().storage, "operator2"));
+
+    Ok(())
+}
+
+#[test]
+fn min_signatories() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+
+    // a second signer registers before the first checkpoint is built
+    HEADER_CONFIG.save(store, &HeaderConfig::mainnet()?)?;
+    let consensus_key = [1; 32];
+    VALIDATORS.save(store, &consensus_key, &(100, "validator2".to_string()))?;
+    SIGNERS.save(store, "validator2", &consensus_key)?;
+    let xpriv = ExtendedPrivKey::new_master(Bitcoin::default().network(store)?, &[1])?;
+    Bitcoin::default().set_signatory_key(
+        store,
+        Addr::unchecked("validator2"),
+        Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv)),
+    )?;
+
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    assert_eq!(btc.checkpoints.building(store)?.sigset.signatories.len(), 2);
+    CHECKPOINT_CONFIG.save(
+        store,
+        &CheckpointConfig {
+            min_signatories: 3,
+            ..Default::default()
+        },
+    )?;
+
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+
+    btc.begin_block_step(set_time(1000), store, vec![1, 2, 3])?;
+    assert!(btc.checkpoints.signing(store)?.is_none());
+    assert_eq!(btc.checkpoints.len(store)?, 1);
+
+    CHECKPOINT_CONFIG.save(
+        store,
+        &CheckpointConfig {
+            min_signatories: 2,
+            ..Default::default()
+        },
+    )?;
+    btc.begin_block_step(set_time(1001), store, vec![1, 2, 3])?;
+    assert!(btc.checkpoints.signing(store)?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn observed_fee_rate() -> ContractResult<()> {
+    // returns the fee rates of the advanced checkpoint and of the new
+    // building checkpoint
+    let step = |observed_fee_rate: Option<u64>| -> ContractResult<(u64, u64)> {
+        let mut deps = mock_dependencies();
+        let store = deps.as_mut().storage;
+        let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+        let dest = Dest::Address(Addr::unchecked("depositor"));
+        let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+        relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+        if let Some(fee_rate) = observed_fee_rate {
+            let err = btc
+                .observe_fee_rate(store, &Addr::unchecked("relayer"), fee_rate)
+                .unwrap_err();
+            assert!(matches!(err, ContractError::Unauthorized {}));
+            btc.observe_fee_rate(store, &Addr::unchecked("validator1"), fee_rate)?;
+        }
+
+        btc.begin_block_step(set_time(1000), store, vec![1, 2, 3])?;
+        // an observation only applies to the next checkpoint
+        assert!(OBSERVED_FEE_RATE.may_load(store)?.is_none());
+        Ok((
+            btc.checkpoints.signing(store)?.unwrap().fee_rate,
+            btc.checkpoints.building(store)?.fee_rate,
+        ))
+    };
+
+    let (prev, adjusted) = step(None)?;
+    assert_eq!(adjusted, prev);
+    let config = CheckpointConfig::default();
+    let max_step = adjust_fee_rate(adjusted, true, &config);
+    assert!(max_step > adjusted + 1);
+
+    // a lower observed fee rate leaves the adjusted rate unchanged
+    assert_eq!(step(Some(1))?.1, adjusted);
+    // a higher one raises it, by at most one adjustment step
+    assert_eq!(step(Some(adjusted + 1))?.1, adjusted + 1);
+    assert_eq!(step(Some(MAX_FEE_RATE))?.1, max_step);
+
+    Ok(())
+}
+
+#[test]
+fn next_checkpoint_eta() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let config = CheckpointConfig::default();
+
+    let eta = query_next_checkpoint_eta(store)?;
+    assert_eq!(eta.earliest, config.min_checkpoint_interval);
+    assert_eq!(eta.latest, config.max_checkpoint_interval);
+
+    // the window moves with the building checkpoint, created at time 1000
+    setup_signing_checkpoint(&mut btc, store)?;
+    let eta = query_next_checkpoint_eta(store)?;
+    assert_eq!(eta.earliest, 1000 + config.min_checkpoint_interval);
+    assert_eq!(eta.latest, 1000 + config.max_checkpoint_interval);
+
+    Ok(())
+}
+
+#[test]
+fn withdrawals_enabled() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let btc = setup_deposits(
+        store,
+        &BitcoinConfig {
+            min_withdrawal_checkpoints: 2,
+            ..Default::default()
+        },
+    )?;
+
+    let res = query_withdrawals_enabled(store)?;
+    assert!(!res.enabled);
+    assert_eq!(res.checkpoints, 1);
+    assert_eq!(res.min_withdrawal_checkpoints, 2);
+
+    let mut checkpoint = btc.checkpoints.building(store)?;
+    checkpoint.status = CheckpointStatus::Complete;
+    btc.checkpoints.set(store, 0, &checkpoint)?;
+    CHECKPOINTS.push_back(store, &Checkpoint::new(checkpoint.sigset.clone())?)?;
+    BUILDING_INDEX.save(store, &1)?;
+
+    let res = query_withdrawals_enabled(store)?;
+    assert!(res.enabled);
+    assert_eq!(res.checkpoints, 2);
+
+    Ok(())
+}
+
+#[test]
+fn max_withdrawal_amount() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let config = BitcoinConfig {
+        min_withdrawal_checkpoints: 0,
+        max_withdrawal_amount: 100_000,
+        ..Default::default()
+    };
+    let mut btc = setup_deposits(store, &config)?;
+    let withdrawer = Addr::unchecked("withdrawer");
+    let fee_rate = btc.checkpoints.building(store)?.fee_rate;
+    let fee = btc.calc_minimum_withdrawal_fees(store, 0, fee_rate)?;
+    let gross = |sats: u64| -> ContractResult<Uint128> {
+        Ok(config.sat_to_units(Sats(sats))?.checked_add(fee)?.into())
+    };
+
+    let err = btc
+        .add_withdrawal(
+            store,
+            &withdrawer,
+            Adapter::new(Script::new()),
+            gross(100_001)?,
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::App(_)));
+
+    btc.add_withdrawal(
+        store,
+        &withdrawer,
+        Adapter::new(Script::new()),
+        gross(100_000)?,
+    )?;
+    let building = btc.checkpoints.building(store)?;
+    let output = building.batches[BatchType::Checkpoint][0]
+        .output
+        .last()
+        .unwrap();
+    assert_eq!(output.value, 100_000);
+
+    Ok(())
+}
+
+#[test]
+fn building_checkpoint_query() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let config = BitcoinConfig {
+        min_withdrawal_checkpoints: 0,
+        ..Default::default()
+    };
+    let mut btc = setup_deposits(store, &config)?;
+
+    let res = query_building_checkpoint(store)?;
+    assert_eq!(res.index, 0);
+    assert!(res.inputs.is_empty());
+    assert!(res.outputs.is_empty());
+    assert_eq!(res.pending, 0);
+
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+
+    let fee_rate = btc.checkpoints.building(store)?.fee_rate;
+    let fee = btc.calc_minimum_withdrawal_fees(store, 0, fee_rate)?;
+    btc.add_withdrawal(
+        store,
+        &Addr::unchecked("withdrawer"),
+        Adapter::new(Script::new()),
+        config.sat_to_units(Sats(50_000))?.checked_add(fee)?.into(),
+    )?;
+
+    let building = btc.checkpoints.building(store)?;
+    let res = query_building_checkpoint(store)?;
+    assert_eq!(res.inputs.len(), 1);
+    assert_eq!(res.inputs[0].txid.0, btc_tx.txid());
+    assert_eq!(res.inputs[0].vout, 0);
+    assert_eq!(res.inputs[0].amount, 100_000_000);
+    assert_eq!(res.outputs.len(), 1);
+    assert_eq!(res.outputs[0].script.clone().into_inner(), Script::new());
+    assert_eq!(res.outputs[0].amount, 50_000);
+    assert_eq!(res.fees_collected, building.fees_collected);
+    assert!(res.fees_collected > 0);
+    assert_eq!(res.pending, 1);
+
+    Ok(())
+}
+
+#[test]
+fn to_sign_query() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let xpriv = ExtendedPrivKey::new_master(btc.network(store)?, &[0])?;
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv));
+
+    // nothing to sign while the checkpoint is still building
+    assert!(query_to_sign(store, HashBinary(xpub), 0)?.is_empty());
+
+    setup_signing_checkpoint(&mut btc, store)?;
+    let cp = btc.checkpoints.signing(store)?.unwrap();
+    let to_sign = query_to_sign(store, HashBinary(xpub), 0)?;
+    assert!(!to_sign.is_empty());
+    assert_eq!(to_sign, cp.to_sign(&xpub)?);
+    assert!(query_to_sign(store, HashBinary(xpub), 1)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn submit_signatures() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let mut btc = setup_deposits(deps.as_mut().storage, &BitcoinConfig::default())?;
+    let xpriv = setup_signing_checkpoint(&mut btc, deps.as_mut().storage)?;
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv));
+
+    let to_sign = query_to_sign(deps.as_ref().storage, HashBinary(xpub), 0)?;
+    let sigs: Vec<Binary> = sign(&Secp256k1::signing_only(), &xpriv, &to_sign)?
+        .into_iter()
+        .map(|sig| Binary::from(sig.0))
+        .collect();
+
+    let err = entrypoints::submit_signatures(
+        &deps.api,
+        &mut deps.storage,
+        mock_info("stranger", &[]),
+        HashBinary(xpub),
+        sigs.clone(),
+        0,
+        SignatureEncoding::Compact,
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    let err = entrypoints::submit_signatures(
+        &deps.api,
+        &mut deps.storage,
+        mock_info("validator1", &[]),
+        HashBinary(xpub),
+        vec![],
+        0,
+        SignatureEncoding::Compact,
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "Not enough signatures supplied");
+
+    let res = entrypoints::submit_signatures(
+        &deps.api,
+        &mut deps.storage,
+        mock_info("validator1", &[]),
+        HashBinary(xpub),
+        sigs,
+        0,
+        SignatureEncoding::Compact,
+    )?;
+    assert!(res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "sigset_index" && attr.value == "0"));
+    let cp = btc.checkpoints.get(deps.as_ref().storage, 0)?;
+    assert_eq!(cp.status, CheckpointStatus::Complete);
+    assert_eq!(
+        cp.signed_at_btc_height,
+        Some(btc.headers.height(deps.as_ref().storage)?)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn submit_checkpoint_signature() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let mut btc = setup_deposits(deps.as_mut().storage, &BitcoinConfig::default())?;
+    let xpriv = setup_signing_checkpoint(&mut btc, deps.as_mut().storage)?;
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv));
+
+    let to_sign = query_to_sign(deps.as_ref().storage, HashBinary(xpub), 0)?;
+    let msg = ExecuteMsg::SubmitCheckpointSignature {
+        xpub: HashBinary(xpub),
+        sigs: sign(&Secp256k1::signing_only(), &xpriv, &to_sign)?,
+        checkpoint_index: 0,
+        btc_height: 0,
+    };
+
+    // the legacy message is checked against the registered signer too
+    let err = contract::execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("stranger", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    contract::execute(deps.as_mut(), mock_env(), mock_info("validator1", &[]), msg)?;
+    let cp = btc.checkpoints.get(deps.as_ref().storage, 0)?;
+    assert_eq!(cp.status, CheckpointStatus::Complete);
+    assert_eq!(
+        cp.signed_at_btc_height,
+        Some(btc.headers.height(deps.as_ref().storage)?)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn submit_signature_encodings() -> ContractResult<()> {
+    let submit = |encoding: SignatureEncoding| -> ContractResult<CheckpointStatus> {
+        let mut deps = mock_dependencies();
+        let mut btc = setup_deposits(deps.as_mut().storage, &BitcoinConfig::default())?;
+        let xpriv = setup_signing_checkpoint(&mut btc, deps.as_mut().storage)?;
+        let xpub = Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv));
+
+        let to_sign = query_to_sign(deps.as_ref().storage, HashBinary(xpub), 0)?;
+        let sigs = sign(&Secp256k1::signing_only(), &xpriv, &to_sign)?
+            .into_iter()
+            .map(|sig| -> ContractResult<_> {
+                Ok(match encoding {
+                    SignatureEncoding::Compact => Binary::from(sig.0),
+                    SignatureEncoding::Der => Binary::from(
+                        ecdsa::Signature::from_compact(&sig.0)?
+                            .serialize_der()
+                            .to_vec(),
+                    ),
+                })
+            })
+            .collect::<ContractResult<Vec<_>>>()?;
+
+        // signatures in the other encoding are rejected
+        let other = match encoding {
+            SignatureEncoding::Compact => SignatureEncoding::Der,
+            SignatureEncoding::Der => SignatureEncoding::Compact,
+        };
+        let err = entrypoints::submit_signatures(
+            &deps.api,
+            &mut deps.storage,
+            mock_info("validator1", &[]),
+            HashBinary(xpub),
+            sigs.clone(),
+            0,
+            other,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Secp(_)));
+
+        entrypoints::submit_signatures(
+            &deps.api,
+            &mut deps.storage,
+            mock_info("validator1", &[]),
+            HashBinary(xpub),
+            sigs,
+            0,
+            encoding,
+        )?;
+        Ok(btc.checkpoints.get(deps.as_ref().storage, 0)?.status)
+    };
+
+    assert_eq!(
+        submit(SignatureEncoding::Compact)?,
+        CheckpointStatus::Complete
+    );
+    assert_eq!(submit(SignatureEncoding::Der)?, CheckpointStatus::Complete);
+
+    Ok(())
+}
+
+#[test]
+fn fee_pool_surplus() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let mut btc = setup_deposits(&mut deps.storage, &BitcoinConfig::default())?;
+    assert_eq!(query_fee_pool(&deps.storage)?, 0);
+
+    // a second signatory registers but doesn't sign
+    let secp = Secp256k1::new();
+    VALIDATORS.save(&mut deps.storage, &[1; 32], &(10, "validator2".to_string()))?;
+    SIGNERS.save(&mut deps.storage, "validator2", &[1; 32])?;
+    let xpriv2 = ExtendedPrivKey::new_master(btc.network(&deps.storage)?, &[1])?;
+    btc.set_signatory_key(
+        &mut deps.storage,
+        Addr::unchecked("validator2"),
+        Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv2)),
+    )?;
+
+    let xpriv = setup_signing_checkpoint(&mut btc, &mut deps.storage)?;
+    let cp = btc.checkpoints.signing(&deps.storage)?.unwrap();
+    let units_per_sat = BitcoinConfig::default().units_per_sat;
+    let surplus = (cp.fees_collected - cp.checkpoint_tx_miner_fees()?) * units_per_sat;
+
+    // the pool is still below its target, so a tenth of the surplus is set
+    // aside for the signers and the rest stays in the pool
+    let reward = surplus / 10;
+    assert!(reward > 0);
+    assert_eq!(query_fee_pool(&deps.storage)?, (surplus - reward) as i64);
+    assert_eq!(cp.signatory_reward, Uint128::from(reward));
+    assert!(!SIGNATORY_REWARDS.has(&deps.storage, "validator1"));
+
+    // once signed, the reward is credited to the signer only
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv));
+    let to_sign = query_to_sign(&deps.storage, HashBinary(xpub), 0)?;
+    let sigs = sign(&Secp256k1::signing_only(), &xpriv, &to_sign)?
+        .into_iter()
+        .map(|sig| Binary::from(sig.0))
+        .collect();
+    entrypoints::submit_signatures(
+        &deps.api,
+        &mut deps.storage,
+        mock_info("validator1", &[]),
+        HashBinary(xpub),
+        sigs,
+        0,
+        SignatureEncoding::Compact,
+    )?;
+    assert_eq!(
+        SIGNATORY_REWARDS.load(&deps.storage, "validator1")?,
+        Uint128::from(reward)
+    );
+    assert!(!SIGNATORY_REWARDS.has(&deps.storage, "validator2"));
+    let cp = btc.checkpoints.get(&deps.storage, 0)?;
+    assert_eq!(cp.status, CheckpointStatus::Complete);
+    assert!(cp.signatory_reward.is_zero());
+
+    // and only once
+    btc.credit_signatory_rewards(&mut deps.storage, 0)?;
+    assert_eq!(
+        SIGNATORY_REWARDS.load(&deps.storage, "validator1")?,
+        Uint128::from(reward)
+    );
+    assert_eq!(query_fee_pool(&deps.storage)?, (surplus - reward) as i64);
+
+    Ok(())
+}
+
+#[test]
+fn claim_signatory_rewards() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    setup_deposits(store, &BitcoinConfig::default())?;
+
+    // rewards accrue as the signer signs checkpoints (see `fee_pool_surplus`)
+    let rewards = Uint128::from(1_800_000_000u128);
+    SIGNATORY_REWARDS.save(store, "validator1", &rewards)?;
+
+    let err = entrypoints::claim_signatory_rewards(store, mock_info("stranger", &[])).unwrap_err();
+    assert!(matches!(err, ContractError::Signer(_)));
+
+    let res = entrypoints::claim_signatory_rewards(store, mock_info("validator1", &[]))?;
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token_factory".to_string(),
+            msg: to_json_binary(&tokenfactory::msg::ExecuteMsg::MintTokens {
+                denom: get_full_btc_denom(store)?,
+                amount: rewards,
+                mint_to_address: "validator1".to_string(),
+            })?,
+            funds: vec![],
+        })
+    );
+    assert!(!SIGNATORY_REWARDS.has(store, "validator1"));
+
+    // the balance was zeroed, so there is nothing left to claim
+    let err =
+        entrypoints::claim_signatory_rewards(store, mock_info("validator1", &[])).unwrap_err();
+    assert_eq!(err.to_string(), "App Error: No rewards to claim");
+
+    Ok(())
+}
+
+#[test]
+fn transfer_fee() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let btc = setup_deposits(
+        store,
+        &BitcoinConfig {
+            transfer_fee: 1_000,
+            ..Default::default()
+        },
+    )?;
+    let denom = get_full_btc_denom(store)?;
+    let recipient = "recipient".to_string();
+
+    let err = entrypoints::transfer(
+        &MockApi::default(),
+        store,
+        mock_info("sender", &[Coin::new(1_000_000, denom.clone())]),
+        set_time(0),
+        "Not A Valid Address".to_string(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Std(_)));
+
+    let err = entrypoints::transfer(
+        &MockApi::default(),
+        store,
+        mock_info("sender", &[Coin::new(1_000, denom.clone())]),
+        set_time(0),
+        recipient.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "App Error: Transfer amount must exceed the transfer fee"
+    );
+
+    let fee_pool = query_fee_pool(store)?;
+    let res = entrypoints::transfer(
+        &MockApi::default(),
+        store,
+        mock_info("sender", &[Coin::new(1_000_000, denom.clone())]),
+        set_time(0),
+        recipient.clone(),
+    )?;
+    assert_eq!(query_fee_pool(store)?, fee_pool + 1_000);
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token_factory".to_string(),
+            msg: to_json_binary(&tokenfactory::msg::ExecuteMsg::BurnTokens {
+                amount: 1_000_000u128.into(),
+                denom: denom.clone(),
+                burn_from_address: "cosmos2contract".to_string(),
+            })?,
+            funds: vec![],
+        })
+    );
+
+    // the recipient is minted the amount less the fee once the checkpoint
+    // completes
+    let mut checkpoint = btc.checkpoints.building(store)?;
+    checkpoint.status = CheckpointStatus::Complete;
+    btc.checkpoints.set(store, 0, &checkpoint)?;
+    CHECKPOINTS.push_back(store, &Checkpoint::new(checkpoint.sigset.clone())?)?;
+    BUILDING_INDEX.save(store, &1)?;
+
+    let res = entrypoints::clock_end_block(&set_time(0), store, Binary::from(vec![1, 2, 3]))?;
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token_factory".to_string(),
+            msg: to_json_binary(&tokenfactory::msg::ExecuteMsg::MintTokens {
+                denom,
+                amount: 999_000u128.into(),
+                mint_to_address: "recipient".to_string(),
+            })?,
+            funds: vec![],
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn deposit_then_transfer_units() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let config = BitcoinConfig {
+        transfer_fee: 1_000,
+        ..Default::default()
+    };
+    let mut btc = setup_deposits(store, &config)?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    // the deposit is credited in nBTC units, less its miner fee
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+    let (_, credited) = btc.checkpoints.building(store)?.pending[0].clone();
+    let credited = MSats::try_from(credited.amount)?;
+    let deposit_fee = config
+        .sat_to_units(Sats(100_000_000))?
+        .checked_sub(credited)?;
+    assert!(deposit_fee > MSats(0));
+    assert_eq!(
+        config.units_to_sat(credited.checked_add(deposit_fee)?)?,
+        Sats(100_000_000)
+    );
+
+    // the transfer fee is charged in the same units as the credited amount
+    let fee_pool = btc.fee_pool(store)?;
+    let net = btc.transfer(
+        store,
+        &Addr::unchecked("sender"),
+        Addr::unchecked("recipient"),
+        credited.into(),
+    )?;
+    assert_eq!(
+        MSats::try_from(net)?,
+        credited.checked_sub(MSats(config.transfer_fee))?
+    );
+    assert_eq!(btc.fee_pool(store)?, fee_pool + 1_000);
+
+    Ok(())
+}
+
+#[test]
+fn set_signatory_key() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    BITCOIN_CONFIG.save(store, &BitcoinConfig::default())?;
+    SIGNERS.save(store, "validator1", &[0; 32])?;
+    SIGNERS.save(store, "validator2", &[1; 32])?;
+
+    let secp = Secp256k1::new();
+    let network = Bitcoin::default().network(store)?;
+    let xpub = |seed: u8| -> ContractResult<HashBinary<Xpub>> {
+        let xpriv = ExtendedPrivKey::new_master(network, &[seed])?;
+        Ok(HashBinary(Xpub::new(ExtendedPubKey::from_priv(
+            &secp, &xpriv,
+        ))))
+    };
+
+    let err =
+        entrypoints::set_signatory_key(store, mock_info("stranger", &[]), xpub(0)?).unwrap_err();
+    assert!(matches!(err, ContractError::Signer(_)));
+
+    entrypoints::set_signatory_key(store, mock_info("validator1", &[]), xpub(0)?)?;
+    assert_eq!(SIG_KEYS.load(store, &[0; 32])?, xpub(0)?.0);
+
+    // another signer can't claim the same key
+    let err =
+        entrypoints::set_signatory_key(store, mock_info("validator2", &[]), xpub(0)?).unwrap_err();
+    assert_eq!(err.to_string(), "Duplicate signatory key");
+    assert!(!SIG_KEYS.has(store, &[1; 32]));
+
+    Ok(())
+}
+
+#[test]
+fn step_without_signatories() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let header_config = HeaderConfig::mainnet()?;
+    HEADER_CONFIG.save(store, &header_config)?;
+    HEADERS.push_back(store, &header_config.work_header())?;
+    BITCOIN_CONFIG.save(store, &BitcoinConfig::default())?;
+    FEE_POOL.save(store, &0)?;
+    CHECKPOINT_CONFIG.save(store, &CheckpointConfig::default())?;
+    BUILDING_INDEX.save(store, &0)?;
+    VALIDATORS.save(store, &[0; 32], &(100, "validator1".to_string()))?;
+    SIGNERS.save(store, "validator1", &[0; 32])?;
+    CONFIG.save(
+        store,
+        &Config {
+            token_factory_addr: Addr::unchecked("token_factory"),
+            owner: Addr::unchecked("owner"),
+            bridge_wasm_addr: None,
+        },
+    )?;
+
+    let mut btc = Bitcoin::default();
+    let err = btc
+        .begin_block_step(set_time(0), store, vec![1, 2, 3])
+        .unwrap_err();
+    assert!(matches!(err, ContractError::EmptySignatorySet));
+    assert_eq!(btc.checkpoints.len(store)?, 0);
+
+    // the end blocker skips checkpointing instead of failing
+    entrypoints::clock_end_block(&set_time(0), store, Binary::from(vec![1, 2, 3]))?;
+    assert_eq!(btc.checkpoints.len(store)?, 0);
+
+    // the first checkpoint is built once a signatory has registered
+    let secp = Secp256k1::new();
+    let xpriv = ExtendedPrivKey::new_master(btc.network(store)?, &[0])?;
+    btc.set_signatory_key(
+        store,
+        Addr::unchecked("validator1"),
+        Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv)),
+    )?;
+    btc.begin_block_step(set_time(0), store, vec![1, 2, 3])?;
+    assert_eq!(btc.checkpoints.len(store)?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn address_script_round_trip() -> ContractResult<()> {
+    let deps = mock_dependencies();
+    let store = deps.as_ref().storage;
+    let address = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string();
+    let script = query_script_for_address(store, address.clone())?;
+    assert_eq!(
+        script,
+        Binary::from(Vec::<u8>::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap())
+    );
+    assert_eq!(query_address_for_script(store, script)?, address);
+
+    let err = query_script_for_address(store, "bc1qnotanaddress".to_string()).unwrap_err();
+    assert!(matches!(err, ContractError::BitcoinAddress(_)));
+
+    // testnet addresses are rejected on mainnet
+    let err = query_script_for_address(
+        store,
+        "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx".to_string(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Address(_)));
+
+    let err = query_address_for_script(store, Binary::from(vec![0x6a])).unwrap_err();
+    assert!(matches!(err, ContractError::Address(_)));
+
+    Ok(())
+}
+
+#[test]
+fn regtest_deposit_address() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+
+    let header_config = HeaderConfig::from_bytes_for_network(
+        include_bytes!("../checkpoint.json"),
+        bitcoin::Network::Regtest,
+    )?;
+    assert!(!header_config.retargeting);
+    assert_eq!(header_config.max_target, 0x207fffff);
+    HEADER_CONFIG.save(store, &header_config)?;
+    assert_eq!(btc.network(store)?, bitcoin::Network::Regtest);
+
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+    let res = query_deposit_address(store, dest)?;
+    assert!(res.address.starts_with("bcrt1"));
+
+    // signatory keys must now be for regtest
+    let secp = Secp256k1::new();
+    let mainnet_xpriv = ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &[0])?;
+    let err = btc
+        .set_signatory_key(
+            store,
+            Addr::unchecked("validator1"),
+            Xpub::new(ExtendedPubKey::from_priv(&secp, &mainnet_xpriv)),
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::App(_)));
+
+    let regtest_xpriv = ExtendedPrivKey::new_master(bitcoin::Network::Regtest, &[0])?;
+    btc.set_signatory_key(
+        store,
+        Addr::unchecked("validator1"),
+        Xpub::new(ExtendedPubKey::from_priv(&secp, &regtest_xpriv)),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn set_recovery_script() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let btc = Bitcoin::default();
+    let user = Addr::unchecked("user");
+    assert_eq!(btc.recovery_script(store, &user)?, None);
+
+    let script = Vec::<u8>::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+    entrypoints::set_recovery_script(store, mock_info("user", &[]), Binary::from(script.clone()))?;
+    assert_eq!(
+        btc.recovery_script(store, &user)?,
+        Some(Script::from(script))
+    );
+
+    // non-standard scripts are rejected and the registration is unchanged
+    let err =
+        entrypoints::set_recovery_script(store, mock_info("user", &[]), Binary::from(vec![0x6a]))
+            .unwrap_err();
+    assert!(matches!(err, ContractError::BitcoinRecoveryScript(_)));
+    assert!(btc.recovery_script(store, &user)?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn units_per_sat_conversion() -> ContractResult<()> {
+    let config = BitcoinConfig::default();
+    let sats = Sats(21_000_000 * 100_000_000);
+    let units = config.sat_to_units(sats)?;
+    assert_eq!(units, MSats(sats.0 * config.units_per_sat));
+    assert_eq!(config.units_to_sat(units)?, sats);
+    // partial satoshis round down
+    assert_eq!(
+        config.units_to_sat(units.checked_add(MSats(config.units_per_sat - 1))?)?,
+        sats
+    );
+
+    let max_sats = u64::MAX / config.units_per_sat;
+    assert_eq!(
+        config.sat_to_units(Sats(max_sats))?,
+        MSats(max_sats * config.units_per_sat)
+    );
+    let err = config.sat_to_units(Sats(max_sats + 1)).unwrap_err();
+    assert!(matches!(err, ContractError::Overflow(_)));
+    let err = MSats(u64::MAX).checked_add(MSats(1)).unwrap_err();
+    assert!(matches!(err, ContractError::Overflow(_)));
+
+    let config = BitcoinConfig {
+        units_per_sat: 0,
+        ..Default::default()
+    };
+    assert!(matches!(config.validate(), Err(ContractError::App(_))));
+    assert!(config.units_to_sat(MSats(1)).is_err());
+
+    for slash_fraction in [(1, 0), (2, 1)] {
+        let config = BitcoinConfig {
+            slash_fraction,
+            ..Default::default()
+        };
+        assert!(matches!(config.validate(), Err(ContractError::App(_))));
+    }
+
+    // configs stored before `slash_fraction` was added load with the default
+    let mut config = serde_json::to_value(BitcoinConfig::default()).unwrap();
+    config.as_object_mut().unwrap().remove("slash_fraction");
+    let config: BitcoinConfig = serde_json::from_value(config).unwrap();
+    assert_eq!(config.slash_fraction, SLASH_FRACTION);
+
+    Ok(())
+}
+
+#[test]
+fn current_fee_rate() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    setup_deposits(store, &BitcoinConfig::default())?;
+
+    let config = CheckpointConfig::default();
+    let res = query_current_fee_rate(store)?;
+    assert_eq!(res.fee_rate, DEFAULT_FEE_RATE);
+    assert_eq!(
+        res.target_checkpoint_inclusion,
+        config.target_checkpoint_inclusion
+    );
+    assert_eq!(res.min_fee_rate, config.min_fee_rate);
+    assert_eq!(res.max_fee_rate, config.max_fee_rate);
+
+    Ok(())
+}
+
+#[test]
+fn signing_progress() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let header_config = HeaderConfig::mainnet()?;
+    HEADER_CONFIG.save(deps.as_mut().storage, &header_config)?;
+    HEADERS.push_back(deps.as_mut().storage, &header_config.work_header())?;
+    BITCOIN_CONFIG.save(deps.as_mut().storage, &BitcoinConfig::default())?;
+    FEE_POOL.save(deps.as_mut().storage, &0)?;
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+    BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+
+    let mut btc = Bitcoin::default();
+    let secp = Secp256k1::new();
+    let mut xpubs = vec![];
+    let mut xprivs = vec![];
+    for i in 0..2u8 {
+        let consensus_key = [i; 32];
+        let addr = format!("validator{}", i + 1);
+        VALIDATORS.save(deps.as_mut().storage, &consensus_key, &(100, addr.clone()))?;
+        SIGNERS.save(deps.as_mut().storage, &addr, &consensus_key)?;
+        let xpriv = ExtendedPrivKey::new_master(btc.network(deps.as_ref().storage)?, &[i])?;
+        let xpub = Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv));
+        btc.set_signatory_key(deps.as_mut().storage, Addr::unchecked(addr), xpub)?;
+        xprivs.push(xpriv);
+        xpubs.push(xpub);
+    }
+    btc.begin_block_step(set_time(0), deps.as_mut().storage, vec![1, 2, 3])?;
+    setup_signing_checkpoint(&mut btc, deps.as_mut().storage)?;
+
+    let progress = query_signing_progress(deps.as_ref().storage, 0)?;
+    assert_eq!(progress.signed_voting_power, 0);
+    assert_eq!(progress.threshold_voting_power, 133);
+    assert_eq!(progress.total_inputs, 1);
+    assert!(!progress.threshold_met);
+
+    for i in 0..2 {
+        let cp = btc.checkpoints.signing(deps.as_ref().storage)?.unwrap();
+        let to_sign = cp.to_sign(&xpubs[i])?;
+        let sigs = sign(&Secp256k1::signing_only(), &xprivs[i], &to_sign)?;
+        btc.checkpoints
+            .sign(&deps.api, &mut deps.storage, &xpubs[i], sigs, 0, 10)?;
+
+        let progress = query_signing_progress(deps.as_ref().storage, 0)?;
+        assert_eq!(progress.signed_voting_power, 100 * (i as u64 + 1));
+        assert_eq!(progress.threshold_met, i == 1);
+        assert_eq!(progress.signed_inputs, i as u32);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn checkpoint_txid() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let mut btc = setup_deposits(deps.as_mut().storage, &BitcoinConfig::default())?;
+    let xpriv = setup_signing_checkpoint(&mut btc, deps.as_mut().storage)?;
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv));
+
+    // the txid is not available until the checkpoint is fully signed
+    let err = query_checkpoint_txid(deps.as_ref().storage, 0).unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+
+    let cp = btc.checkpoints.signing(deps.as_ref().storage)?.unwrap();
+    let sigs = sign(&Secp256k1::signing_only(), &xpriv, &cp.to_sign(&xpub)?)?;
+    btc.checkpoints
+        .sign(&deps.api, &mut deps.storage, &xpub, sigs, 0, 10)?;
+
+    let txid = query_checkpoint_txid(deps.as_ref().storage, 0)?;
+    let cp = btc.checkpoints.get(deps.as_ref().storage, 0)?;
+    assert_eq!(txid.0, cp.checkpoint_tx()?.txid());
+    assert_eq!(query_checkpoint_txid(deps.as_ref().storage, 0)?.0, txid.0);
+
+    Ok(())
+}
+
+#[test]
+fn sign_rejects_invalid_signature() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let mut btc = setup_deposits(deps.as_mut().storage, &BitcoinConfig::default())?;
+    let xpriv = setup_signing_checkpoint(&mut btc, deps.as_mut().storage)?;
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv));
+    let cp = btc.checkpoints.signing(deps.as_ref().storage)?.unwrap();
+    let to_sign = cp.to_sign(&xpub)?;
+
+    // a well-formed signature over the wrong sighash
+    let wrong_msg: Vec<_> = to_sign.iter().map(|(_, index)| ([1; 32], *index)).collect();
+    let sigs = sign(&Secp256k1::signing_only(), &xpriv, &wrong_msg)?;
+    let err = btc
+        .checkpoints
+        .sign(&deps.api, &mut deps.storage, &xpub, sigs, 0, 10)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Secp(_)));
+
+    // malformed signature bytes
+    let sigs = vec![threshold_sig::Signature(vec![0xff; 10]); to_sign.len()];
+    let err = btc
+        .checkpoints
+        .sign(&deps.api, &mut deps.storage, &xpub, sigs, 0, 10)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Secp(_)));
+
+    let cp = btc.checkpoints.get(deps.as_ref().storage, 0)?;
+    assert_eq!(cp.status, CheckpointStatus::Signing);
+    assert!(!cp.signed());
+
+    // the valid signatures are still accepted
+    let sigs = sign(&Secp256k1::signing_only(), &xpriv, &to_sign)?;
+    btc.checkpoints
+        .sign(&deps.api, &mut deps.storage, &xpub, sigs, 0, 10)?;
+    let cp = btc.checkpoints.get(deps.as_ref().storage, 0)?;
+    assert_eq!(cp.status, CheckpointStatus::Complete);
+
+    Ok(())
+}
+
+#[test]
+fn checkpoint_tx_hex() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let mut btc = setup_deposits(deps.as_mut().storage, &BitcoinConfig::default())?;
+    let xpriv = setup_signing_checkpoint(&mut btc, deps.as_mut().storage)?;
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv));
+
+    let err = query_checkpoint_tx_hex(deps.as_ref().storage, 0).unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+
+    let cp = btc.checkpoints.signing(deps.as_ref().storage)?.unwrap();
+    let sigs = sign(&Secp256k1::signing_only(), &xpriv, &cp.to_sign(&xpub)?)?;
+    btc.checkpoints
+        .sign(&deps.api, &mut deps.storage, &xpub, sigs, 0, 10)?;
+
+    let tx_hex = query_checkpoint_tx_hex(deps.as_ref().storage, 0)?;
+    assert_eq!(tx_hex, tx_hex.to_lowercase());
+    let tx: Transaction =
+        bitcoin::consensus::encode::deserialize(&Vec::<u8>::from_hex(&tx_hex).unwrap())?;
+    let cp = btc.checkpoints.get(deps.as_ref().storage, 0)?;
+    assert_eq!(tx, *cp.checkpoint_tx()?);
+    assert!(tx.input.iter().all(|input| !input.witness.is_empty()));
+
+    Ok(())
+}
+
+#[test]
+fn reserve_value() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let mut btc = setup_deposits(deps.as_mut().storage, &BitcoinConfig::default())?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    for value in [100_000_000, 50_000_000] {
+        let (btc_tx, height) = mine_deposit(&btc, deps.as_mut().storage, value, &dest)?;
+        relay_mined_deposit(&mut btc, deps.as_mut().storage, &btc_tx, height, &dest)?;
+    }
+    assert_eq!(query_reserve_value(deps.as_ref().storage)?, 0);
+
+    btc.begin_block_step(set_time(1000), deps.as_mut().storage, vec![1, 2, 3])?;
+    let xpriv = ExtendedPrivKey::new_master(btc.network(deps.as_ref().storage)?, &[0])?;
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv));
+    let cp = btc.checkpoints.signing(deps.as_ref().storage)?.unwrap();
+    let sigs = sign(&Secp256k1::signing_only(), &xpriv, &cp.to_sign(&xpub)?)?;
+    btc.checkpoints
+        .sign(&deps.api, &mut deps.storage, &xpub, sigs, 0, 10)?;
+
+    // the reserve only counts once the checkpoint is confirmed on Bitcoin
+    assert_eq!(query_reserve_value(deps.as_ref().storage)?, 0);
+    CONFIRMED_INDEX.save(deps.as_mut().storage, &0)?;
+
+    let cp = btc.checkpoints.get(deps.as_ref().storage, 0)?;
+    assert_eq!(
+        query_reserve_value(deps.as_ref().storage)?,
+        150_000_000 - cp.checkpoint_tx_miner_fees()?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn reserve_utxos() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let mut btc = setup_deposits(deps.as_mut().storage, &BitcoinConfig::default())?;
+    CHECKPOINT_CONFIG.save(
+        deps.as_mut().storage,
+        &CheckpointConfig {
+            reserve_output_count: 2,
+            ..Default::default()
+        },
+    )?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    for value in [100_000_000, 50_000_000] {
+        let (btc_tx, height) = mine_deposit(&btc, deps.as_mut().storage, value, &dest)?;
+        relay_mined_deposit(&mut btc, deps.as_mut().storage, &btc_tx, height, &dest)?;
+    }
+    btc.begin_block_step(set_time(1000), deps.as_mut().storage, vec![1, 2, 3])?;
+    let xpriv = ExtendedPrivKey::new_master(btc.network(deps.as_ref().storage)?, &[0])?;
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv));
+    let cp = btc.checkpoints.signing(deps.as_ref().storage)?.unwrap();
+    let sigs = sign(&Secp256k1::signing_only(), &xpriv, &cp.to_sign(&xpub)?)?;
+    btc.checkpoints
+        .sign(&deps.api, &mut deps.storage, &xpub, sigs, 0, 10)?;
+
+    // only outputs of a confirmed checkpoint are reserve UTXOs
+    assert!(query_reserve_utxos(deps.as_ref().storage, None, 10)?.is_empty());
+    CONFIRMED_INDEX.save(deps.as_mut().storage, &0)?;
+
+    let cp = btc.checkpoints.get(deps.as_ref().storage, 0)?;
+    let txid = cp.txid()?;
+    let utxos = query_reserve_utxos(deps.as_ref().storage, None, 10)?;
+    assert_eq!(utxos.len(), 2);
+    for (vout, (utxo, output)) in utxos.iter().zip(cp.reserve_outputs()?).enumerate() {
+        assert_eq!(utxo.txid, HashBinary(txid));
+        assert_eq!(utxo.vout, vout as u32);
+        assert_eq!(utxo.amount, output.value);
+        assert_eq!(utxo.sigset_index, cp.sigset.index);
+    }
+
+    // pages continue after the given outpoint
+    let page = query_reserve_utxos(deps.as_ref().storage, None, 1)?;
+    assert_eq!(page, utxos[..1]);
+    let page = query_reserve_utxos(deps.as_ref().storage, Some(format!("{}:0", txid)), 10)?;
+    assert_eq!(page, utxos[1..]);
+
+    Ok(())
+}
+
+#[test]
+fn max_sigset_change() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let header_config = HeaderConfig::mainnet()?;
+    HEADER_CONFIG.save(store, &header_config)?;
+    HEADERS.push_back(store, &header_config.work_header())?;
+    BITCOIN_CONFIG.save(store, &BitcoinConfig::default())?;
+    FEE_POOL.save(store, &0)?;
+    CHECKPOINT_CONFIG.save(
+        store,
+        &CheckpointConfig {
+            max_sigset_change: 1_000,
+            ..Default::default()
+        },
+    )?;
+    BUILDING_INDEX.save(store, &0)?;
+
+    let mut btc = Bitcoin::default();
+    let secp = Secp256k1::new();
+    for i in 0..2u8 {
+        let consensus_key = [i; 32];
+        let addr = format!("validator{}", i + 1);
+        VALIDATORS.save(store, &consensus_key, &(100, addr.clone()))?;
+        SIGNERS.save(store, &addr, &consensus_key)?;
+        let xpriv = ExtendedPrivKey::new_master(btc.network(store)?, &[i])?;
+        let xpub = Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv));
+        btc.set_signatory_key(store, Addr::unchecked(addr), xpub)?;
+    }
+    btc.begin_block_step(set_time(0), store, vec![1, 2, 3])?;
+    let initial_sigset = btc.checkpoints.building(store)?.sigset.clone();
+
+    // the second validator's power spikes, which would move ~41% of the
+    // voting power to them in one checkpoint
+    VALIDATORS.save(store, &[1; 32], &(1_000, "validator2".to_string()))?;
+    let spiked_sigset = signatory::SignatorySet::from_validator_ctx(store, 1000, 1)?;
+    assert!(spiked_sigset.change_since(store, &initial_sigset)? > 1_000);
+
+    setup_signing_checkpoint(&mut btc, store)?;
+    assert_eq!(btc.checkpoints.index(store), 1);
+    assert_eq!(
+        btc.checkpoints.signing(store)?.unwrap().sigset,
+        initial_sigset
+    );
+    // the new checkpoint gets a fresh signatory set which only moves 10% of
+    // the voting power towards the spiked one
+    let sigset = btc.checkpoints.building(store)?.sigset.clone();
+    assert_eq!(sigset.index(), 1);
+    assert_eq!(sigset.create_time(), 1000);
+    assert_ne!(
+        sigset.signatories[0].pubkey,
+        initial_sigset.signatories[0].pubkey
+    );
+    let powers: Vec<_> = sigset.iter().map(|s| s.voting_power).collect();
+    assert_eq!(powers, vec![660, 440]);
+    assert!(sigset.change_since(store, &initial_sigset)? <= 1_000);
+
+    Ok(())
+}
+
+#[test]
+fn derive_pubkeys() -> ContractResult<()> {
+    let secp = Secp256k1::new();
+    let xpubs: Vec<_> = (0..3u8)
+        .map(|i| {
+            let xpriv = ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &[i]).unwrap();
+            HashBinary(Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv)))
+        })
+        .collect();
+
+    let pubkeys = query_derive_pubkeys(xpubs.clone(), 5)?;
+    assert_eq!(pubkeys.len(), xpubs.len());
+    for (xpub, pubkey) in xpubs.into_iter().zip(pubkeys) {
+        assert_eq!(pubkey.len(), 33);
+        assert_eq!(pubkey, query_derive_pubkey(xpub, 5)?);
+    }
+    assert!(query_derive_pubkeys(vec![], 5)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn relay_deposit_confirmation_tiers() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let bitcoin_config = BitcoinConfig {
+        confirmation_tiers: vec![(10_000_000, 1), (50_000_000, 2)],
+        ..Default::default()
+    };
+    let mut btc = setup_deposits(store, &bitcoin_config)?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+    assert_eq!(bitcoin_config.deposit_confirmations(10_000_000, &dest), 1);
+    assert_eq!(bitcoin_config.deposit_confirmations(10_000_001, &dest), 2);
+    assert_eq!(bitcoin_config.deposit_confirmations(100_000_000, &dest), 3);
+    assert_eq!(bitcoin_config.amount_confirmations(100_000_000), 3);
 
-    VALIDATORS.save(
+    // both deposits have one confirmation, which is enough for the small one
+    let (small_tx, small_height) = mine_deposit(&btc, store, 5_000_000, &dest)?;
+    let (large_tx, large_height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &small_tx, small_height, &dest)?;
+    let err = relay_mined_deposit(&mut btc, store, &large_tx, large_height, &dest).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "App Error: Block is not sufficiently confirmed for the deposit amount"
+    );
+
+    // two more blocks give the large deposit the confirmations it needs
+    mine_tx(
+        &btc,
+        store,
+        &Transaction {
+            version: 1,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![],
+            output: vec![],
+        },
+    )?;
+    relay_mined_deposit(&mut btc, store, &large_tx, large_height, &dest)?;
+    assert_eq!(btc.checkpoints.building(store)?.pending.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn relay_deposit_ibc_confirmations() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let bitcoin_config = BitcoinConfig {
+        ibc_extra_confirmations: 2,
+        ..Default::default()
+    };
+    let mut btc = setup_deposits(store, &bitcoin_config)?;
+    IBC_CHANNEL_ALLOWLIST.save(store, &vec!["channel-0".to_string()])?;
+    let address_dest = Dest::Address(Addr::unchecked("depositor"));
+    let ibc_dest = Dest::Ibc(IbcDest {
+        source_port: "transfer".to_string(),
+        source_channel: "channel-0".to_string(),
+        receiver: "receiver".to_string(),
+        sender: "sender".to_string(),
+        timeout_timestamp: 10,
+        memo: "".to_string(),
+    });
+    assert_eq!(
+        bitcoin_config.deposit_confirmations(100_000_000, &ibc_dest),
+        bitcoin_config.deposit_confirmations(100_000_000, &address_dest) + 2
+    );
+
+    // both deposits have one confirmation, which is enough for the address
+    let (address_tx, address_height) = mine_deposit(&btc, store, 100_000_000, &address_dest)?;
+    let (ibc_tx, ibc_height) = mine_deposit(&btc, store, 100_000_000, &ibc_dest)?;
+    relay_mined_deposit(&mut btc, store, &address_tx, address_height, &address_dest)?;
+    let err = relay_mined_deposit(&mut btc, store, &ibc_tx, ibc_height, &ibc_dest).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "App Error: Block is not sufficiently confirmed for an IBC deposit"
+    );
+
+    // two more blocks give the IBC deposit the confirmations it needs
+    mine_tx(
+        &btc,
+        store,
+        &Transaction {
+            version: 1,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![],
+            output: vec![],
+        },
+    )?;
+    relay_mined_deposit(&mut btc, store, &ibc_tx, ibc_height, &ibc_dest)?;
+    assert_eq!(btc.checkpoints.building(store)?.pending.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn relay_deposit_max_age() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let max_deposit_block_age = BitcoinConfig::default().max_deposit_block_age;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    // mines an empty block at the given time on top of the current tip
+    let push_header = |store: &mut dyn Storage, time: u64| -> ContractResult<()> {
+        let height = Bitcoin::default().headers.height(store)? + 1;
+        let header = WorkHeader::new(
+            WrappedHeader::new(
+                Adapter::new(BlockHeader {
+                    bits: 0,
+                    merkle_root: TxMerkleNode::all_zeros(),
+                    nonce: 0,
+                    prev_blockhash: BlockHash::all_zeros(),
+                    time: time as u32,
+                    version: 0,
+                }),
+                height,
+            ),
+            uint::Uint256([0, 0, 0, 0]),
+        );
+        HEADERS.push_back(store, &header)
+    };
+
+    // the deposit's block was mined just over `max_deposit_block_age` before
+    // the tip
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    push_header(store, max_deposit_block_age + 1)?;
+    assert_eq!(
+        relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)
+            .unwrap_err()
+            .to_string(),
+        "App Error: deposit too old",
+    );
+
+    // a deposit exactly `max_deposit_block_age` old is still accepted
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    push_header(store, max_deposit_block_age)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+    assert_eq!(btc.checkpoints.building(store)?.pending.len(), 1);
+
+    // an old deposit to a signatory set which has expired is still recovered
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    let now = 2 * BitcoinConfig::default()
+        .max_deposit_age
+        .max(max_deposit_block_age);
+    for _ in 0..11 {
+        push_header(store, now)?;
+    }
+    let outcome = btc.relay_deposit(
+        set_time(now),
+        store,
+        Adapter::new(btc_tx.clone()),
+        height,
+        Adapter::new(PartialMerkleTree::from_txids(&[btc_tx.txid()], &[true])),
+        None,
+        0,
+        0,
+        dest.clone(),
+    )?;
+    assert_eq!(outcome, DepositOutcome::Expired);
+    assert_eq!(RECOVERY_TXS.len(store)?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn relay_checkpoint() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let mut btc = setup_deposits(deps.as_mut().storage, &BitcoinConfig::default())?;
+    let xpriv = setup_signing_checkpoint(&mut btc, deps.as_mut().storage)?;
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv));
+    let cp = btc.checkpoints.signing(deps.as_ref().storage)?.unwrap();
+    let sigs = sign(&Secp256k1::signing_only(), &xpriv, &cp.to_sign(&xpub)?)?;
+    btc.checkpoints
+        .sign(&deps.api, &mut deps.storage, &xpub, sigs, 0, 10)?;
+
+    // mine another transaction, then the checkpoint transaction, each alone in
+    // a block
+    let other_tx = Transaction {
+        input: vec![],
+        lock_time: bitcoin::PackedLockTime(0),
+        output: vec![],
+        version: 1,
+    };
+    let tx = btc
+        .checkpoints
+        .get(deps.as_ref().storage, 0)?
+        .checkpoint_tx()?
+        .into_inner();
+    let other_height = btc.headers.height(deps.as_ref().storage)? + 1;
+    for (height, txid) in [
+        (other_height, other_tx.txid()),
+        (other_height + 1, tx.txid()),
+    ] {
+        let header = WorkHeader::new(
+            WrappedHeader::new(
+                Adapter::new(BlockHeader {
+                    bits: 0,
+                    merkle_root: TxMerkleNode::from_inner(txid.into_inner()),
+                    nonce: 0,
+                    prev_blockhash: BlockHash::all_zeros(),
+                    time: 0,
+                    version: 0,
+                }),
+                height,
+            ),
+            uint::Uint256([0, 0, 0, 0]),
+        );
+        HEADERS.push_back(deps.as_mut().storage, &header)?;
+    }
+
+    // a proof of a transaction other than the checkpoint's is rejected
+    let err = entrypoints::relay_checkpoint(
         deps.as_mut().storage,
-        &consensus_key1,
-        &(100, addr[0].to_string()),
+        mock_info("relayer", &[]),
+        other_height,
+        Adapter::new(PartialMerkleTree::from_txids(&[other_tx.txid()], &[true])),
+        0,
+        None,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "App Error: Bitcoin merkle proof does not match transaction"
+    );
+    assert_eq!(btc.checkpoints.confirmed_index(deps.as_ref().storage), None);
+
+    entrypoints::relay_checkpoint(
+        deps.as_mut().storage,
+        mock_info("relayer", &[]),
+        other_height + 1,
+        Adapter::new(PartialMerkleTree::from_txids(&[tx.txid()], &[true])),
+        0,
+        None,
     )?;
-    VALIDATORS.save(
+    assert_eq!(
+        btc.checkpoints.confirmed_index(deps.as_ref().storage),
+        Some(0)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn confirm_checkpoint() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let mut btc = setup_deposits(
         deps.as_mut().storage,
-        &consensus_key2,
-        &(10, addr[1].to_string()),
+        &BitcoinConfig {
+            min_checkpoint_confirmations: 1,
+            ..Default::default()
+        },
     )?;
+    let xpriv = setup_signing_checkpoint(&mut btc, deps.as_mut().storage)?;
+    let xpub = Xpub::new(ExtendedPubKey::from_priv(&Secp256k1::new(), &xpriv));
+    let cp = btc.checkpoints.signing(deps.as_ref().storage)?.unwrap();
+    let sigs = sign(&Secp256k1::signing_only(), &xpriv, &cp.to_sign(&xpub)?)?;
+    btc.checkpoints
+        .sign(&deps.api, &mut deps.storage, &xpub, sigs, 0, 10)?;
 
-    SIGNERS.save(deps.as_mut().storage, addr[0], &consensus_key1)?;
-    SIGNERS.save(deps.as_mut().storage, addr[1], &consensus_key2)?;
+    // mine the checkpoint transaction alone in a block
+    let tx = btc
+        .checkpoints
+        .get(deps.as_ref().storage, 0)?
+        .checkpoint_tx()?
+        .into_inner();
+    let height = btc.headers.height(deps.as_ref().storage)? + 1;
+    let push_header = |store: &mut dyn Storage, height: u32, merkle_root: TxMerkleNode| {
+        let header = WorkHeader::new(
+            WrappedHeader::new(
+                Adapter::new(BlockHeader {
+                    bits: 0,
+                    merkle_root,
+                    nonce: 0,
+                    prev_blockhash: BlockHash::all_zeros(),
+                    time: 0,
+                    version: 0,
+                }),
+                height,
+            ),
+            uint::Uint256([0, 0, 0, 0]),
+        );
+        HEADERS.push_back(store, &header)
+    };
+    push_header(
+        deps.as_mut().storage,
+        height,
+        TxMerkleNode::from_inner(tx.txid().into_inner()),
+    )?;
+    let proof = PartialMerkleTree::from_txids(&[tx.txid()], &[true]);
+    let proof = Binary::from(bitcoin::consensus::serialize(&proof));
+    let confirm = |store: &mut dyn Storage, tx: &Transaction| {
+        entrypoints::confirm_checkpoint(
+            store,
+            mock_info("relayer", &[]),
+            0,
+            Binary::from(bitcoin::consensus::serialize(tx)),
+            height,
+            proof.clone(),
+            0,
+            None,
+        )
+    };
 
-    let btc = RefCell::new(Bitcoin::default());
-    let secp = Secp256k1::new();
-    let network = btc.borrow().network();
-    let xpriv = vec![
-        ExtendedPrivKey::new_master(network, &[0])?,
-        ExtendedPrivKey::new_master(network, &[1])?,
-    ];
-    let xpub = vec![
-        ExtendedPubKey::from_priv(&secp, &xpriv[0]),
-        ExtendedPubKey::from_priv(&secp, &xpriv[1]),
-    ];
+    // a transaction other than the checkpoint's is rejected
+    let other_tx = Transaction {
+        input: vec![],
+        lock_time: bitcoin::PackedLockTime(0),
+        output: vec![],
+        version: 1,
+    };
+    let err = confirm(deps.as_mut().storage, &other_tx).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "App Error: Transaction does not match checkpoint"
+    );
 
-    let push_deposit = |store: &mut dyn Storage, dest: Dest, coin: Coin| -> ContractResult<()> {
-        let fixed_amount: u64 = 100_000_000;
-        assert_eq!(coin.amount.le(&Uint128::new(fixed_amount.into())), true);
-        let input = Input::new(
-            OutPoint {
-                txid: Txid::from_slice(&[0; 32])?,
-                vout: 0,
-            },
-            &btc.borrow().checkpoints.building(store)?.sigset,
-            &[0u8],
-            fixed_amount,
-            (9, 10),
-        )?;
-        let btc = btc.borrow_mut();
-        let mut building_mut = btc.checkpoints.building(store)?;
-        building_mut.fees_collected += 100_000_000u64 - (coin.amount.u128() as u64);
-        building_mut.pending.push((dest, coin));
-        let building_checkpoint_batch = &mut building_mut.batches[BatchType::Checkpoint];
-        let checkpoint_tx = building_checkpoint_batch.get_mut(0).unwrap();
-        checkpoint_tx.input.push(input);
-        let index = btc.checkpoints.index(store);
-        btc.checkpoints.set(store, index, &building_mut)?;
-        Ok(())
+    // the block at the tip has a single confirmation, one short of
+    // `min_checkpoint_confirmations + 1`
+    let err = confirm(deps.as_mut().storage, &tx).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "App Error: Block is not sufficiently confirmed"
+    );
+    assert_eq!(btc.checkpoints.confirmed_index(deps.as_ref().storage), None);
+
+    push_header(deps.as_mut().storage, height + 1, TxMerkleNode::all_zeros())?;
+
+    // the proof places the transaction at index 0 of its block
+    let err = entrypoints::confirm_checkpoint(
+        deps.as_mut().storage,
+        mock_info("relayer", &[]),
+        0,
+        Binary::from(bitcoin::consensus::serialize(&tx)),
+        height,
+        proof.clone(),
+        1,
+        None,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Merkle proof places the transaction at block index 0, not 1"
+    );
+
+    confirm(deps.as_mut().storage, &tx)?;
+    assert_eq!(
+        btc.checkpoints.confirmed_index(deps.as_ref().storage),
+        Some(0)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn ibc_channel_allowlist() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let ibc_dest = |channel: &str| {
+        Dest::Ibc(IbcDest {
+            source_port: "transfer".to_string(),
+            source_channel: channel.to_string(),
+            receiver: "receiver".to_string(),
+            sender: "sender".to_string(),
+            timeout_timestamp: 10,
+            memo: "".to_string(),
+        })
     };
 
-    let sign_batch = |api: &dyn Api, store: &mut dyn Storage, btc_height| -> ContractResult<()> {
-        let mut btc = btc.borrow_mut();
-        let queue = &mut btc.checkpoints;
-        let cp = queue.signing(store)?.unwrap();
-        let sigset_index = cp.sigset.index;
-        for i in 0..2 {
-            let Some(cp) = queue.signing(store)? else {
-                break;
-            };
+    // every channel is allowed until the allowlist is first set
+    let unlisted = ibc_dest("channel-9");
+    assert!(query_deposit_address(store, unlisted.clone()).is_ok());
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &unlisted)?;
+    assert_eq!(
+        relay_mined_deposit(&mut btc, store, &btc_tx, height, &unlisted)?,
+        DepositOutcome::Credited
+    );
 
-            let to_sign = cp.to_sign(&Xpub::new(xpub[i]))?;
-            let secp2 = Secp256k1::signing_only();
-            let sigs = sign(&secp2, &xpriv[i], &to_sign)?;
-            queue.sign(
-                api,
-                store,
-                &Xpub::new(xpub[i]),
-                sigs,
-                sigset_index,
-                btc_height,
-            )?;
-        }
+    let channels = vec!["channel-0".to_string()];
+    let err =
+        entrypoints::set_ibc_channel_allowlist(store, mock_info("stranger", &[]), channels.clone())
+            .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    entrypoints::set_ibc_channel_allowlist(store, mock_info("owner", &[]), channels)?;
 
-        Ok(())
+    let allowed = ibc_dest("channel-0");
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &allowed)?;
+    assert_eq!(
+        relay_mined_deposit(&mut btc, store, &btc_tx, height, &allowed)?,
+        DepositOutcome::Credited
+    );
+
+    // a deposit to a channel which is no longer allowed is rejected
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &unlisted)?;
+    let err = relay_mined_deposit(&mut btc, store, &btc_tx, height, &unlisted).unwrap_err();
+    assert!(matches!(err, ContractError::Ibc(_)));
+    assert_eq!(err.to_string(), "Channel channel-9 is not allowed");
+    let outpoint = OutPoint::new(btc_tx.txid(), 0);
+    assert!(!btc.processed_outpoints.contains(store, outpoint));
+    let err = query_deposit_address(store, unlisted.clone()).unwrap_err();
+    assert_eq!(err.to_string(), "Channel channel-9 is not allowed");
+
+    let building = btc.checkpoints.building(store)?;
+    assert_eq!(building.pending.len(), 2);
+    assert_eq!(building.pending[0].0, unlisted);
+    assert_eq!(building.pending[1].0, allowed);
+
+    Ok(())
+}
+
+#[test]
+fn ibc_memo_length() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let config = BitcoinConfig::default();
+    let mut btc = setup_deposits(store, &config)?;
+    IBC_CHANNEL_ALLOWLIST.save(store, &vec!["channel-0".to_string()])?;
+    let ibc_dest = |memo: String| {
+        Dest::Ibc(IbcDest {
+            source_port: "transfer".to_string(),
+            source_channel: "channel-0".to_string(),
+            receiver: "receiver".to_string(),
+            sender: "sender".to_string(),
+            timeout_timestamp: 10,
+            memo,
+        })
     };
-    let sign_cp = |deps: DepsMut, btc_height| -> ContractResult<()> {
-        if btc.borrow().checkpoints.signing(deps.storage)?.is_some() {
-            sign_batch(deps.api, deps.storage, btc_height)?;
-        }
+    // a JSON string memo of the given length
+    let memo_of_length = |length: u64| format!("\"{}\"", "a".repeat(length as usize - 2));
+
+    let at_limit = ibc_dest(memo_of_length(config.max_memo_length));
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &at_limit)?;
+    assert_eq!(
+        relay_mined_deposit(&mut btc, store, &btc_tx, height, &at_limit)?,
+        DepositOutcome::Credited
+    );
+
+    let over_limit = ibc_dest(memo_of_length(config.max_memo_length + 1));
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &over_limit)?;
+    let err = relay_mined_deposit(&mut btc, store, &btc_tx, height, &over_limit).unwrap_err();
+    assert!(matches!(err, ContractError::Ibc(_)));
+    assert_eq!(btc.checkpoints.building(store)?.pending.len(), 1);
+
+    // a deposit whose memo isn't JSON is rejected in the same way
+    let not_json = ibc_dest("not json".to_string());
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &not_json)?;
+    let err = relay_mined_deposit(&mut btc, store, &btc_tx, height, &not_json).unwrap_err();
+    assert!(matches!(err, ContractError::Ibc(_)));
+
+    let building = btc.checkpoints.building(store)?;
+    assert_eq!(building.pending.len(), 1);
+    assert_eq!(building.pending[0].0, at_limit);
+
+    Ok(())
+}
+
+#[test]
+fn relay_deposit_to_prior_sigset() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    // the deposit pays to the first checkpoint's signatory set, which is no
+    // longer the building one once the queue advances
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    setup_signing_checkpoint(&mut btc, store)?;
+    assert_eq!(btc.checkpoints.building(store)?.sigset.index(), 1);
+
+    let mut relay = |sigset_index| {
+        btc.relay_deposit(
+            set_time(1000),
+            store,
+            Adapter::new(btc_tx.clone()),
+            height,
+            Adapter::new(PartialMerkleTree::from_txids(&[btc_tx.txid()], &[true])),
+            None,
+            0,
+            sigset_index,
+            dest.clone(),
+        )
+    };
+    assert_eq!(
+        relay(1).unwrap_err().to_string(),
+        "App Error: Output script does not match signature set",
+    );
+    relay(0)?;
+
+    // the input is signed for with keys derived for the prior sigset
+    let building = btc.checkpoints.building(deps.as_ref().storage)?;
+    assert!(building.batches[BatchType::Checkpoint][0]
+        .input
+        .iter()
+        .any(|input| input.prevout.txid == btc_tx.txid() && input.sigset_index == 0));
+
+    Ok(())
+}
+
+#[test]
+fn token_info() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let bitcoin_config = BitcoinConfig::default();
+    setup_deposits(&mut deps.storage, &bitcoin_config)?;
+    let denom = get_full_btc_denom(&deps.storage)?;
+
+    let info = query_token_info(QuerierWrapper::new(&deps.querier), &deps.storage)?;
+    assert_eq!(info.denom, denom);
+    assert_eq!(info.total_supply, Uint128::zero());
+    // 1 sat is 1_000_000 units, so a unit has 6 more decimals than BTC
+    assert_eq!(info.decimals, 14);
+
+    // the supply is read from the bank module
+    deps.querier
+        .update_balance("alice", vec![Coin::new(30_000_000_000_000, &denom)]);
+    assert_eq!(
+        query_token_info(QuerierWrapper::new(&deps.querier), &deps.storage)?.total_supply,
+        Uint128::from(30_000_000_000_000u128)
+    );
+
+    BITCOIN_CONFIG.save(
+        &mut deps.storage,
+        &BitcoinConfig {
+            units_per_sat: 1,
+            ..bitcoin_config
+        },
+    )?;
+    assert_eq!(
+        query_token_info(QuerierWrapper::new(&deps.querier), &deps.storage)?.decimals,
+        8
+    );
+
+    Ok(())
+}
 
-        Ok(())
-    };
+#[test]
+fn relay_op_return_deposit() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
 
-    let confirm_cp = |store: &mut dyn Storage, confirmed_index: u32| {
-        let btc = btc.borrow_mut();
-        let current_checkpoint_index = btc.checkpoints.index(store);
-        assert_eq!(current_checkpoint_index, confirmed_index + 1);
-        let confirmed_checkpoint_index = btc.checkpoints.last_completed_index(store).unwrap();
-        assert_eq!(confirmed_checkpoint_index, confirmed_index);
-        CONFIRMED_INDEX.save(store, &confirmed_index).unwrap();
+    // the deposit pays the static address and names its dest in an OP_RETURN
+    let sigset = btc.checkpoints.building(store)?.sigset.clone();
+    let threshold = btc.checkpoints.config(store).sigset_threshold;
+    let btc_tx = Transaction {
+        input: vec![],
+        lock_time: bitcoin::PackedLockTime(0),
+        output: vec![
+            TxOut {
+                value: 100_000_000,
+                script_pubkey: sigset.output_script(OP_RETURN_DEPOSIT_COMMITMENT, threshold)?,
+            },
+            TxOut {
+                value: 0,
+                script_pubkey: Script::new_op_return(&dest.commitment_bytes()?),
+            },
+        ],
+        version: 1,
     };
+    let height = mine_tx(&btc, store, &btc_tx)?;
 
-    let take_pending = |store: &mut dyn Storage| -> ContractResult<_> {
-        let mut btc = btc.borrow_mut();
-        let pending = btc.take_pending_completed(store)?;
-        Ok(pending)
-    };
+    let other = Dest::Address(Addr::unchecked("other"));
+    assert_eq!(
+        relay_mined_deposit(&mut btc, store, &btc_tx, height, &other)
+            .unwrap_err()
+            .to_string(),
+        "App Error: OP_RETURN commitment does not match dest",
+    );
 
-    let maybe_step = |env: Env, store: &mut dyn Storage| -> ContractResult<()> {
-        let mut btc = btc.borrow_mut();
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+    let building = btc.checkpoints.building(store)?;
+    assert_eq!(building.pending.len(), 1);
+    assert_eq!(building.pending[0].0, dest);
+    assert_eq!(building.checkpoint_tx()?.input.len(), 1);
 
-        btc.begin_block_step(env, store, vec![1, 2, 3])?;
+    Ok(())
+}
 
-        Ok(())
-    };
+#[test]
+fn signatories() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    BITCOIN_CONFIG.save(store, &BitcoinConfig::default())?;
 
-    let env = set_time(0);
-    for i in 0..2 {
-        btc.borrow_mut().set_signatory_key(
-            deps.as_mut().storage,
-            Addr::unchecked(addr[i]),
-            Xpub::new(xpub[i]),
-        )?;
+    let mut btc = Bitcoin::default();
+    let secp = Secp256k1::new();
+    let mut xpubs = vec![];
+    for (i, power) in [(0u8, 100), (1, 50)] {
+        let consensus_key = [i; 32];
+        let addr = format!("validator{}", i + 1);
+        VALIDATORS.save(store, &consensus_key, &(power, addr.clone()))?;
+        SIGNERS.save(store, &addr, &consensus_key)?;
+        let xpriv = ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &[i])?;
+        let xpub = Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv));
+        btc.set_signatory_key(store, Addr::unchecked(addr), xpub)?;
+        xpubs.push(xpub);
     }
 
-    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 0);
-    maybe_step(env, deps.as_mut().storage)?;
-    assert_eq!(btc.borrow().checkpoints.len(deps.as_ref().storage)?, 1);
-    let env = set_time(1000);
+    let signatories = query_signatories(store)?;
+    assert_eq!(signatories.len(), 2);
+    assert_eq!(signatories[0].address, "validator1");
+    assert_eq!(signatories[0].xpub, Some(HashBinary(xpubs[0])));
+    assert_eq!(signatories[0].voting_power, 100);
+    assert_eq!(signatories[1].address, "validator2");
+    assert_eq!(signatories[1].xpub, Some(HashBinary(xpubs[1])));
+    assert_eq!(signatories[1].voting_power, 50);
 
-    let mut dest = IbcDest {
-        source_port: "transfer".to_string(),
-        source_channel: "channel-0".to_string(),
-        sender: "sender1".to_string(),
-        receiver: "receiver".to_string(),
-        timeout_timestamp: 10u64,
-        memo: "".to_string(),
-    };
+    Ok(())
+}
 
-    // initially, there should not be any confirmed checkpoints -> return empty array for pending dests
-    assert_eq!(take_pending(deps.as_mut().storage)?.len(), 0);
-    // fixture: create 2 confirmed checkpoints having deposits so we can validate later
-    push_deposit(
-        deps.as_mut().storage,
-        Dest::Ibc(dest.clone()),
-        Coin {
-            denom: BTC_NATIVE_TOKEN_DENOM.to_string(),
-            amount: 95_000_000u128.into(),
-        },
+#[test]
+fn threshold_unreachable() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let header_config = HeaderConfig::mainnet()?;
+    HEADER_CONFIG.save(deps.as_mut().storage, &header_config)?;
+    HEADERS.push_back(deps.as_mut().storage, &header_config.work_header())?;
+    BITCOIN_CONFIG.save(deps.as_mut().storage, &BitcoinConfig::default())?;
+    FEE_POOL.save(deps.as_mut().storage, &0)?;
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+    BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+
+    let mut btc = Bitcoin::default();
+    let secp = Secp256k1::new();
+    let mut xpubs = vec![];
+    let mut xprivs = vec![];
+    for i in 0..3u8 {
+        let consensus_key = [i; 32];
+        let addr = format!("validator{}", i + 1);
+        VALIDATORS.save(deps.as_mut().storage, &consensus_key, &(100, addr.clone()))?;
+        SIGNERS.save(deps.as_mut().storage, &addr, &consensus_key)?;
+        let xpriv = ExtendedPrivKey::new_master(btc.network(deps.as_ref().storage)?, &[i])?;
+        let xpub = Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv));
+        btc.set_signatory_key(deps.as_mut().storage, Addr::unchecked(addr), xpub)?;
+        xprivs.push(xpriv);
+        xpubs.push(xpub);
+    }
+    btc.begin_block_step(set_time(0), deps.as_mut().storage, vec![1, 2, 3])?;
+    setup_signing_checkpoint(&mut btc, deps.as_mut().storage)?;
+
+    // the first signatory signs while the threshold is still reachable
+    let cp = btc.checkpoints.signing(deps.as_ref().storage)?.unwrap();
+    let sigs = sign(
+        &Secp256k1::signing_only(),
+        &xprivs[0],
+        &cp.to_sign(&xpubs[0])?,
     )?;
-    dest.sender = "sender2".to_string();
-    push_deposit(
-        deps.as_mut().storage,
-        Dest::Ibc(dest.clone()),
-        Coin {
-            denom: BTC_NATIVE_TOKEN_DENOM.to_string(),
-            amount: 95_000_000u128.into(),
-        },
+    btc.checkpoints
+        .sign(&deps.api, &mut deps.storage, &xpubs[0], sigs, 0, 10)?;
+
+    // jailing one of the others leaves at most 200 of the 300 voting power,
+    // which is not more than the two-thirds threshold
+    btc.punish_validator(deps.as_mut().storage, &[2; 32], "validator3".to_string())?;
+
+    let cp = btc.checkpoints.signing(deps.as_ref().storage)?.unwrap();
+    let sigs = sign(
+        &Secp256k1::signing_only(),
+        &xprivs[1],
+        &cp.to_sign(&xpubs[1])?,
     )?;
-    maybe_step(env, deps.as_mut().storage)?;
+    let err = btc
+        .checkpoints
+        .sign(&deps.api, &mut deps.storage, &xpubs[1], sigs, 0, 10)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "threshold unreachable");
 
-    // validate current checkpoint is on signing state
-    let checkpoint_signing = btc.borrow().checkpoints.signing(deps.as_ref().storage)?;
+    let progress = query_signing_progress(deps.as_ref().storage, 0)?;
+    assert_eq!(progress.signed_voting_power, 100);
+    assert!(!progress.threshold_met);
 
-    match checkpoint_signing {
-        Some(checkpoint_data) => {
-            println!("Checkpoint Data: {:?}", checkpoint_data.fees_collected);
-            println!("{:?}", checkpoint_data.pending);
-            assert_eq!(checkpoint_data.fees_collected > 0, true);
-        }
-        None => {
-            panic!("Checkpoint not found");
-        }
+    Ok(())
+}
+
+#[test]
+fn deposit_history() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+    let other = Dest::Address(Addr::unchecked("other"));
+
+    let mut deposits = vec![];
+    for (value, dest) in [
+        (100_000_000, &dest),
+        (50_000_000, &other),
+        (20_000_000, &dest),
+    ] {
+        let (btc_tx, height) = mine_deposit(&btc, store, value, dest)?;
+        relay_mined_deposit(&mut btc, store, &btc_tx, height, dest)?;
+        deposits.push((btc_tx.txid(), height));
     }
-    sign_cp(deps.as_mut(), 10)?;
-    confirm_cp(deps.as_mut().storage, 0);
+    let pending = btc.checkpoints.building(store)?.pending;
 
-    let env = set_time(2000);
-    push_deposit(
-        deps.as_mut().storage,
-        Dest::Ibc(dest.clone()),
-        Coin {
-            denom: BTC_NATIVE_TOKEN_DENOM.to_string(),
-            amount: 98_000_000u128.into(),
+    let history = query_deposit_history(store, "depositor".to_string(), None, 10)?;
+    assert_eq!(history.len(), 2);
+    for (i, (record, deposit)) in history.iter().zip([0, 2]).enumerate() {
+        assert_eq!(record.index, i as u64);
+        assert_eq!(record.txid, HashBinary(deposits[deposit].0));
+        assert_eq!(record.vout, 0);
+        assert_eq!(record.amount, pending[deposit].1.amount);
+        assert_eq!(record.height, deposits[deposit].1);
+    }
+
+    let history = query_deposit_history(store, "depositor".to_string(), Some(0), 10)?;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].txid, HashBinary(deposits[2].0));
+    assert_eq!(
+        query_deposit_history(store, "depositor".to_string(), None, 1)?.len(),
+        1
+    );
+    assert_eq!(
+        query_deposit_history(store, "other".to_string(), None, 10)?.len(),
+        1
+    );
+
+    Ok(())
+}
+
+#[test]
+fn min_signatory_power() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    BITCOIN_CONFIG.save(store, &BitcoinConfig::default())?;
+    CHECKPOINT_CONFIG.save(
+        store,
+        &CheckpointConfig {
+            min_signatory_power: 5_000,
+            ..Default::default()
         },
     )?;
-    maybe_step(env, deps.as_mut().storage)?;
-    sign_cp(deps.as_mut(), 10)?;
-    confirm_cp(deps.as_mut().storage, 1);
 
-    let first_unhandled_confirmed_cp_index = FIRST_UNHANDLED_CONFIRMED_INDEX
-        .load(deps.as_ref().storage)
-        .unwrap();
-    assert_eq!(first_unhandled_confirmed_cp_index, 0);
+    let mut btc = Bitcoin::default();
+    let secp = Secp256k1::new();
+    for i in 0..3u8 {
+        let consensus_key = [i; 32];
+        let addr = format!("validator{}", i + 1);
+        SIGNERS.save(store, &addr, &consensus_key)?;
+        let xpriv = ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &[i])?;
+        let xpub = Xpub::new(ExtendedPubKey::from_priv(&secp, &xpriv));
+        btc.set_signatory_key(store, Addr::unchecked(addr), xpub)?;
+    }
+    let set_powers = |store: &mut dyn Storage, powers: [u64; 3]| -> ContractResult<()> {
+        for (i, power) in powers.into_iter().enumerate() {
+            let addr = format!("validator{}", i + 1);
+            VALIDATORS.save(store, &[i as u8; 32], &(power, addr))?;
+        }
+        Ok(())
+    };
 
-    let confirmed_index = CONFIRMED_INDEX.load(deps.as_ref().storage)?;
-    assert_eq!(confirmed_index, 1);
-    // before take pending, the confirmed checkpoints should have some pending deposits
+    // the tiny signatory is below half of the total voting power
+    set_powers(store, [100, 100, 1])?;
+    let sigset = signatory::SignatorySet::from_validator_ctx(store, 0, 0)?;
+    assert_eq!(sigset.signatories.len(), 2);
+    assert!(sigset
+        .signatories
+        .iter()
+        .all(|signatory| signatory.voting_power == 100));
+    assert_eq!(sigset.present_vp(), 200);
+    assert_eq!(sigset.possible_vp(), 201);
+    assert_eq!(sigset.pruned_vp, 1);
+
+    // both small signatories are below the minimum, but pruning the second
+    // would leave no more than the full set's 2/3 threshold of 8
+    set_powers(store, [6, 3, 3])?;
+    let sigset = signatory::SignatorySet::from_validator_ctx(store, 0, 0)?;
+    assert_eq!(sigset.signatories.len(), 2);
+    assert_eq!(sigset.present_vp(), 9);
+    assert_eq!(sigset.pruned_vp, 3);
+    // the threshold is still that of the full set, not 2/3 of what's left
+    assert_eq!(sigset.signature_threshold(SIGSET_THRESHOLD), 8);
     assert_eq!(
-        btc.borrow()
-            .checkpoints
-            .get(deps.as_ref().storage, 0)?
-            .pending
-            .iter()
-            .count(),
-        2
+        ThresholdSig::from_sigset(&sigset).threshold,
+        sigset.signature_threshold(SIGSET_THRESHOLD)
     );
-    assert_eq!(
-        btc.borrow()
-            .checkpoints
-            .get(deps.as_ref().storage, 1)?
-            .pending
-            .iter()
-            .count(),
-        1
+
+    Ok(())
+}
+
+#[test]
+fn reorged_deposit_is_invalidated() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let header_config = super::header::regtest_config(1000);
+    let trusted = header_config.work_header().header;
+    btc.headers.configure(store, header_config)?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    let mine = |prev: &WrappedHeader, merkle_root: TxMerkleNode| {
+        let mut header = BlockHeader {
+            version: 1,
+            prev_blockhash: prev.block_hash(),
+            merkle_root,
+            time: prev.time() + 600,
+            bits: prev.bits(),
+            nonce: 0,
+        };
+        while header.validate_pow(&header.target()).is_err() {
+            header.nonce += 1;
+        }
+        WrappedHeader::from_header(&header, prev.height() + 1)
+    };
+
+    let sigset = btc.checkpoints.building(store)?.sigset.clone();
+    let threshold = btc.checkpoints.config(store).sigset_threshold;
+    let btc_tx = Transaction {
+        version: 1,
+        lock_time: bitcoin::PackedLockTime(0),
+        input: vec![],
+        output: vec![TxOut {
+            value: 100_000_000,
+            script_pubkey: sigset.output_script(&dest.commitment_bytes()?, threshold)?,
+        }],
+    };
+    let deposit_block = mine(
+        &trusted,
+        TxMerkleNode::from_inner(btc_tx.txid().into_inner()),
     );
+    let confirmation = mine(&deposit_block, TxMerkleNode::all_zeros());
+    btc.relay_headers(store, vec![deposit_block, confirmation])?;
+    let building = btc.checkpoints.building(store)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, 1001, &dest)?;
+    assert_eq!(btc.checkpoints.building(store)?.pending.len(), 1);
 
-    // action. After take pending, the unhandled confirmed index should increase to 2 since we handled 2 confirmed checkpoints
-    let cp_dests = take_pending(deps.as_mut().storage)?;
+    // a longer fork replaces the deposit's block, and includes the deposit
+    // one block later
+    let mut fork = vec![mine(&trusted, TxMerkleNode::from_inner([1; 32]))];
+    fork.push(mine(
+        &fork[0],
+        TxMerkleNode::from_inner(btc_tx.txid().into_inner()),
+    ));
+    for _ in 0..2 {
+        let next = mine(fork.last().unwrap(), TxMerkleNode::all_zeros());
+        fork.push(next);
+    }
+    let res = entrypoints::relay_headers(store, fork)?;
+    assert!(res.messages.is_empty());
+    assert_eq!(
+        query_deposit_status(store, btc_tx.txid().to_string(), 0)?,
+        DepositStatus::Invalidated
+    );
 
-    let first_unhandled_confirmed_cp_index =
-        FIRST_UNHANDLED_CONFIRMED_INDEX.load(deps.as_ref().storage)?;
-    assert_eq!(first_unhandled_confirmed_cp_index, 2);
-    assert_eq!(cp_dests.len(), 3);
-    assert_eq!(cp_dests[0].len(), 2); // cp_dest confirmed
-    assert_eq!(cp_dests[1].len(), 1); // cp_dest confirmed
-    assert_eq!(cp_dests[2].len(), 0); // cp_dest completed
+    // the credit, the input and the fee are all reversed
+    let invalidated = btc.checkpoints.building(store)?;
+    assert!(invalidated.pending.is_empty());
     assert_eq!(
-        cp_dests[0][0].0,
-        Dest::Ibc(IbcDest {
-            sender: "sender1".to_string(),
-            ..dest.clone()
-        })
+        invalidated.batches[BatchType::Checkpoint][0].input,
+        building.batches[BatchType::Checkpoint][0].input
     );
-    assert_eq!(cp_dests[0][0].1.amount.u128(), 95_000_000u128);
+    assert_eq!(invalidated.fees_collected, building.fees_collected);
+    let outpoint = OutPoint::new(btc_tx.txid(), 0);
+    assert!(!btc.processed_outpoints.contains(store, outpoint));
+
+    // relaying more headers on the new chain doesn't invalidate it again
+    let tip = btc.headers.get_by_height(store, 1004)?.unwrap().header;
+    entrypoints::relay_headers(store, vec![mine(&tip, TxMerkleNode::all_zeros())])?;
 
+    // the deposit is credited again from its block on the new chain
+    relay_mined_deposit(&mut btc, store, &btc_tx, 1002, &dest)?;
+    let recredited = btc.checkpoints.building(store)?;
+    assert_eq!(recredited.pending.len(), 1);
+    assert_eq!(recredited.pending[0].0, dest);
     assert_eq!(
-        cp_dests[0][1].0,
-        Dest::Ibc(IbcDest {
-            sender: "sender2".to_string(),
-            ..dest.clone()
-        })
+        query_deposit_status(store, btc_tx.txid().to_string(), 0)?,
+        DepositStatus::Confirmed
     );
-    assert_eq!(cp_dests[0][1].1.amount.u128(), 95_000_000u128);
+    let err = relay_mined_deposit(&mut btc, store, &btc_tx, 1002, &dest).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "App Error: Output has already been relayed"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn reorged_deposit_in_signing_checkpoint_stays_credited() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let header_config = super::header::regtest_config(1000);
+    let trusted = header_config.work_header().header;
+    btc.headers.configure(store, header_config)?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+
+    let mine = |prev: &WrappedHeader, merkle_root: TxMerkleNode| {
+        let mut header = BlockHeader {
+            version: 1,
+            prev_blockhash: prev.block_hash(),
+            merkle_root,
+            time: prev.time() + 600,
+            bits: prev.bits(),
+            nonce: 0,
+        };
+        while header.validate_pow(&header.target()).is_err() {
+            header.nonce += 1;
+        }
+        WrappedHeader::from_header(&header, prev.height() + 1)
+    };
+
+    let sigset = btc.checkpoints.building(store)?.sigset.clone();
+    let threshold = btc.checkpoints.config(store).sigset_threshold;
+    let btc_tx = Transaction {
+        version: 1,
+        lock_time: bitcoin::PackedLockTime(0),
+        input: vec![],
+        output: vec![TxOut {
+            value: 100_000_000,
+            script_pubkey: sigset.output_script(&dest.commitment_bytes()?, threshold)?,
+        }],
+    };
+    let deposit_block = mine(
+        &trusted,
+        TxMerkleNode::from_inner(btc_tx.txid().into_inner()),
+    );
+    let confirmation = mine(&deposit_block, TxMerkleNode::all_zeros());
+    btc.relay_headers(store, vec![deposit_block, confirmation])?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, 1001, &dest)?;
+
+    // the checkpoint spending the deposit advances to signing
+    let mut checkpoint = btc.checkpoints.building(store)?;
+    checkpoint.status = CheckpointStatus::Signing;
+    btc.checkpoints.set(store, 0, &checkpoint)?;
+    CHECKPOINTS.push_back(store, &Checkpoint::new(checkpoint.sigset.clone())?)?;
+    BUILDING_INDEX.save(store, &1)?;
+
+    let mut fork = vec![mine(&trusted, TxMerkleNode::from_inner([1; 32]))];
+    for _ in 0..2 {
+        let next = mine(fork.last().unwrap(), TxMerkleNode::all_zeros());
+        fork.push(next);
+    }
+    entrypoints::relay_headers(store, fork)?;
+
+    // its transaction still spends the deposit, so the credit stands
+    assert_eq!(btc.checkpoints.get(store, 0)?.pending, checkpoint.pending);
+    assert_ne!(
+        query_deposit_status(store, btc_tx.txid().to_string(), 0)?,
+        DepositStatus::Invalidated
+    );
+    assert!(btc
+        .processed_outpoints
+        .contains(store, OutPoint::new(btc_tx.txid(), 0)));
+
+    Ok(())
+}
+
+#[test]
+fn signing_checkpoint_expires() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let mut btc = setup_deposits(store, &BitcoinConfig::default())?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+
+    // checkpoint 0 starts signing at time 1000, but never gets signed
+    let fee_pool = query_fee_pool(store)?;
+    setup_signing_checkpoint(&mut btc, store)?;
+    let signing = btc.checkpoints.get(store, 0)?;
+    assert!(!signing.signatory_reward.is_zero());
+    let prevouts = |checkpoint: &Checkpoint| -> Vec<OutPoint> {
+        checkpoint.batches[BatchType::Checkpoint][0]
+            .input
+            .iter()
+            .map(|input| *input.prevout)
+            .collect()
+    };
+    assert_eq!(btc.checkpoints.index(store), 1);
+
+    let timeout = CheckpointConfig::default().signing_timeout;
+    let res = entrypoints::clock_end_block(&set_time(1000 + timeout), store, Binary::default())?;
+    assert!(!res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "checkpoint_expired"));
+    assert!(btc.checkpoints.signing(store)?.is_some());
 
+    let res = entrypoints::clock_end_block(&set_time(1001 + timeout), store, Binary::default())?;
+    assert!(res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "checkpoint_expired" && attr.value == "0"));
+
+    // the inputs are back in a building checkpoint at the aborted index, and
+    // the reserve input spending the aborted transaction is gone
+    assert_eq!(btc.checkpoints.index(store), 0);
+    assert!(btc.checkpoints.signing(store)?.is_none());
+    let building = btc.checkpoints.building(store)?;
+    assert_eq!(building.status, CheckpointStatus::Building);
+    assert_eq!(prevouts(&building), prevouts(&signing));
+    assert!(building.batches[BatchType::Checkpoint][0]
+        .input
+        .iter()
+        .all(|input| input.signatures.sigs().is_empty()));
+    // the aborted checkpoint had no withdrawals to carry over
+    assert!(building.batches[BatchType::Checkpoint][0].output.is_empty());
+    assert_eq!(building.pending, signing.pending);
+    // and the fee pool gets back the miner fee and signatory rewards
+    assert_eq!(query_fee_pool(store)?, fee_pool);
     assert_eq!(
-        cp_dests[1][0].0,
-        Dest::Ibc(IbcDest {
-            sender: "sender2".to_string(),
-            ..dest.clone()
-        })
+        query_deposit_status(store, btc_tx.txid().to_string(), 0)?,
+        DepositStatus::Confirmed
     );
-    assert_eq!(cp_dests[1][0].1.amount.u128(), 98_000_000u128);
 
-    // // assert confirmed checkpoints pending. Should not have anything because we have removed them already in take_pending()
-    let checkpoints = &btc.borrow().checkpoints;
-    let first_cp = checkpoints.get(deps.as_ref().storage, 0).unwrap();
-    assert_eq!(first_cp.pending.iter().count(), 0);
-    let second_cp = checkpoints.get(deps.as_ref().storage, 1).unwrap();
-    assert_eq!(second_cp.pending.iter().count(), 0);
+    Ok(())
+}
+
+#[test]
+fn cancel_withdrawal_after_signing_expires() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    let bitcoin_config = BitcoinConfig {
+        min_withdrawal_checkpoints: 1,
+        ..Default::default()
+    };
+    let mut btc = setup_deposits(store, &bitcoin_config)?;
+    let dest = Dest::Address(Addr::unchecked("depositor"));
+    let (btc_tx, height) = mine_deposit(&btc, store, 100_000_000, &dest)?;
+    relay_mined_deposit(&mut btc, store, &btc_tx, height, &dest)?;
+
+    let amount = Uint128::from(10_000_000_000_000u128);
+    let denom = get_full_btc_denom(store)?;
+    let script = |byte: u8| {
+        Builder::new()
+            .push_int(0)
+            .push_slice(&[byte; 20])
+            .into_script()
+    };
+    let withdraw = |store: &mut dyn Storage, withdrawer: &str, byte: u8| {
+        entrypoints::withdraw_to_bitcoin(
+            store,
+            mock_info(withdrawer, &[Coin::new(amount.u128(), denom.clone())]),
+            mock_env(),
+            Adapter::new(script(byte)),
+        )
+    };
+
+    // alice's withdrawal is in checkpoint 0, which never gets signed, and
+    // bob's is in checkpoint 1
+    withdraw(store, "alice", 1)?;
+    setup_signing_checkpoint(&mut btc, store)?;
+    withdraw(store, "bob", 2)?;
+    let timeout = CheckpointConfig::default().signing_timeout;
+    entrypoints::clock_end_block(&set_time(1001 + timeout), store, Binary::default())?;
+    assert_eq!(btc.checkpoints.index(store), 0);
+    let output_scripts = |store: &dyn Storage| -> ContractResult<Vec<Script>> {
+        Ok(btc
+            .checkpoints
+            .building(store)?
+            .checkpoint_tx()?
+            .output
+            .iter()
+            .map(|output| output.script_pubkey.clone())
+            .collect())
+    };
+    assert_eq!(output_scripts(store)?, vec![script(2), script(1)]);
+
+    // canceling alice's merged withdrawal removes her output, not another
+    // user's
+    withdraw(store, "carol", 3)?;
+    let err = entrypoints::cancel_withdrawal(store, mock_info("alice", &[]), 0, 0).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    entrypoints::cancel_withdrawal(store, mock_info("alice", &[]), 0, 1)?;
+    assert_eq!(output_scripts(store)?, vec![script(2), script(3)]);
+    entrypoints::cancel_withdrawal(store, mock_info("carol", &[]), 0, 1)?;
+    assert_eq!(output_scripts(store)?, vec![script(2)]);
+
     Ok(())
 }
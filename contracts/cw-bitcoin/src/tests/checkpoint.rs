@@ -1,12 +1,28 @@
-use cosmwasm_std::{testing::mock_dependencies, Storage};
+use cosmwasm_std::{
+    from_json,
+    testing::{mock_dependencies, mock_env, mock_info},
+    Addr, Binary, Storage, Uint128,
+};
 
 use crate::{
-    checkpoint::{adjust_fee_rate, BitcoinTx, Checkpoint, CheckpointQueue, CheckpointStatus},
-    constants::DEFAULT_FEE_RATE,
-    error::ContractResult,
-    interface::CheckpointConfig,
+    checkpoint::{
+        adjust_fee_rate, BatchType, BitcoinTx, Checkpoint, CheckpointQueue, CheckpointStatus, Input,
+    },
+    constants::{DEFAULT_FEE_RATE, DEFAULT_FEE_RATE_HISTORY_LIMIT, SCHEMA_VERSION},
+    contract::migrate,
+    entrypoints::{
+        prune_checkpoints, query_checkpoint, query_checkpoint_queue_status, query_deposit_address,
+        query_emergency_disbursal_lock_time, query_estimate_withdrawal_fee, query_fee_rate_history,
+        query_list_checkpoints, query_signatory_set, update_checkpoint_config,
+    },
+    error::{ContractError, ContractResult},
+    interface::{BitcoinConfig, CheckpointConfig, Config, Dest, IbcDest, InputSelection},
+    msg::{MigrateMsg, QueryMsg},
     signatory::{Signatory, SignatorySet},
-    state::{BUILDING_INDEX, CHECKPOINTS, CONFIRMED_INDEX},
+    state::{
+        BITCOIN_CONFIG, BUILDING_INDEX, CHECKPOINTS, CHECKPOINT_CONFIG, CONFIG, CONFIRMED_INDEX,
+        FIRST_UNHANDLED_CONFIRMED_INDEX, IBC_CHANNEL_ALLOWLIST, STORED_SCHEMA_VERSION,
+    },
     tests::helper::push_bitcoin_tx_output,
     threshold_sig::Pubkey,
 };
@@ -61,9 +77,12 @@ fn create_queue_with_status(
             status,
             fee_rate: DEFAULT_FEE_RATE,
             signed_at_btc_height: None,
+            signing_since: None,
+            was_signed: false,
             deposits_enabled: true,
             sigset: SignatorySet::default(),
             fees_collected: 0,
+            signatory_reward: Uint128::zero(),
             pending: vec![],
             batches: vec![],
         };
@@ -141,6 +160,17 @@ fn completed_pruned() {
     assert_eq!(cp[1].status, CheckpointStatus::Complete);
 }
 
+#[test]
+fn get_out_of_bounds() {
+    let mut deps = mock_dependencies();
+    let queue = create_queue_with_status(deps.as_mut().storage, 10, false).unwrap();
+    let err = queue.get(deps.as_ref().storage, 11).unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+
+    let err = query_checkpoint(deps.as_ref().storage, Some(11)).unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+}
+
 #[test]
 fn num_unconfirmed() {
     let mut deps = mock_dependencies();
@@ -171,6 +201,99 @@ fn num_unconfirmed() {
     assert_eq!(queue.num_unconfirmed(deps.as_ref().storage).unwrap(), 10);
 }
 
+#[test]
+fn fee_rate_history() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let queue = create_queue_with_status(deps.as_mut().storage, 5, true)?;
+    for index in 0..5 {
+        let mut checkpoint = queue.get(deps.as_ref().storage, index)?;
+        checkpoint.fee_rate = 10 + index as u64;
+        queue.set(deps.as_mut().storage, index, &checkpoint)?;
+    }
+
+    assert_eq!(
+        query_fee_rate_history(deps.as_ref().storage, 3)?,
+        vec![(4, 14, false), (3, 13, false), (2, 12, false)]
+    );
+
+    CONFIRMED_INDEX.save(deps.as_mut().storage, &3)?;
+    let history = query_fee_rate_history(deps.as_ref().storage, 100)?;
+    assert_eq!(history.len(), 5);
+    assert_eq!(history[0], (4, 14, false));
+    assert_eq!(history[1], (3, 13, true));
+    assert_eq!(history[4], (0, 10, true));
+
+    // the limit defaults to `DEFAULT_FEE_RATE_HISTORY_LIMIT` when omitted
+    let msg: QueryMsg = from_json(br#"{"fee_rate_history":{}}"#)?;
+    assert_eq!(
+        msg,
+        QueryMsg::FeeRateHistory {
+            limit: DEFAULT_FEE_RATE_HISTORY_LIMIT
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn list_checkpoints() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let queue = create_queue_with_status(deps.as_mut().storage, 5, true)?;
+    for index in 0..7 {
+        let mut checkpoint = Checkpoint::new(SignatorySet::default())?;
+        checkpoint.status = queue.get(deps.as_ref().storage, index)?.status;
+        checkpoint.fee_rate = 10 + index as u64;
+        for _ in 0..index {
+            push_bitcoin_tx_output(&mut checkpoint.batches[BatchType::Checkpoint][0], 1000);
+        }
+        queue.set(deps.as_mut().storage, index, &checkpoint)?;
+    }
+    CONFIRMED_INDEX.save(deps.as_mut().storage, &2)?;
+
+    let first_page = query_list_checkpoints(deps.as_ref().storage, None, 4)?;
+    let indexes: Vec<_> = first_page.iter().map(|summary| summary.index).collect();
+    assert_eq!(indexes, vec![6, 5, 4, 3]);
+    assert_eq!(first_page[0].status, CheckpointStatus::Building);
+    assert_eq!(first_page[1].status, CheckpointStatus::Signing);
+    assert_eq!(first_page[2].status, CheckpointStatus::Complete);
+    assert_eq!(first_page[1].fee_rate, 15);
+    assert_eq!(first_page[1].output_count, 5);
+    assert_eq!(first_page[1].input_count, 0);
+    assert!(!first_page[3].confirmed);
+
+    let second_page = query_list_checkpoints(deps.as_ref().storage, Some(3), 4)?;
+    let indexes: Vec<_> = second_page.iter().map(|summary| summary.index).collect();
+    assert_eq!(indexes, vec![2, 1, 0]);
+    assert!(second_page.iter().all(|summary| summary.confirmed));
+
+    assert!(query_list_checkpoints(deps.as_ref().storage, Some(0), 4)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn emergency_disbursal_lock_time() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let queue = create_queue_with_status(deps.as_mut().storage, 5, false)?;
+    let interval = CheckpointConfig::default().emergency_disbursal_lock_time_interval;
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+
+    let err = query_emergency_disbursal_lock_time(deps.as_ref().storage).unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+
+    let mut checkpoint = queue.get(deps.as_ref().storage, 3)?;
+    checkpoint.sigset.create_time = 1_700_000_000;
+    queue.set(deps.as_mut().storage, 3, &checkpoint)?;
+    CONFIRMED_INDEX.save(deps.as_mut().storage, &3)?;
+
+    assert_eq!(
+        query_emergency_disbursal_lock_time(deps.as_ref().storage)?,
+        1_700_000_000 + interval
+    );
+
+    Ok(())
+}
+
 #[test]
 fn first_unconfirmed_index() {
     let mut deps = mock_dependencies();
@@ -263,3 +386,544 @@ fn sigset(n: u32) -> SignatorySet {
 
     sigset
 }
+
+#[test]
+fn deposit_address() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+    BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+    let dest = Dest::Address(Addr::unchecked("bob"));
+
+    // no building checkpoint yet
+    let err = query_deposit_address(deps.as_ref().storage, dest.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+
+    CHECKPOINTS.push_back(deps.as_mut().storage, &Checkpoint::new(sigset(3))?)?;
+    let res = query_deposit_address(deps.as_ref().storage, dest.clone())?;
+    let expected_script = sigset(3).output_script(
+        &dest.commitment_bytes()?,
+        CheckpointConfig::default().sigset_threshold,
+    )?;
+    let expected_address =
+        bitcoin::Address::from_script(&expected_script, bitcoin::Network::Bitcoin).unwrap();
+    assert_eq!(res.sigset_index, 3);
+    assert_eq!(res.script.into_inner(), expected_script);
+    assert_eq!(res.address, expected_address.to_string());
+
+    // IBC memos for packet-forward-middleware must be valid JSON
+    IBC_CHANNEL_ALLOWLIST.save(deps.as_mut().storage, &vec!["channel-0".to_string()])?;
+    let mut ibc_dest = IbcDest {
+        source_port: "transfer".to_string(),
+        source_channel: "channel-0".to_string(),
+        receiver: "receiver".to_string(),
+        sender: "sender".to_string(),
+        timeout_timestamp: 10,
+        memo: r#"{"forward":{"receiver":"osmo1receiver","port":"transfer","channel":"channel-1"}}"#
+            .to_string(),
+    };
+    query_deposit_address(deps.as_ref().storage, Dest::Ibc(ibc_dest.clone()))?;
+
+    ibc_dest.memo = "not json".to_string();
+    let err = query_deposit_address(deps.as_ref().storage, Dest::Ibc(ibc_dest)).unwrap_err();
+    assert!(matches!(err, ContractError::Ibc(_)));
+
+    Ok(())
+}
+
+#[test]
+fn signatory_set_by_index() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+    BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+
+    let mut sigset = sigset(0);
+    sigset.signatories.push(Signatory {
+        pubkey: sigset(1).signatories[0].pubkey.clone(),
+        voting_power: 50,
+    });
+    sigset.possible_vp = 150;
+    sigset.present_vp = 150;
+    CHECKPOINTS.push_back(deps.as_mut().storage, &Checkpoint::new(sigset.clone())?)?;
+
+    let res = query_signatory_set(deps.as_ref().storage, 0)?;
+    assert_eq!(res.index, 0);
+    assert_eq!(res.threshold, CheckpointConfig::default().sigset_threshold);
+    assert_eq!(res.signatories.len(), 2);
+    assert_eq!(
+        res.signatories[0].pubkey.as_slice(),
+        sigset.signatories[0].pubkey.as_slice()
+    );
+    let total: u64 = res.signatories.iter().map(|s| s.voting_power).sum();
+    assert_eq!(total, 150);
+    assert_eq!(res.present_vp, total);
+
+    let err = query_signatory_set(deps.as_ref().storage, 1).unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+
+    Ok(())
+}
+
+#[test]
+fn estimate_withdrawal_fee() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    BITCOIN_CONFIG.save(deps.as_mut().storage, &BitcoinConfig::default())?;
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+    BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+    CHECKPOINTS.push_back(deps.as_mut().storage, &Checkpoint::new(sigset(0))?)?;
+
+    let units_per_sat = BitcoinConfig::default().units_per_sat as u128;
+    let amount = Uint128::from(100_000 * units_per_sat);
+
+    // (9 + 22) vbytes * 55 sat/vb * 2.7
+    let p2wpkh = Binary::from([&[0, 20][..], &[1; 20]].concat());
+    let res = query_estimate_withdrawal_fee(deps.as_ref().storage, p2wpkh, amount)?;
+    assert_eq!(res.gross_amount, amount);
+    assert_eq!(res.fee, Uint128::from(4_603 * units_per_sat));
+    assert_eq!(res.net_amount, Uint128::from(95_397 * units_per_sat));
+
+    // (9 + 34) vbytes * 55 sat/vb * 2.7
+    let p2wsh = Binary::from([&[0, 32][..], &[1; 32]].concat());
+    let res = query_estimate_withdrawal_fee(deps.as_ref().storage, p2wsh.clone(), amount)?;
+    assert_eq!(res.fee, Uint128::from(6_385 * units_per_sat));
+    assert_eq!(res.net_amount, Uint128::from(93_615 * units_per_sat));
+
+    let err = query_estimate_withdrawal_fee(
+        deps.as_ref().storage,
+        p2wsh,
+        Uint128::from(10_000 * units_per_sat),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "App Error: Withdrawal is smaller than minimum amount"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn update_checkpoint_config_auth() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    CONFIG.save(
+        deps.as_mut().storage,
+        &Config {
+            token_factory_addr: Addr::unchecked("token_factory"),
+            owner: Addr::unchecked("owner"),
+            bridge_wasm_addr: None,
+        },
+    )?;
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+
+    let config = CheckpointConfig {
+        max_inputs: 20,
+        target_checkpoint_inclusion: 3,
+        ..Default::default()
+    };
+
+    let err = update_checkpoint_config(
+        deps.as_mut().storage,
+        mock_info("stranger", &[]),
+        config.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    assert_eq!(
+        CHECKPOINT_CONFIG.load(deps.as_ref().storage)?,
+        CheckpointConfig::default()
+    );
+
+    update_checkpoint_config(
+        deps.as_mut().storage,
+        mock_info("owner", &[]),
+        config.clone(),
+    )?;
+    assert_eq!(CHECKPOINT_CONFIG.load(deps.as_ref().storage)?, config);
+
+    let invalid = CheckpointConfig {
+        min_fee_rate: 100,
+        max_fee_rate: 10,
+        ..Default::default()
+    };
+    let err = update_checkpoint_config(deps.as_mut().storage, mock_info("owner", &[]), invalid)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+
+    let invalid = CheckpointConfig {
+        sigset_threshold: (2, 0),
+        ..Default::default()
+    };
+    let err = update_checkpoint_config(deps.as_mut().storage, mock_info("owner", &[]), invalid)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+    assert_eq!(CHECKPOINT_CONFIG.load(deps.as_ref().storage)?, config);
+
+    Ok(())
+}
+
+#[test]
+fn migrate_validates_config() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    cw2::set_contract_version(deps.as_mut().storage, "crates.io:cw_bitcoin", "0.0.1")?;
+    BITCOIN_CONFIG.save(deps.as_mut().storage, &BitcoinConfig::default())?;
+    CHECKPOINT_CONFIG.save(
+        deps.as_mut().storage,
+        &CheckpointConfig {
+            min_fee_rate: 100,
+            max_fee_rate: 10,
+            ..Default::default()
+        },
+    )?;
+
+    let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+    assert_eq!(err.to_string(), "min_fee_rate must not exceed max_fee_rate");
+    assert_eq!(
+        cw2::get_contract_version(deps.as_ref().storage)?.version,
+        "0.0.1"
+    );
+
+    assert_eq!(STORED_SCHEMA_VERSION.may_load(deps.as_ref().storage)?, None);
+
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {})?;
+    assert_eq!(
+        cw2::get_contract_version(deps.as_ref().storage)?.version,
+        env!("CARGO_PKG_VERSION")
+    );
+    assert_eq!(
+        STORED_SCHEMA_VERSION.load(deps.as_ref().storage)?,
+        SCHEMA_VERSION
+    );
+    let attr = |key: &str| {
+        res.attributes
+            .iter()
+            .find(|attr| attr.key == key)
+            .map(|attr| attr.value.clone())
+    };
+    assert_eq!(attr("original_version"), Some("0.0.1".to_string()));
+    assert_eq!(
+        attr("new_version"),
+        Some(env!("CARGO_PKG_VERSION").to_string())
+    );
+    assert_eq!(attr("schema_version"), Some(SCHEMA_VERSION.to_string()));
+
+    // state written by a newer schema can't be migrated back
+    cw2::set_contract_version(deps.as_mut().storage, "crates.io:cw_bitcoin", "0.0.1")?;
+    STORED_SCHEMA_VERSION.save(deps.as_mut().storage, &(SCHEMA_VERSION + 1))?;
+    assert!(migrate(deps.as_mut(), mock_env(), MigrateMsg {}).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn dust_reserve_is_dropped() -> ContractResult<()> {
+    let advance_with_leftover = |leftover: u64| -> ContractResult<_> {
+        let mut deps = mock_dependencies();
+        let config = CheckpointConfig::default();
+        CHECKPOINT_CONFIG.save(deps.as_mut().storage, &config)?;
+        BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+        CHECKPOINTS.push_back(deps.as_mut().storage, &Checkpoint::new(sigset(0))?)?;
+
+        let queue = CheckpointQueue::default();
+        let mut building = queue.building(deps.as_ref().storage)?;
+        let input = Input::new(
+            bitcoin::OutPoint::default(),
+            &building.sigset,
+            &[0u8],
+            100_000,
+            config.sigset_threshold,
+        )?;
+        let checkpoint_tx = &mut building.batches[BatchType::Checkpoint][0];
+        checkpoint_tx.input.push(input);
+        // a withdrawal taking everything but the fee and `leftover`
+        push_bitcoin_tx_output(checkpoint_tx, 100_000 - 1_000 - leftover);
+
+        let (reserve_outpoints, ..) = building.advance(vec![0; 32], 1_000, &config)?;
+        Ok((reserve_outpoints, Checkpoint::clone(&building)))
+    };
+
+    let (reserve_outpoints, checkpoint) = advance_with_leftover(100)?;
+    assert!(reserve_outpoints.is_empty());
+    assert_eq!(checkpoint.fees_collected, 100);
+    assert!(checkpoint.reserve_output()?.is_none());
+    let outputs = checkpoint.checkpoint_tx()?.output.clone();
+    assert_eq!(outputs.len(), 2);
+    assert!(outputs[0].script_pubkey.is_op_return());
+
+    let (reserve_outpoints, checkpoint) = advance_with_leftover(10_000)?;
+    assert_eq!(reserve_outpoints.len(), 1);
+    assert_eq!(reserve_outpoints[0].1, 10_000);
+    assert_eq!(checkpoint.fees_collected, 0);
+    assert_eq!(checkpoint.reserve_output()?.unwrap().value, 10_000);
+    assert_eq!(checkpoint.checkpoint_tx()?.output.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn reserve_output_count() -> ContractResult<()> {
+    let advance_with_reserve = |reserve: u64| -> ContractResult<_> {
+        let mut deps = mock_dependencies();
+        let config = CheckpointConfig {
+            reserve_output_count: 3,
+            ..Default::default()
+        };
+        CHECKPOINT_CONFIG.save(deps.as_mut().storage, &config)?;
+        BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+        CHECKPOINTS.push_back(deps.as_mut().storage, &Checkpoint::new(sigset(0))?)?;
+
+        let queue = CheckpointQueue::default();
+        let mut building = queue.building(deps.as_ref().storage)?;
+        let input = Input::new(
+            bitcoin::OutPoint::default(),
+            &building.sigset,
+            &[0u8],
+            reserve + 1_000,
+            config.sigset_threshold,
+        )?;
+        building.batches[BatchType::Checkpoint][0].input.push(input);
+
+        let (reserve_outpoints, ..) = building.advance(vec![0; 32], 1_000, &config)?;
+        Ok((reserve_outpoints, Checkpoint::clone(&building)))
+    };
+
+    let (reserve_outpoints, checkpoint) = advance_with_reserve(100_001)?;
+    let values = reserve_outpoints
+        .iter()
+        .map(|(_, value)| *value)
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec![33_335, 33_333, 33_333]);
+    let txid = checkpoint.checkpoint_tx()?.txid();
+    for (vout, (outpoint, _)) in reserve_outpoints.iter().enumerate() {
+        assert_eq!(*outpoint, bitcoin::OutPoint::new(txid, vout as u32));
+    }
+    let outputs = checkpoint.reserve_outputs()?;
+    assert_eq!(
+        outputs
+            .iter()
+            .map(|output| output.value)
+            .collect::<Vec<_>>(),
+        values
+    );
+    assert_eq!(checkpoint.reserve_value()?, 100_001);
+    assert_eq!(checkpoint.checkpoint_tx()?.output.len(), 4);
+
+    // shares below the dust limit use fewer outputs
+    let dust = outputs[0].script_pubkey.dust_value().to_sat();
+    let (reserve_outpoints, checkpoint) = advance_with_reserve(dust * 2)?;
+    assert_eq!(reserve_outpoints.len(), 2);
+    assert_eq!(checkpoint.reserve_value()?, dust * 2);
+    assert_eq!(checkpoint.checkpoint_tx()?.output.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn input_selection() -> ContractResult<()> {
+    let advance_with =
+        |input_selection: InputSelection, largest_first_fee_rate: u64| -> ContractResult<_> {
+            let mut deps = mock_dependencies();
+            let config = CheckpointConfig {
+                max_inputs: 3,
+                input_selection,
+                largest_first_fee_rate,
+                ..Default::default()
+            };
+            CHECKPOINT_CONFIG.save(deps.as_mut().storage, &config)?;
+            BUILDING_INDEX.save(deps.as_mut().storage, &0)?;
+            CHECKPOINTS.push_back(deps.as_mut().storage, &Checkpoint::new(sigset(0))?)?;
+
+            let queue = CheckpointQueue::default();
+            let mut building = queue.building(deps.as_ref().storage)?;
+            let reserve = Input::new(
+                bitcoin::OutPoint::default(),
+                &building.sigset,
+                &[0u8],
+                1_000_000,
+                config.sigset_threshold,
+            )?;
+            let checkpoint_tx = &mut building.batches[BatchType::Checkpoint][0];
+            checkpoint_tx.input.push(reserve);
+            for amount in [50_000, 10_000, 40_000, 20_000, 30_000] {
+                let input = Input::new(
+                    bitcoin::OutPoint::default(),
+                    &building.sigset,
+                    &[1u8],
+                    amount,
+                    config.sigset_threshold,
+                )?;
+                building.batches[BatchType::Checkpoint][0].input.push(input);
+            }
+
+            let (_, _, excess_inputs, _) = building.advance(vec![0; 32], 1_000, &config)?;
+            let amounts =
+                |inputs: &[Input]| inputs.iter().map(|input| input.amount).collect::<Vec<_>>();
+            Ok((
+                amounts(&building.batches[BatchType::Checkpoint][0].input),
+                amounts(&excess_inputs),
+            ))
+        };
+
+    let (spent, excess) = advance_with(InputSelection::Oldest, 0)?;
+    assert_eq!(spent, vec![1_000_000, 50_000, 10_000]);
+    assert_eq!(excess, vec![30_000, 20_000, 40_000]);
+
+    let (spent, excess) = advance_with(InputSelection::SmallestFirst, 0)?;
+    assert_eq!(spent, vec![1_000_000, 10_000, 20_000]);
+    assert_eq!(excess, vec![50_000, 40_000, 30_000]);
+
+    let (spent, excess) = advance_with(InputSelection::LargestFirst, 0)?;
+    assert_eq!(spent, vec![1_000_000, 50_000, 40_000]);
+    assert_eq!(excess, vec![10_000, 20_000, 30_000]);
+
+    // above the threshold fee rate the largest inputs are spent first
+    let (spent, _) = advance_with(InputSelection::SmallestFirst, DEFAULT_FEE_RATE - 1)?;
+    assert_eq!(spent, vec![1_000_000, 50_000, 40_000]);
+    let (spent, _) = advance_with(InputSelection::SmallestFirst, DEFAULT_FEE_RATE)?;
+    assert_eq!(spent, vec![1_000_000, 10_000, 20_000]);
+
+    Ok(())
+}
+
+#[test]
+fn checkpoint_queue_status() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &CheckpointConfig::default())?;
+    let queue = create_queue_with_status(deps.as_mut().storage, 4, true)?;
+    CONFIRMED_INDEX.save(deps.as_mut().storage, &1)?;
+    FIRST_UNHANDLED_CONFIRMED_INDEX.save(deps.as_mut().storage, &1)?;
+
+    let mut building = Checkpoint::new(sigset(5))?;
+    let checkpoint_tx = &mut building.batches[BatchType::Checkpoint][0];
+    checkpoint_tx.input.push(Input::new(
+        bitcoin::OutPoint::default(),
+        &sigset(5),
+        &[0u8],
+        100_000,
+        CheckpointConfig::default().sigset_threshold,
+    )?);
+    push_bitcoin_tx_output(checkpoint_tx, 1_000);
+    push_bitcoin_tx_output(checkpoint_tx, 2_000);
+    queue.set(deps.as_mut().storage, 5, &building)?;
+
+    let status = query_checkpoint_queue_status(deps.as_ref().storage)?;
+    assert_eq!(status.index, 5);
+    assert_eq!(status.confirmed_index, Some(1));
+    assert_eq!(status.first_unhandled_confirmed_cp_index, 1);
+    // checkpoints 2 and 3 are complete but unconfirmed, 4 is still signing
+    assert_eq!(status.num_unconfirmed, 2);
+    assert_eq!(
+        status.max_unconfirmed_checkpoints,
+        CheckpointConfig::default().max_unconfirmed_checkpoints
+    );
+    assert_eq!(status.building_inputs, 1);
+    assert_eq!(status.building_outputs, 2);
+
+    Ok(())
+}
+
+#[test]
+fn halts_at_max_unconfirmed_checkpoints() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    CHECKPOINT_CONFIG.save(
+        deps.as_mut().storage,
+        &CheckpointConfig {
+            max_unconfirmed_checkpoints: 3,
+            ..Default::default()
+        },
+    )?;
+    let queue = create_queue_with_status(deps.as_mut().storage, 2, false)?;
+    assert!(!queue.halted(deps.as_ref().storage)?);
+
+    let mut queue = create_queue_with_status(deps.as_mut().storage, 3, false)?;
+    assert!(queue.halted(deps.as_ref().storage)?);
+
+    // even long after the max checkpoint interval, nothing is pushed
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(1_000_000);
+    assert!(!queue.should_push(env, deps.as_ref().storage, &[0u8], 0)?);
+    assert_eq!(queue.index(deps.as_ref().storage), 3);
+
+    // once a checkpoint confirms, the queue resumes
+    CONFIRMED_INDEX.save(deps.as_mut().storage, &0)?;
+    assert_eq!(queue.num_unconfirmed(deps.as_ref().storage)?, 2);
+    assert!(!queue.halted(deps.as_ref().storage)?);
+
+    Ok(())
+}
+
+#[test]
+fn halts_when_fee_rate_ceiling_stuck() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let config = CheckpointConfig {
+        max_fee_rate_checkpoints: 3,
+        ..Default::default()
+    };
+    CHECKPOINT_CONFIG.save(deps.as_mut().storage, &config)?;
+    let queue = create_queue_with_status(deps.as_mut().storage, 3, false)?;
+
+    let mut fee_rate = config.min_fee_rate;
+    for index in 0..3 {
+        assert!(!queue.fee_rate_ceiling_stuck(deps.as_ref().storage)?);
+        assert!(!queue.halted(deps.as_ref().storage)?);
+
+        // checkpoints keep going unconfirmed until the fee rate is pinned
+        while fee_rate < config.max_fee_rate {
+            fee_rate = adjust_fee_rate(fee_rate, true, &config);
+        }
+        let mut checkpoint = queue.get(deps.as_ref().storage, index)?;
+        checkpoint.fee_rate = fee_rate;
+        queue.set(deps.as_mut().storage, index, &checkpoint)?;
+    }
+    assert_eq!(fee_rate, config.max_fee_rate);
+    assert!(queue.fee_rate_ceiling_stuck(deps.as_ref().storage)?);
+    assert!(queue.halted(deps.as_ref().storage)?);
+
+    // once a checkpoint confirms, the queue resumes
+    CONFIRMED_INDEX.save(deps.as_mut().storage, &0)?;
+    assert!(!queue.halted(deps.as_ref().storage)?);
+
+    Ok(())
+}
+
+#[test]
+fn prune_checkpoints() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    let store = deps.as_mut().storage;
+    CONFIG.save(
+        store,
+        &Config {
+            token_factory_addr: Addr::unchecked("token_factory"),
+            owner: Addr::unchecked("owner"),
+            bridge_wasm_addr: None,
+        },
+    )?;
+    let config = CheckpointConfig::default();
+    CHECKPOINT_CONFIG.save(store, &config)?;
+    let queue = create_queue_with_status(store, 20, false)?;
+    FIRST_UNHANDLED_CONFIRMED_INDEX.save(store, &5)?;
+
+    // every checkpoint but the building one is past `max_age`
+    let mut building = queue.get(store, 20)?;
+    building.sigset.create_time = config.max_age + 1;
+    queue.set(store, 20, &building)?;
+
+    let err = prune_checkpoints(store, mock_info("stranger", &[])).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    // checkpoints whose transfers have not been handled are kept
+    let res = prune_checkpoints(store, mock_info("owner", &[]))?;
+    assert_eq!(res.attributes[1].value, "5");
+    assert_eq!(queue.first_index(store)?, 5);
+
+    // at least 10 checkpoints are kept
+    FIRST_UNHANDLED_CONFIRMED_INDEX.save(store, &20)?;
+    let res = prune_checkpoints(store, mock_info("owner", &[]))?;
+    assert_eq!(res.attributes[1].value, "6");
+    assert_eq!(queue.len(store)?, 10);
+    assert_eq!(queue.first_index(store)?, 11);
+
+    let res = prune_checkpoints(store, mock_info("owner", &[]))?;
+    assert_eq!(res.attributes[1].value, "0");
+
+    Ok(())
+}
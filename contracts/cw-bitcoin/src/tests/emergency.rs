@@ -0,0 +1,234 @@
+use bitcoin::{hashes::Hash, OutPoint, PubkeyHash, Script, TxOut};
+use cosmwasm_std::{
+    testing::{mock_dependencies, MockApi, MockQuerier, MockStorage},
+    Addr, Coin, OwnedDeps, Storage,
+};
+
+use crate::{
+    adapter::Adapter,
+    app::Bitcoin,
+    checkpoint::{BatchType, Checkpoint},
+    entrypoints::query_emergency_disbursal_txs,
+    error::{ContractError, ContractResult},
+    interface::{BitcoinConfig, CheckpointConfig, Config},
+    signatory::{Signatory, SignatorySet},
+    state::{
+        get_full_btc_denom, BITCOIN_CONFIG, CHECKPOINTS, CHECKPOINT_CONFIG, CONFIG,
+        CONFIRMED_INDEX, RECOVERY_SCRIPTS,
+    },
+    threshold_sig::Pubkey,
+};
+
+type MockDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+fn sigset(n: u32) -> SignatorySet {
+    let mut sigset = SignatorySet::default();
+    sigset.index = n;
+    sigset.create_time = n as u64;
+
+    let secret = bitcoin::secp256k1::SecretKey::from_slice(&[(n + 1) as u8; 32]).unwrap();
+    let pubkey: Pubkey = bitcoin::secp256k1::PublicKey::from_secret_key(
+        &bitcoin::secp256k1::Secp256k1::new(),
+        &secret,
+    )
+    .into();
+
+    sigset.signatories.push(Signatory {
+        pubkey: pubkey.into(),
+        voting_power: 100,
+    });
+
+    sigset.possible_vp = 100;
+    sigset.present_vp = 100;
+
+    sigset
+}
+
+fn recovery_script(n: u8) -> Script {
+    Script::new_p2pkh(&PubkeyHash::from_inner([n; 20]))
+}
+
+fn reserve_script() -> ContractResult<Script> {
+    sigset(0).output_script(&[0u8], CheckpointConfig::default().sigset_threshold)
+}
+
+/// Confirms a checkpoint holding `reserve` satoshis and gives account `i` a
+/// balance of `balances[i]` satoshis and the recovery script
+/// `recovery_script(i)`.
+fn setup(deps: &mut MockDeps, reserve: u64, balances: &[u64]) -> ContractResult<()> {
+    let store: &mut dyn Storage = &mut deps.storage;
+    CONFIG.save(
+        store,
+        &Config {
+            token_factory_addr: Addr::unchecked("token_factory"),
+            owner: Addr::unchecked("owner"),
+            bridge_wasm_addr: None,
+        },
+    )?;
+    BITCOIN_CONFIG.save(store, &BitcoinConfig::default())?;
+    CHECKPOINT_CONFIG.save(store, &CheckpointConfig::default())?;
+
+    let mut checkpoint = Checkpoint::new(sigset(0))?;
+    checkpoint.batches[BatchType::Checkpoint][0]
+        .output
+        .push(Adapter::new(TxOut {
+            value: reserve,
+            script_pubkey: reserve_script()?,
+        }));
+    CHECKPOINTS.push_back(store, &checkpoint)?;
+    CONFIRMED_INDEX.save(store, &0)?;
+
+    let denom = get_full_btc_denom(store)?;
+    let units_per_sat = BitcoinConfig::default().units_per_sat as u128;
+    for (i, balance) in balances.iter().enumerate() {
+        let address = format!("account{:03}", i);
+        RECOVERY_SCRIPTS.save(
+            &mut deps.storage,
+            &address,
+            &Adapter::new(recovery_script(i as u8)),
+        )?;
+        deps.querier.update_balance(
+            address,
+            vec![Coin::new(*balance as u128 * units_per_sat, &denom)],
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn emergency_disbursal_txs() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    // account 1 is below the dust limit, account 3 is above it but below
+    // emergency_disbursal_min_tx_amt
+    setup(&mut deps, 10_000_000, &[100_000, 500, 200_000, 600])?;
+
+    let btc = Bitcoin::default();
+    let txs = btc.emergency_disbursal_txs(deps.as_ref().querier, deps.as_ref().storage)?;
+    assert_eq!(txs.len(), 1);
+    let tx = &txs[0];
+
+    // the checkpoint was created at time 0
+    let lock_time = CheckpointConfig::default().emergency_disbursal_lock_time_interval;
+    assert_eq!(tx.lock_time.0 as u64, lock_time);
+
+    assert_eq!(tx.input.len(), 1);
+    let outputs: Vec<_> = tx
+        .output
+        .iter()
+        .map(|output| (output.script_pubkey.clone(), output.value))
+        .collect();
+    assert_eq!(outputs.len(), 3);
+    assert_eq!(
+        outputs[..2],
+        [(recovery_script(0), 100_000), (recovery_script(2), 200_000)]
+    );
+    assert_eq!(outputs[2].0, reserve_script()?);
+
+    let mut config = CheckpointConfig::default();
+    config.emergency_disbursal_min_tx_amt = 0;
+    CHECKPOINT_CONFIG.save(&mut deps.storage, &config)?;
+    let txs = btc.emergency_disbursal_txs(deps.as_ref().querier, deps.as_ref().storage)?;
+    assert_eq!(txs[0].output.len(), 4);
+    assert_eq!(txs[0].output[2].script_pubkey, recovery_script(3));
+
+    Ok(())
+}
+
+#[test]
+fn emergency_disbursal_max_tx_size() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    setup(&mut deps, 10_000_000, &[10_000; 20])?;
+
+    let mut config = CheckpointConfig::default();
+    config.emergency_disbursal_max_tx_size = 400;
+    CHECKPOINT_CONFIG.save(&mut deps.storage, &config)?;
+
+    let txs =
+        Bitcoin::default().emergency_disbursal_txs(deps.as_ref().querier, deps.as_ref().storage)?;
+    assert!(txs.len() > 1);
+    // every transaction has a change output after the accounts it pays
+    let paid: usize = txs.iter().map(|tx| tx.output.len() - 1).sum();
+    assert_eq!(paid, 20);
+
+    Ok(())
+}
+
+#[test]
+fn emergency_disbursal_txs_pagination() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    setup(&mut deps, 100_000_000, &[10_000; 50])?;
+
+    let mut config = CheckpointConfig::default();
+    config.emergency_disbursal_max_tx_size = 400;
+    CHECKPOINT_CONFIG.save(&mut deps.storage, &config)?;
+
+    let deps = deps.as_ref();
+    let all = query_emergency_disbursal_txs(deps.querier, deps.storage, None, 100)?;
+    assert!(all.iter().zip(0..).all(|(tx, index)| tx.index == index));
+    let first = query_emergency_disbursal_txs(deps.querier, deps.storage, None, 3)?;
+    let second = query_emergency_disbursal_txs(deps.querier, deps.storage, Some(2), 100)?;
+    assert_eq!(first.len(), 3);
+    assert!(!second.is_empty());
+    assert_eq!([first, second].concat(), all);
+
+    // every account is paid exactly once across the two pages, in address
+    // order
+    let reserve_script = reserve_script()?;
+    let paid: Vec<_> = all
+        .iter()
+        .flat_map(|response| response.tx.output.iter())
+        .map(|output| output.script_pubkey.clone())
+        .filter(|script| *script != reserve_script)
+        .collect();
+    assert_eq!(paid, (0..50).map(recovery_script).collect::<Vec<_>>());
+
+    Ok(())
+}
+
+#[test]
+fn emergency_disbursal_chain() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    setup(&mut deps, 10_000_000, &[10_000; 20])?;
+
+    let mut config = CheckpointConfig::default();
+    config.emergency_disbursal_max_tx_size = 400;
+    CHECKPOINT_CONFIG.save(&mut deps.storage, &config)?;
+
+    let deps = deps.as_ref();
+    let txs = query_emergency_disbursal_txs(deps.querier, deps.storage, None, 100)?;
+    assert!(txs.len() > 1);
+    for pair in txs.windows(2) {
+        let (prev, next) = (&pair[0], &pair[1]);
+        assert_eq!(next.index, prev.index + 1);
+
+        let change = prev.tx.output.len() - 1;
+        assert_eq!(prev.tx.output[change].script_pubkey, reserve_script()?);
+        assert_eq!(next.tx.input.len(), 1);
+        assert_eq!(
+            next.tx.input[0].previous_output,
+            OutPoint::new(prev.tx.txid(), change as u32)
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn emergency_disbursal_insufficient_reserve() -> ContractResult<()> {
+    let mut deps = mock_dependencies();
+    setup(&mut deps, 100_000, &[100_000])?;
+
+    let err = Bitcoin::default()
+        .emergency_disbursal_txs(deps.as_ref().querier, deps.as_ref().storage)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+
+    CONFIRMED_INDEX.remove(&mut deps.storage);
+    let err = Bitcoin::default()
+        .emergency_disbursal_txs(deps.as_ref().querier, deps.as_ref().storage)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Checkpoint(_)));
+
+    Ok(())
+}
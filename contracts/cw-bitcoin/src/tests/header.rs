@@ -1,15 +1,22 @@
 use bitcoin::consensus::Decodable;
 use bitcoin::hashes::hex::FromHex;
 use bitcoin::hashes::sha256d::Hash;
+use bitcoin::hashes::Hash as _;
+use bitcoin::util::uint::Uint256;
 use bitcoin::BlockHash;
 use bitcoin::{hash_types::TxMerkleNode, BlockHeader};
 use chrono::{TimeZone, Utc};
 use cosmwasm_std::{from_json, testing::mock_dependencies, to_json_binary, Binary};
+use std::str::FromStr;
 
 use crate::adapter::Adapter;
-use crate::header::{HeaderQueue, WrappedHeader};
+use crate::app::Bitcoin;
+use crate::constants::{MAX_FORK_HEADERS, MAX_REORG_DEPTH};
+use crate::entrypoints::query_header_tip;
+use crate::error::ContractError;
+use crate::header::{HeaderQueue, WorkHeader, WrappedHeader};
 use crate::interface::HeaderConfig;
-use crate::state::{HEADERS, HEADER_CONFIG};
+use crate::state::{FORK_HEADERS, HEADERS, HEADER_CONFIG};
 
 #[test]
 fn primitive_adapter_encode_decode() {
@@ -178,6 +185,7 @@ fn add_multiple() {
 
     let test_config = HeaderConfig {
         max_length: 2000,
+        max_reorg_depth: 100,
         max_time_increase: 8 * 60 * 60,
         trusted_height: 42,
         retarget_interval: 2016,
@@ -186,6 +194,7 @@ fn add_multiple() {
         max_target: 0x1d00ffff,
         retargeting: true,
         min_difficulty_blocks: false,
+        network: bitcoin::Network::Bitcoin,
         trusted_header: BlockHeader {
             version: 1,
             prev_blockhash: BlockHash::from_hex(
@@ -239,6 +248,7 @@ fn add_into_iterator() {
 
     let test_config = HeaderConfig {
         max_length: 2000,
+        max_reorg_depth: 100,
         max_time_increase: 8 * 60 * 60,
         trusted_height: 42,
         retarget_interval: 2016,
@@ -247,6 +257,7 @@ fn add_into_iterator() {
         max_target: 0x1d00ffff,
         retargeting: true,
         min_difficulty_blocks: false,
+        network: bitcoin::Network::Bitcoin,
         trusted_header: BlockHeader {
             version: 1,
             prev_blockhash: Hash::from_hex(
@@ -302,6 +313,7 @@ fn add_wrong_bits_non_retarget() {
 
     let test_config = HeaderConfig {
         max_length: 2000,
+        max_reorg_depth: 100,
         max_time_increase: 8 * 60 * 60,
         trusted_height: 42,
         retarget_interval: 2016,
@@ -310,6 +322,7 @@ fn add_wrong_bits_non_retarget() {
         max_target: 0x1d00ffff,
         retargeting: true,
         min_difficulty_blocks: false,
+        network: bitcoin::Network::Bitcoin,
         trusted_header: BlockHeader {
             version: 1,
             prev_blockhash: Hash::from_hex(
@@ -342,3 +355,432 @@ fn add_wrong_bits_non_retarget() {
     q.configure(deps.as_mut().storage, test_config).unwrap();
     q.add_into_iter(deps.as_mut().storage, header_list).unwrap();
 }
+
+/// A header config trusting a regtest-difficulty header at the given height,
+/// so that test chains can be mined on the fly.
+pub(super) fn regtest_config(trusted_height: u32) -> HeaderConfig {
+    HeaderConfig {
+        max_length: 2000,
+        max_reorg_depth: 5,
+        max_time_increase: 8 * 60 * 60,
+        trusted_height,
+        retarget_interval: 2016,
+        target_spacing: 10 * 60,
+        target_timespan: 2016 * (10 * 60),
+        max_target: 0x207fffff,
+        retargeting: true,
+        min_difficulty_blocks: false,
+        network: bitcoin::Network::Bitcoin,
+        trusted_header: BlockHeader {
+            version: 1,
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 1_600_000_000,
+            bits: 0x207fffff,
+            nonce: 0,
+        }
+        .into(),
+    }
+}
+
+/// Finds a nonce giving the header valid proof-of-work for its own target.
+fn mine_header(prev_blockhash: BlockHash, time: u32, bits: u32) -> BlockHeader {
+    let mut header = BlockHeader {
+        version: 1,
+        prev_blockhash,
+        merkle_root: TxMerkleNode::all_zeros(),
+        time,
+        bits,
+        nonce: 0,
+    };
+    while header.validate_pow(&header.target()).is_err() {
+        header.nonce += 1;
+    }
+    header
+}
+
+/// Mines `count` consecutive headers on top of `prev`.
+fn mine_headers(prev: &WrappedHeader, count: u32) -> Vec<WrappedHeader> {
+    mine_headers_spaced(prev, count, 600)
+}
+
+/// Mines `count` consecutive headers on top of `prev`, `spacing` seconds
+/// apart.
+fn mine_headers_spaced(prev: &WrappedHeader, count: u32, spacing: u32) -> Vec<WrappedHeader> {
+    let mut headers = vec![];
+    let mut prev = prev.clone();
+    for _ in 0..count {
+        let header = mine_header(prev.block_hash(), prev.time() + spacing, prev.bits());
+        let wrapped = WrappedHeader::from_header(&header, prev.height() + 1);
+        headers.push(wrapped.clone());
+        prev = wrapped;
+    }
+    headers
+}
+
+#[test]
+fn relay_header_batch() {
+    let mut deps = mock_dependencies();
+    let config = regtest_config(1000);
+    let trusted = config.work_header().header;
+    HeaderQueue::default()
+        .configure(deps.as_mut().storage, config)
+        .unwrap();
+
+    let headers = mine_headers(&trusted, 10);
+    let mut btc = Bitcoin::default();
+    btc.relay_headers(deps.as_mut().storage, headers.clone())
+        .unwrap();
+
+    assert_eq!(btc.headers.height(deps.as_ref().storage).unwrap(), 1010);
+    assert_eq!(
+        btc.headers.hash(deps.as_ref().storage).unwrap(),
+        headers.last().unwrap().block_hash()
+    );
+}
+
+#[test]
+fn relay_header_batch_broken_link() {
+    let mut deps = mock_dependencies();
+    let config = regtest_config(1000);
+    let trusted = config.work_header().header;
+    HeaderQueue::default()
+        .configure(deps.as_mut().storage, config)
+        .unwrap();
+
+    let mut headers = mine_headers(&trusted, 10);
+    let unlinked = mine_header(BlockHash::all_zeros(), headers[5].time() + 1, 0x207fffff);
+    headers[6] = WrappedHeader::from_header(&unlinked, 1006);
+
+    let mut btc = Bitcoin::default();
+    let err = btc
+        .relay_headers(deps.as_mut().storage, headers)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Header(_)));
+
+    // nothing from the batch was added
+    assert_eq!(btc.headers.height(deps.as_ref().storage).unwrap(), 1000);
+}
+
+/// Mines `count` headers on top of `prev` which compete with any chain
+/// already mined from it by [`mine_headers`].
+fn mine_fork(prev: &WrappedHeader, count: u32) -> Vec<WrappedHeader> {
+    let header = mine_header(prev.block_hash(), prev.time() + 1, prev.bits());
+    let first = WrappedHeader::from_header(&header, prev.height() + 1);
+    let mut headers = vec![first.clone()];
+    headers.extend(mine_headers(&first, count - 1));
+    headers
+}
+
+#[test]
+fn shallow_reorg() {
+    let mut deps = mock_dependencies();
+    let config = regtest_config(1000);
+    let trusted = config.work_header().header;
+    let mut q = HeaderQueue::default();
+    q.configure(deps.as_mut().storage, config).unwrap();
+
+    let headers = mine_headers(&trusted, 10);
+    q.add_into_iter(deps.as_mut().storage, headers.clone())
+        .unwrap();
+
+    // replace the last 2 headers with a longer chain of 3
+    let fork = mine_fork(&headers[7], 3);
+    q.add_into_iter(deps.as_mut().storage, fork.clone())
+        .unwrap();
+
+    assert_eq!(q.height(deps.as_ref().storage).unwrap(), 1011);
+    assert_eq!(
+        q.hash(deps.as_ref().storage).unwrap(),
+        fork.last().unwrap().block_hash()
+    );
+}
+
+#[test]
+fn reorg_to_stored_fork() {
+    let mut deps = mock_dependencies();
+    let config = regtest_config(1000);
+    let trusted = config.work_header().header;
+    let mut q = HeaderQueue::default();
+    q.configure(deps.as_mut().storage, config).unwrap();
+
+    let headers = mine_headers(&trusted, 10);
+    q.add_into_iter(deps.as_mut().storage, headers.clone())
+        .unwrap();
+
+    // a fork with as much work as the last 2 headers is stored, but the tip
+    // stays on the current chain
+    let fork = mine_fork(&headers[7], 3);
+    q.add_into_iter(deps.as_mut().storage, fork[..2].to_vec())
+        .unwrap();
+    assert_eq!(q.height(deps.as_ref().storage).unwrap(), 1010);
+    assert_eq!(
+        q.hash(deps.as_ref().storage).unwrap(),
+        headers.last().unwrap().block_hash()
+    );
+    assert!(FORK_HEADERS.has(deps.as_ref().storage, &fork[1].block_hash().to_string()));
+
+    // extending the fork past the current chain's work switches the tip
+    q.add_into_iter(deps.as_mut().storage, fork[2..].to_vec())
+        .unwrap();
+    assert_eq!(q.height(deps.as_ref().storage).unwrap(), 1011);
+    assert_eq!(
+        q.hash(deps.as_ref().storage).unwrap(),
+        fork.last().unwrap().block_hash()
+    );
+    assert_eq!(
+        q.get_by_height(deps.as_ref().storage, 1009)
+            .unwrap()
+            .unwrap()
+            .block_hash(),
+        fork[0].block_hash()
+    );
+    assert!(!FORK_HEADERS.has(deps.as_ref().storage, &fork[1].block_hash().to_string()));
+
+    // headers which connect to neither the queue nor a stored fork are
+    // rejected
+    let unlinked = mine_header(BlockHash::all_zeros(), fork[2].time() + 1, 0x207fffff);
+    let err = q
+        .add_into_iter(
+            deps.as_mut().storage,
+            vec![WrappedHeader::from_header(&unlinked, 1012)],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Header(_)));
+}
+
+#[test]
+fn stored_forks_checked_and_capped() {
+    let mut deps = mock_dependencies();
+    let config = regtest_config(1000);
+    let trusted = config.work_header().header;
+    let mut q = HeaderQueue::default();
+    q.configure(deps.as_mut().storage, config).unwrap();
+
+    let headers = mine_headers(&trusted, 10);
+    q.add_into_iter(deps.as_mut().storage, headers.clone())
+        .unwrap();
+
+    // a fork header with less work than the chain must still use the
+    // expected target to be stored
+    let prev = &headers[7];
+    let easy = mine_header(prev.block_hash(), prev.time() + 1, 0x2100ffff);
+    let easy = WrappedHeader::from_header(&easy, prev.height() + 1);
+    let err = q
+        .add_into_iter(deps.as_mut().storage, vec![easy.clone()])
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Bitcoin(_)));
+    assert!(!FORK_HEADERS.has(deps.as_ref().storage, &easy.block_hash().to_string()));
+
+    // only so many competing fork headers are kept
+    let fork_header = |i: u32| {
+        let header = mine_header(prev.block_hash(), prev.time() + 1 + i, prev.bits());
+        WrappedHeader::from_header(&header, prev.height() + 1)
+    };
+    for i in 0..MAX_FORK_HEADERS as u32 {
+        q.add_into_iter(deps.as_mut().storage, vec![fork_header(i)])
+            .unwrap();
+    }
+    let err = q
+        .add_into_iter(
+            deps.as_mut().storage,
+            vec![fork_header(MAX_FORK_HEADERS as u32)],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Header(_)));
+    assert_eq!(
+        q.hash(deps.as_ref().storage).unwrap(),
+        headers.last().unwrap().block_hash()
+    );
+}
+
+#[test]
+fn deep_reorg() {
+    let mut deps = mock_dependencies();
+    let config = regtest_config(1000);
+    let trusted = config.work_header().header;
+    let mut q = HeaderQueue::default();
+    q.configure(deps.as_mut().storage, config).unwrap();
+
+    let headers = mine_headers(&trusted, 10);
+    q.add_into_iter(deps.as_mut().storage, headers.clone())
+        .unwrap();
+
+    // replacing the last 6 headers goes past the maximum depth of 5, even
+    // though the fork has more work
+    let fork = mine_fork(&headers[3], 7);
+    let err = q.add_into_iter(deps.as_mut().storage, fork).unwrap_err();
+    assert!(matches!(err, ContractError::Header(_)));
+
+    assert_eq!(q.height(deps.as_ref().storage).unwrap(), 1010);
+    assert_eq!(
+        q.hash(deps.as_ref().storage).unwrap(),
+        headers.last().unwrap().block_hash()
+    );
+}
+
+#[test]
+fn difficulty_retarget() {
+    let mut deps = mock_dependencies();
+    let config = HeaderConfig {
+        max_length: 2100,
+        ..regtest_config(2016)
+    };
+    let trusted = config.work_header().header;
+    let mut q = HeaderQueue::default();
+    q.configure(deps.as_mut().storage, config).unwrap();
+
+    // blocks come twice as fast as targeted for the rest of the period
+    let headers = mine_headers_spaced(&trusted, 2015, 300);
+    q.add_into_iter(deps.as_mut().storage, headers.clone())
+        .unwrap();
+    let last = headers.last().unwrap();
+
+    let timespan = Uint256::from_u64((last.time() - trusted.time()) as u64).unwrap();
+    let target_timespan = Uint256::from_u64(2016 * 600).unwrap();
+    let expected_bits =
+        WrappedHeader::compact_target_from_u256(&(trusted.target() * timespan / target_timespan));
+    assert!(expected_bits < trusted.bits());
+
+    // keeping the previous period's difficulty is too easy
+    let easy = mine_header(last.block_hash(), last.time() + 300, trusted.bits());
+    let err = q
+        .add_into_iter(
+            deps.as_mut().storage,
+            [WrappedHeader::from_header(&easy, 4032)],
+        )
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::Bitcoin(bitcoin::Error::BlockBadTarget)
+    ));
+    assert_eq!(q.height(deps.as_ref().storage).unwrap(), 4031);
+
+    let retargeted = mine_header(last.block_hash(), last.time() + 300, expected_bits);
+    q.add_into_iter(
+        deps.as_mut().storage,
+        [WrappedHeader::from_header(&retargeted, 4032)],
+    )
+    .unwrap();
+    assert_eq!(q.height(deps.as_ref().storage).unwrap(), 4032);
+}
+
+#[test]
+fn median_time_past() {
+    let mut deps = mock_dependencies();
+    let push = |store: &mut dyn cosmwasm_std::Storage, height: u32, time: u32| {
+        let header = BlockHeader {
+            version: 1,
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time,
+            bits: 0x207fffff,
+            nonce: 0,
+        };
+        let work_header = WorkHeader::new(
+            WrappedHeader::from_header(&header, height),
+            Uint256::from_u64(0).unwrap(),
+        );
+        HEADERS.push_back(store, &work_header).unwrap();
+    };
+
+    let q = HeaderQueue::default();
+    let times = [7, 3, 12, 1, 9, 15, 2, 8, 11, 4, 6];
+    for (i, time) in times.iter().take(5).enumerate() {
+        push(deps.as_mut().storage, i as u32 + 1, *time);
+    }
+    // fewer than 11 headers use all of them
+    assert_eq!(q.median_time_past(deps.as_ref().storage).unwrap(), 7);
+
+    for (i, time) in times.iter().enumerate().skip(5) {
+        push(deps.as_mut().storage, i as u32 + 1, *time);
+    }
+    assert_eq!(q.median_time_past(deps.as_ref().storage).unwrap(), 7);
+
+    // only the last 11 headers count, and a far-future timestamp only moves
+    // the median by one position
+    push(deps.as_mut().storage, 12, 100);
+    assert_eq!(q.median_time_past(deps.as_ref().storage).unwrap(), 8);
+}
+
+#[test]
+fn prune_headers() {
+    let mut deps = mock_dependencies();
+    let mut config = regtest_config(1000);
+    config.max_length = 100;
+    let trusted = config.work_header().header;
+    HeaderQueue::default()
+        .configure(deps.as_mut().storage, config.clone())
+        .unwrap();
+
+    let mut btc = Bitcoin::default();
+    btc.relay_headers(deps.as_mut().storage, mine_headers(&trusted, 50))
+        .unwrap();
+    assert_eq!(btc.headers.len(deps.as_ref().storage).unwrap(), 51);
+
+    config.max_length = 10;
+    HEADER_CONFIG.save(deps.as_mut().storage, &config).unwrap();
+
+    // headers from the retained height up are kept even past max_length
+    btc.headers.prune(deps.as_mut().storage, 1030).unwrap();
+    assert_eq!(btc.headers.len(deps.as_ref().storage).unwrap(), 21);
+    assert!(btc
+        .headers
+        .get_by_height(deps.as_ref().storage, 1029)
+        .is_err());
+    assert!(btc
+        .headers
+        .get_by_height(deps.as_ref().storage, 1030)
+        .unwrap()
+        .is_some());
+
+    btc.headers.prune(deps.as_mut().storage, u32::MAX).unwrap();
+    assert_eq!(btc.headers.len(deps.as_ref().storage).unwrap(), 10);
+    assert_eq!(btc.headers.height(deps.as_ref().storage).unwrap(), 1050);
+    assert!(btc
+        .headers
+        .get_by_height(deps.as_ref().storage, 1040)
+        .is_err());
+}
+
+#[test]
+fn header_tip() {
+    let mut deps = mock_dependencies();
+    let config = regtest_config(1000);
+    let trusted = config.work_header().header;
+    HeaderQueue::default()
+        .configure(deps.as_mut().storage, config)
+        .unwrap();
+
+    let headers = mine_headers(&trusted, 10);
+    let mut btc = Bitcoin::default();
+    btc.relay_headers(deps.as_mut().storage, headers.clone())
+        .unwrap();
+
+    let tip = query_header_tip(deps.as_ref().storage).unwrap();
+    let last = headers.last().unwrap();
+    assert_eq!(tip.hash.0, last.block_hash());
+    assert_eq!(tip.height, 1010);
+    assert_eq!(tip.time, last.time());
+
+    // the chain work grows by the work of each header added on top
+    let next = mine_headers(last, 1);
+    btc.relay_headers(deps.as_mut().storage, next.clone())
+        .unwrap();
+    let next_tip = query_header_tip(deps.as_ref().storage).unwrap();
+    assert_eq!(next_tip.hash.0, next[0].block_hash());
+    assert_eq!(
+        cosmwasm_std::Uint256::from_str(&next_tip.chain_work).unwrap(),
+        cosmwasm_std::Uint256::from_str(&tip.chain_work).unwrap()
+            + cosmwasm_std::Uint256::from_be_bytes(next[0].work().to_be_bytes())
+    );
+}
+
+#[test]
+fn header_config_max_reorg_depth_default() {
+    // configs stored before `max_reorg_depth` was added load with the default
+    let mut config = serde_json::to_value(HeaderConfig::mainnet().unwrap()).unwrap();
+    config.as_object_mut().unwrap().remove("max_reorg_depth");
+    let config: HeaderConfig = serde_json::from_value(config).unwrap();
+    assert_eq!(config.max_reorg_depth, MAX_REORG_DEPTH);
+}
@@ -1,5 +1,6 @@
 mod bitcoin;
 mod checkpoint;
+mod emergency;
 mod header;
 pub mod helper;
 mod relay_deposit;
@@ -40,6 +40,7 @@ fn test_relay_headers() {
     };
     let header_config = HeaderConfig {
         max_length: 2000,
+        max_reorg_depth: 100,
         max_time_increase: 8 * 60 * 60,
         trusted_height: 42,
         retarget_interval: 2016,
@@ -48,6 +49,7 @@ fn test_relay_headers() {
         max_target: 0x1d00ffff,
         retargeting: true,
         min_difficulty_blocks: false,
+        network: bitcoin::Network::Bitcoin,
         trusted_header: Adapter::from(trusted_header),
     };
 
@@ -163,6 +165,7 @@ fn test_relay_headers_2() {
 
     let header_config = HeaderConfig {
         max_length: 2000,
+        max_reorg_depth: 100,
         max_time_increase: 8 * 60 * 60,
         trusted_height: 852711,
         retarget_interval: 2016,
@@ -171,6 +174,7 @@ fn test_relay_headers_2() {
         max_target: 0x1d00ffff,
         retargeting: true,
         min_difficulty_blocks: false,
+        network: bitcoin::Network::Bitcoin,
         trusted_header: Adapter::from(trusted_header),
     };
 
@@ -13,6 +13,8 @@ fn mock_signatory_set() -> SignatorySet {
         present_vp: 12000,
         possible_vp: 12000,
         index: 25,
+        pruned_vp: 0,
+        threshold: Some((2, 3)),
         signatories: vec![
             Signatory {
                 voting_power: 3000,
@@ -100,6 +102,8 @@ fn from_script() {
             present_vp: 7343255,
             possible_vp: 7343255,
             index: 0,
+            pruned_vp: 0,
+            threshold: Some((2, 3)),
             signatories: vec![
                 Signatory {
                     voting_power: 1171513,
@@ -251,3 +255,34 @@ fn from_script() {
     );
     assert_eq!(commitment, vec![0]);
 }
+
+#[test]
+fn test_commitment() {
+    let sigset = mock_signatory_set();
+    let commitment = sigset.commitment();
+
+    // the commitment survives a serialization round trip
+    let bytes = cosmwasm_std::to_json_vec(&sigset).unwrap();
+    let decoded: SignatorySet = cosmwasm_std::from_json(bytes).unwrap();
+    assert_eq!(decoded.commitment(), commitment);
+
+    // and doesn't depend on the order the signatories were inserted in
+    let mut reordered = sigset.clone();
+    reordered.signatories.reverse();
+    assert_eq!(reordered.commitment(), commitment);
+    reordered.signatories.swap(0, 1);
+    assert_eq!(reordered.commitment(), commitment);
+
+    // but changes with the voting power or the recorded threshold
+    let mut changed = sigset.clone();
+    changed.signatories[0].voting_power += 1;
+    assert_ne!(changed.commitment(), commitment);
+    let mut changed = sigset.clone();
+    changed.threshold = Some((1, 2));
+    assert_ne!(changed.commitment(), commitment);
+
+    // sets created before the threshold was recorded use the default
+    let mut legacy = sigset.clone();
+    legacy.threshold = None;
+    assert_eq!(legacy.commitment(), commitment);
+}
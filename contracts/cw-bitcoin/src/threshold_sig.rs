@@ -9,7 +9,7 @@ use bitcoin::secp256k1::{
 };
 use cosmwasm_schema::cw_serde;
 use cosmwasm_schema::serde::{Deserialize, Serialize};
-use cosmwasm_std::Api;
+use cosmwasm_std::{Api, VerificationError};
 
 // TODO: update for taproot-based design (musig rounds, fallback path)
 
@@ -20,6 +20,34 @@ pub type Message = [u8; MESSAGE_SIZE];
 #[cw_serde]
 pub struct Signature(#[serde(serialize_with = "<[_]>::serialize")] pub Vec<u8>);
 
+/// The encoding of signatures submitted by a signer.
+#[cw_serde]
+#[derive(Default)]
+pub enum SignatureEncoding {
+    /// 64-byte compact encoding, as produced by `signer::sign`.
+    #[default]
+    Compact,
+    /// DER encoding, as produced by most other ECDSA libraries.
+    Der,
+}
+
+impl Signature {
+    /// Parses a signature in the given encoding, normalizing it to the
+    /// low-S compact form stored in signature sets.
+    ///
+    /// Returns `ContractError::Secp` if the bytes are not a valid signature in
+    /// that encoding.
+    pub fn from_encoded(bytes: &[u8], encoding: &SignatureEncoding) -> ContractResult<Self> {
+        let mut sig = match encoding {
+            SignatureEncoding::Compact => ecdsa::Signature::from_compact(bytes)?,
+            SignatureEncoding::Der => ecdsa::Signature::from_der(bytes)?,
+        };
+        sig.normalize_s();
+
+        Ok(Signature(sig.serialize_compact().to_vec()))
+    }
+}
+
 /// A compressed secp256k1 public key.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Ord, Deserialize, Serialize)]
 #[serde(crate = "cosmwasm_schema::serde")]
@@ -145,7 +173,6 @@ impl ThresholdSig {
     /// in the given `SignatorySet`.
     pub fn from_sigset(signatories: &SignatorySet) -> Self {
         let mut ts = ThresholdSig::default();
-        let mut total_vp = 0;
 
         for signatory in signatories.iter() {
             ts.sigs.push((
@@ -157,11 +184,9 @@ impl ThresholdSig {
             ));
 
             ts.len += 1;
-            total_vp += signatory.voting_power;
         }
 
-        ts.threshold =
-            ((total_vp as u128) * SIGSET_THRESHOLD.0 as u128 / SIGSET_THRESHOLD.1 as u128) as u64;
+        ts.threshold = signatories.signature_threshold(SIGSET_THRESHOLD);
 
         ts
     }
@@ -197,6 +222,19 @@ impl ThresholdSig {
         self.signed > self.threshold
     }
 
+    /// Returns the most voting power which could end up signing the message:
+    /// the power which has already signed, plus that of each signer which has
+    /// not yet signed and for which `can_sign` returns `true`.
+    pub fn possible_power(&self, can_sign: impl Fn(&Pubkey) -> bool) -> u64 {
+        self.signed
+            + self
+                .sigs
+                .iter()
+                .filter(|(pubkey, share)| share.sig.is_none() && can_sign(pubkey))
+                .map(|(_, share)| share.power)
+                .sum::<u64>()
+    }
+
     /// Returns a vector of `(pubkey, signature)` tuples for each signer who has
     /// signed the message.    
     pub fn sigs(&self) -> Vec<(Pubkey, Signature)> {
@@ -257,18 +295,26 @@ impl ThresholdSig {
 
     /// Verifies the given signature for the message, using the given signer's
     /// pubkey.
-    /// Verifies the given signature for the message, using the given signer's
-    /// pubkey.
+    ///
+    /// Returns `ContractError::Secp` if the signature is malformed or does not
+    /// match, so a signer can't add garbage to the aggregate.
     pub fn secp_verify(
         api: &dyn Api,
         msg: &[u8],
         pubkey: &Pubkey,
         sig: &Signature,
     ) -> ContractResult<()> {
-        let verified = api.secp256k1_verify(msg, &sig.0, pubkey.as_slice())?;
+        let verified = api
+            .secp256k1_verify(msg, &sig.0, pubkey.as_slice())
+            .map_err(|err| match err {
+                VerificationError::InvalidSignatureFormat => {
+                    ContractError::Secp(secp256k1::Error::InvalidSignature)
+                }
+                err => err.into(),
+            })?;
 
         if !verified {
-            return Err(ContractError::App("Can not verify signature".to_string()));
+            return Err(ContractError::Secp(secp256k1::Error::IncorrectSignature));
         }
 
         Ok(())
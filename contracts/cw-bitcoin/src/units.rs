@@ -0,0 +1,82 @@
+use cosmwasm_std::{Uint128, Uint64};
+
+use crate::error::{ContractError, ContractResult};
+
+/// An amount of BTC in satoshis, as held in Bitcoin outputs and used by the
+/// satoshi-denominated config parameters (e.g. `min_deposit_amount`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Sats(pub u64);
+
+/// An amount of nBTC in the units held in accounts and the fee pool, each
+/// worth `1 / units_per_sat` satoshis (micro-satoshis with the default
+/// config). Config parameters such as `transfer_fee` are in these units.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MSats(pub u64);
+
+impl Sats {
+    /// Converts to nBTC units, failing with `ContractError::Overflow` if the
+    /// result does not fit in a `u64`.
+    pub fn to_msats(self, units_per_sat: u64) -> ContractResult<MSats> {
+        Ok(MSats(
+            Uint64::from(self.0)
+                .checked_mul(Uint64::from(units_per_sat))?
+                .u64(),
+        ))
+    }
+
+    pub fn checked_add(self, other: Sats) -> ContractResult<Sats> {
+        Ok(Sats(
+            Uint64::from(self.0)
+                .checked_add(Uint64::from(other.0))?
+                .u64(),
+        ))
+    }
+}
+
+impl MSats {
+    /// Converts to satoshis, rounding partial satoshis down.
+    pub fn to_sats(self, units_per_sat: u64) -> ContractResult<Sats> {
+        self.0
+            .checked_div(units_per_sat)
+            .map(Sats)
+            .ok_or_else(|| ContractError::App("units_per_sat must be non-zero".to_string()))
+    }
+
+    pub fn checked_add(self, other: MSats) -> ContractResult<MSats> {
+        Ok(MSats(
+            Uint64::from(self.0)
+                .checked_add(Uint64::from(other.0))?
+                .u64(),
+        ))
+    }
+
+    pub fn checked_sub(self, other: MSats) -> ContractResult<MSats> {
+        Ok(MSats(
+            Uint64::from(self.0)
+                .checked_sub(Uint64::from(other.0))?
+                .u64(),
+        ))
+    }
+}
+
+impl From<MSats> for Uint128 {
+    fn from(units: MSats) -> Self {
+        Uint128::from(units.0)
+    }
+}
+
+impl TryFrom<Uint128> for MSats {
+    type Error = ContractError;
+
+    fn try_from(amount: Uint128) -> ContractResult<Self> {
+        Ok(MSats(u64::try_from(amount.u128())?))
+    }
+}
+
+impl TryFrom<MSats> for i64 {
+    type Error = ContractError;
+
+    fn try_from(units: MSats) -> ContractResult<Self> {
+        Ok(i64::try_from(units.0)?)
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
-    adapter::Adapter, error::ContractResult, MAX_LENGTH, MAX_TARGET, MAX_TIME_INCREASE,
-    RETARGET_INTERVAL, TARGET_SPACING, TARGET_TIMESPAN,
+    adapter::Adapter, error::ContractResult, MAX_LENGTH, MAX_REORG_DEPTH, MAX_TARGET,
+    MAX_TIME_INCREASE, RETARGET_INTERVAL, TARGET_SPACING, TARGET_TIMESPAN,
 };
 use bitcoin::{util::uint::Uint256, BlockHash, BlockHeader, TxMerkleNode};
 use serde::{Deserialize, Serialize};
@@ -15,6 +15,9 @@ pub struct HeaderConfig {
     /// The maximum number of headers that can be stored in the header queue
     /// before pruning.
     pub max_length: u64,
+    /// The maximum number of headers below the current tip that a competing
+    /// chain may replace.
+    pub max_reorg_depth: u32,
     /// The maximum amount of time (in seconds) that can pass between the
     /// timestamp of the last header in the header queue and the timestamp of
     /// the next header to be added.
@@ -195,6 +198,7 @@ pub fn newHeaderConfig(height: u32, block_header: JsValue) -> ContractResult<Hea
 
     Ok(HeaderConfig {
         max_length: MAX_LENGTH,
+        max_reorg_depth: MAX_REORG_DEPTH,
         max_time_increase: MAX_TIME_INCREASE,
         trusted_height: height,
         retarget_interval: RETARGET_INTERVAL,
@@ -24,6 +24,7 @@ pub const HEADER_BATCH_SIZE: usize = 250;
 pub const MAX_SIGNATORIES: u64 = 20;
 
 pub const MAX_LENGTH: u64 = 24_192; // ~6 months
+pub const MAX_REORG_DEPTH: u32 = 100;
 pub const MAX_RELAY: u64 = 1000;
 pub const MAX_TIME_INCREASE: u32 = 2 * 60 * 60;
 pub const RETARGET_INTERVAL: u32 = 2016;